@@ -1,23 +1,45 @@
 use crate::canvas::Canvas;
 use crate::colors::Color;
-use crate::matrix::Matrix;
-use crate::ray::Ray;
+use crate::matrix::matrix4::{CachedTransform, Matrix4};
+use crate::ray::{Ray, RayDifferential};
+use crate::sampling::random;
+use crate::spectrum::{sample_wavelength, white_point, SpectralFilm};
 use crate::transform::Transform;
-use crate::vectors::{point, vector, Tuple};
+use crate::vectors::typed::Point3;
+use crate::vectors::{point, vector};
 use crate::world::World;
 use std::f64;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Mutex};
 use std::thread;
+use std::time::Instant;
 extern crate rayon;
 use rayon::prelude::*;
 
+pub mod progress;
+pub mod tiles;
+
+use self::progress::{CancelToken, Progress, RenderObserver};
+use self::tiles::{Tile, TileOrder, TileStats};
+
 pub struct Camera {
   hsize: usize,
   vsize: usize,
   half_width: f64,
   half_height: f64,
   fov: f64,
-  pub transform: Matrix,
+  // set through set_transform, which keeps the inverse up to date
+  transform: CachedTransform,
   pixel_size: f64,
+  pub samples: usize,
+  // wavelengths traced per sample in spectral mode; 0 renders in rgb
+  pub spectral_samples: usize,
+  // what spectral films balance against, worked out once per camera
+  white_point: Color,
+  // render splits the image into square tiles this many pixels across,
+  // handed out to the worker threads in tile_order
+  pub tile_size: usize,
+  pub tile_order: TileOrder,
 }
 
 impl Camera {
@@ -43,56 +65,240 @@ impl Camera {
       half_height: half_height,
       fov: fov,
       pixel_size: pixel_size,
-      transform: Matrix::identity(4),
+      transform: CachedTransform::identity(),
+      samples: 1,
+      spectral_samples: 0,
+      white_point: white_point(),
+      tile_size: 16,
+      tile_order: TileOrder::Morton,
     };
   }
 
-  pub fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
-    let xoffset = (x as f64 + 0.5) * self.pixel_size;
-    let yoffset = (y as f64 + 0.5) * self.pixel_size;
+  pub fn set_transform(&mut self, transform: Matrix4) {
+    self.transform = CachedTransform::new(transform);
+  }
 
-    let world_x = self.half_width - xoffset;
-    let world_y = self.half_height - yoffset;
+  pub fn transform(&self) -> &Matrix4 {
+    return &self.transform.transform;
+  }
 
-    let pixel = Matrix::mult_4x4_by_1d(
-      &Matrix::inverse(&self.transform),
-      &point(world_x, world_y, -1.),
-    );
-    let origin = Matrix::mult_4x4_by_1d(&Matrix::inverse(&self.transform), &point(0., 0., 0.));
-    let direction = pixel.sub(origin).norm();
+  pub fn ray_for_pixel(&self, x: usize, y: usize) -> Ray {
+    return self.ray_for_sample(x, y, 0.5, 0.5);
+  }
 
-    return Ray::new(origin, direction);
+  // A ray through the point (dx, dy) inside pixel (x, y), where both
+  // offsets lie in [0, 1). The ray carries differentials towards the
+  // neighbouring pixels for texture filtering.
+  pub fn ray_for_sample(&self, x: usize, y: usize, dx: f64, dy: f64) -> Ray {
+    let inverse = &self.transform.inverse;
+    let origin = *inverse * Point3::new(0., 0., 0.);
+    let direction = |px: f64, py: f64| {
+      let world_x = self.half_width - px * self.pixel_size;
+      let world_y = self.half_height - py * self.pixel_size;
+      let pixel = *inverse * Point3::new(world_x, world_y, -1.);
+      return (pixel - origin).norm();
+    };
+
+    let px = x as f64 + dx;
+    let py = y as f64 + dy;
+    let mut r = Ray::new(origin, direction(px, py));
+    r.differential = Some(RayDifferential {
+      rx_origin: origin,
+      rx_direction: direction(px + 1., py),
+      ry_origin: origin,
+      ry_direction: direction(px, py + 1.),
+    });
+    return r;
   }
 
   pub fn render_line(&self, world: &World, line: usize) -> Vec<Color> {
     let mut out: Vec<Color> = Vec::new();
 
     for x in 0..(self.hsize / 1) {
-      let r = self.ray_for_pixel(x, line);
-      let c = world.color_at(r, 5); // maximum recursion depth for camera is 5
-      out.push(c);
+      out.push(self.render_pixel(world, x, line));
+    }
+
+    return out;
+  }
+
+  // A single sample goes through the pixel center; more samples are jittered
+  // across the pixel and averaged, which noisy integrators rely on.
+  pub fn render_pixel(&self, world: &World, x: usize, y: usize) -> Color {
+    if self.spectral_samples > 0 {
+      return self.render_spectral_pixel(world, x, y);
+    }
+
+    if self.samples <= 1 {
+      let r = self.ray_for_pixel(x, y);
+      return world.color_at(r, 5); // maximum recursion depth for camera is 5
     }
 
+    let mut sum = Color::new(0., 0., 0.);
+    for _i in 0..self.samples {
+      let r = self.ray_for_sample(x, y, random(), random());
+      sum = Color::add(sum, world.color_at(r, 5));
+    }
+    return Color::div(sum, self.samples as f64);
+  }
+
+  // Spectral mode: every sample is traced at a few stratified wavelengths,
+  // and the results are developed through CIE XYZ into rgb.
+  fn render_spectral_pixel(&self, world: &World, x: usize, y: usize) -> Color {
+    let mut film = SpectralFilm::new(self.white_point);
+    let samples = self.samples.max(1);
+
+    for _i in 0..samples {
+      let mut r = if samples == 1 {
+        self.ray_for_pixel(x, y)
+      } else {
+        self.ray_for_sample(x, y, random(), random())
+      };
+      for band in 0..self.spectral_samples {
+        let wavelength = sample_wavelength(band, self.spectral_samples, random());
+        r.wavelength = Some(wavelength);
+        film.add(wavelength, world.color_at(r, 5));
+      }
+    }
+    return film.to_rgb();
+  }
+
+  pub fn render_tile(&self, world: &World, tile: Tile) -> Vec<Color> {
+    let mut out = vec![Color::new(0., 0., 0.); tile.pixels()];
+    let mut rows: Vec<&mut [Color]> = out.chunks_mut(tile.width).collect();
+    self.render_tile_into(world, tile, &mut rows, &CancelToken::new());
     return out;
   }
 
+  // Renders a tile into its rows of the canvas, one slice per row. False if
+  // the render is cancelled before the tile is finished, which leaves the
+  // rows not yet reached untouched.
+  fn render_tile_into(
+    &self,
+    world: &World,
+    tile: Tile,
+    rows: &mut [&mut [Color]],
+    cancel: &CancelToken,
+  ) -> bool {
+    for (y, row) in (tile.y..tile.y + tile.height).zip(rows.iter_mut()) {
+      if cancel.is_cancelled() {
+        return false;
+      }
+      for (x, pixel) in (tile.x..tile.x + tile.width).zip(row.iter_mut()) {
+        *pixel = self.render_pixel(world, x, y);
+      }
+    }
+
+    return true;
+  }
+
   pub fn render(&self, world: World) -> Canvas {
+    return self.render_with_stats(&world).0;
+  }
+
+  pub fn render_with_stats(&self, world: &World) -> (Canvas, Vec<TileStats>) {
+    return self.render_with_progress(world, &|_: &Progress| {}, &CancelToken::new());
+  }
+
+  // Renders tile by tile, rayon stealing tiles for idle threads. The canvas
+  // is split up into disjoint row slices per tile, so every tile renders
+  // straight into its own part of the image. Finished tiles are passed to
+  // a thread of its own that tells the observer, so a slow observer never
+  // holds up the workers. The stats come back in the order the tiles
+  // finished, and only cover the tiles finished before any cancellation.
+  pub fn render_with_progress(
+    &self,
+    world: &World,
+    observer: &dyn RenderObserver,
+    cancel: &CancelToken,
+  ) -> (Canvas, Vec<TileStats>) {
     let mut canvas = Canvas::new(self.hsize, self.vsize);
+    // the stats so far, and the pixels they cover
+    let finished = Mutex::new((Vec::new(), 0));
+    let rays_per_pixel = self.samples.max(1) * self.spectral_samples.max(1);
+    let tiles = tiles::tiles(self.hsize, self.vsize, self.tile_size, self.tile_order);
+    let tiles_total = tiles.len();
+    let pixels_total = self.hsize * self.vsize;
+    let render_start = Instant::now();
 
-    let pixels: Vec<Vec<Color>> = (0..self.vsize)
-      .into_par_iter()
-      .map(|line| {
-        let out = self.render_line(&world, line);
-        return out;
+    // hand each tile the pieces of the canvas rows it covers, found by the
+    // tile's place in the grid
+    let size = self.tile_size.max(1);
+    let columns = (self.hsize + size - 1) / size;
+    let mut regions: Vec<Vec<&mut [Color]>> = (0..tiles_total).map(|_| vec![]).collect();
+    for (y, row) in canvas.rows_mut().enumerate() {
+      for (column, part) in row.chunks_mut(size).enumerate() {
+        regions[(y / size) * columns + column].push(part);
+      }
+    }
+    let work: Vec<(Tile, Vec<&mut [Color]>)> = tiles
+      .into_iter()
+      .map(|tile| {
+        let region = std::mem::take(&mut regions[(tile.y / size) * columns + tile.x / size]);
+        return (tile, region);
       })
       .collect();
 
-    for y in (0..self.vsize) {
-      for x in (0..self.hsize) {
-        canvas.set(x, y, pixels[y][x]);
-      }
+    let (sender, receiver) = mpsc::channel::<Progress>();
+    let panicked = thread::scope(|scope| {
+      // a panicking call is held on to until the render is done, and the
+      // tiles after it are still reported
+      let notifier = scope.spawn(move || {
+        let mut panicked = None;
+        for progress in receiver {
+          let call = panic::catch_unwind(AssertUnwindSafe(|| observer.tile_done(&progress)));
+          if let Err(payload) = call {
+            panicked = panicked.or(Some(payload));
+          }
+        }
+        return panicked;
+      });
+
+      work
+        .into_par_iter()
+        .with_max_len(1)
+        .for_each_with(sender, |sender, (tile, mut rows)| {
+          let start = Instant::now();
+          if !self.render_tile_into(world, tile, &mut rows, cancel) {
+            return;
+          }
+
+          let mut finished = finished.lock().unwrap();
+          let (stats, pixels_done) = &mut *finished;
+          let tile_stats = TileStats {
+            tile: tile,
+            elapsed: start.elapsed(),
+            camera_rays: tile.pixels() * rays_per_pixel,
+          };
+          stats.push(tile_stats);
+          *pixels_done += tile.pixels();
+
+          let elapsed = render_start.elapsed();
+          let seconds = elapsed.as_secs_f64().max(1e-9);
+          // sent while the stats are still locked, so the observer hears
+          // about the tiles in order
+          let progress = Progress {
+            tile: tile_stats,
+            tiles_done: stats.len(),
+            tiles_total: tiles_total,
+            pixels_done: *pixels_done,
+            pixels_total: pixels_total,
+            elapsed: elapsed,
+            camera_rays_per_second: (*pixels_done * rays_per_pixel) as f64 / seconds,
+            eta: elapsed.mul_f64((pixels_total - *pixels_done) as f64 / *pixels_done as f64),
+          };
+          sender.send(progress).unwrap();
+        });
+
+      // the workers' senders are gone, so the notifier finishes once it has
+      // caught up
+      return notifier.join().unwrap();
+    });
+    if let Some(payload) = panicked {
+      panic::resume_unwind(payload);
     }
-    return canvas;
+
+    let finished = finished.into_inner().unwrap();
+    return (canvas, finished.0);
   }
 }
 
@@ -107,7 +313,7 @@ fn constructing_a_camera() {
   assert_eq!(c.hsize, 160);
   assert_eq!(c.vsize, 120);
   assert_eq!(c.fov, f64::consts::PI / 2.);
-  assert_eq!(Matrix::equals(&c.transform, &Matrix::identity(4)), true);
+  assert_eq!(Matrix4::equals(c.transform(), &Matrix4::identity()), true);
 }
 
 #[test]
@@ -127,8 +333,8 @@ fn constructing_a_ray_through_center_of_canvas() {
   let c = Camera::new(201, 101, f64::consts::PI / 2.);
   let r = c.ray_for_pixel(100, 50);
 
-  assert_eq!(r.origin.equals(point(0., 0., 0.)), true);
-  assert_eq!(r.direction.equals(vector(0., 0., -1.)), true);
+  assert_eq!(r.origin.to_tuple().equals(point(0., 0., 0.)), true);
+  assert_eq!(r.direction.to_tuple().equals(vector(0., 0., -1.)), true);
 }
 
 #[test]
@@ -136,9 +342,10 @@ fn constructing_a_ray_through_corner_of_canvas() {
   let c = Camera::new(201, 101, f64::consts::PI / 2.);
   let r = c.ray_for_pixel(0, 0);
 
-  assert_eq!(r.origin.equals(point(0., 0., 0.)), true);
+  assert_eq!(r.origin.to_tuple().equals(point(0., 0., 0.)), true);
   assert_eq!(
     r.direction
+      .to_tuple()
       .approx_equals(vector(0.66519, 0.33259, -0.66851)),
     true
   );
@@ -147,15 +354,18 @@ fn constructing_a_ray_through_corner_of_canvas() {
 #[test]
 fn constructing_a_ray_when_the_camera_is_transformed() {
   let mut c = Camera::new(201, 101, f64::consts::PI / 2.);
-  c.transform = Transform::new()
-    .rotate_y(f64::consts::PI / 4.)
-    .translate(0., -2., 5.)
-    .transform;
+  c.set_transform(
+    Transform::new()
+      .rotate_y(f64::consts::PI / 4.)
+      .translate(0., -2., 5.)
+      .transform,
+  );
   let r = c.ray_for_pixel(100, 50);
 
-  assert_eq!(r.origin.equals(point(0., 2., -5.)), true);
+  assert_eq!(r.origin.to_tuple().equals(point(0., 2., -5.)), true);
   assert_eq!(
     r.direction
+      .to_tuple()
       .approx_equals(vector(2.0f64.sqrt() / 2., 0., -2.0f64.sqrt() / 2.)),
     true
   );
@@ -169,7 +379,7 @@ fn rendering_a_world_with_a_camera() {
   let to = point(0., 0., 0.);
   let up = vector(0., 1., 0.);
 
-  c.transform = Transform::view_transform(from, to, up);
+  c.set_transform(Transform::view_transform(from, to, up));
 
   let mut image = c.render(world);
   let pixel = image.get(5, 5);
@@ -179,3 +389,183 @@ fn rendering_a_world_with_a_camera() {
     true
   );
 }
+
+#[test]
+fn rendering_averages_multiple_samples_per_pixel() {
+  let world = World::default_world();
+  let mut c = Camera::new(101, 101, f64::consts::PI / 2.);
+  c.set_transform(Transform::view_transform(
+    point(0., 0., -5.),
+    point(0., 0., 0.),
+    vector(0., 1., 0.),
+  ));
+  c.samples = 16;
+
+  // the center pixel only covers the flat-ish middle of the sphere
+  let pixel = c.render_pixel(&world, 50, 50);
+
+  assert_eq!((pixel.r - 0.38066).abs() < 0.01, true);
+  assert_eq!((pixel.g - 0.47583).abs() < 0.01, true);
+  assert_eq!((pixel.b - 0.2855).abs() < 0.01, true);
+}
+
+#[test]
+fn spectral_mode_renders_a_white_world_white() {
+  let mut world = World::new();
+  world.set_environment(crate::environment::EnvironmentMap::uniform(Color::new(
+    1., 1., 1.,
+  )));
+  let mut c = Camera::new(11, 11, f64::consts::PI / 2.);
+  c.spectral_samples = 128;
+  crate::sampling::seed(34);
+
+  // the wavelengths are jittered, so white is only reached on average
+  let color = c.render_pixel(&world, 5, 5);
+
+  assert_eq!((color.r - 1.).abs() < 0.05, true);
+  assert_eq!((color.g - 1.).abs() < 0.05, true);
+  assert_eq!((color.b - 1.).abs() < 0.05, true);
+}
+
+#[test]
+fn spectral_mode_keeps_the_hue_of_rgb_materials() {
+  let mut world = World::new();
+  world.set_light(crate::light::PointLight::new(
+    Point3::new(-10., 10., -10.),
+    Color::new(1., 1., 1.),
+  ));
+  let mut ball = crate::shape::Shape::new(crate::shape::ShapeType::Sphere);
+  ball.material.color = Color::new(0.1, 0.8, 0.1);
+  world.add_object(ball);
+  let mut c = Camera::new(11, 11, f64::consts::PI / 2.);
+  c.set_transform(Transform::view_transform(
+    point(0., 0., -5.),
+    point(0., 0., 0.),
+    vector(0., 1., 0.),
+  ));
+  c.spectral_samples = 64;
+  crate::sampling::seed(34);
+
+  let color = c.render_pixel(&world, 5, 5);
+
+  assert_eq!(color.g > color.r, true);
+  assert_eq!(color.g > color.b, true);
+}
+
+#[test]
+fn camera_rays_carry_differentials_to_the_next_pixels() {
+  let c = Camera::new(201, 101, f64::consts::PI / 2.);
+  let r = c.ray_for_pixel(100, 50);
+  let d = r.differential.unwrap();
+
+  assert_eq!(
+    d.rx_direction
+      .to_tuple()
+      .equals(c.ray_for_pixel(101, 50).direction.to_tuple()),
+    true
+  );
+  assert_eq!(
+    d.ry_direction
+      .to_tuple()
+      .equals(c.ray_for_pixel(100, 51).direction.to_tuple()),
+    true
+  );
+  assert_eq!(d.rx_origin.to_tuple().equals(r.origin.to_tuple()), true);
+}
+
+#[test]
+fn tiled_rendering_matches_rendering_pixel_by_pixel() {
+  let world = World::default_world();
+  let mut c = Camera::new(23, 17, f64::consts::PI / 2.);
+  c.set_transform(Transform::view_transform(
+    point(0., 0., -5.),
+    point(0., 0., 0.),
+    vector(0., 1., 0.),
+  ));
+  c.tile_size = 5;
+
+  for order in vec![TileOrder::Scanline, TileOrder::Morton, TileOrder::Spiral] {
+    c.tile_order = order;
+    let (image, stats) = c.render_with_stats(&world);
+
+    for y in 0..17 {
+      for x in 0..23 {
+        assert_eq!(
+          Color::equals(image.pixel_at(x, y), c.render_pixel(&world, x, y)),
+          true
+        );
+      }
+    }
+    assert_eq!(stats.len(), 5 * 4);
+    assert_eq!(stats.iter().map(|s| s.camera_rays).sum::<usize>(), 23 * 17);
+  }
+}
+
+#[test]
+fn the_observer_hears_about_every_tile() {
+  let world = World::default_world();
+  let mut c = Camera::new(20, 12, f64::consts::PI / 2.);
+  c.tile_size = 4;
+  let seen = Mutex::new(Vec::new());
+
+  let observer = |p: &Progress| seen.lock().unwrap().push(*p);
+  let (_image, stats) = c.render_with_progress(&world, &observer, &CancelToken::new());
+
+  let seen = seen.into_inner().unwrap();
+  assert_eq!(stats.len(), 15);
+  assert_eq!(seen.len(), 15);
+  for (i, p) in seen.iter().enumerate() {
+    assert_eq!(p.tiles_done, i + 1);
+    assert_eq!(p.tiles_total, 15);
+    assert_eq!(p.camera_rays_per_second > 0., true);
+  }
+  let last = seen.last().unwrap();
+  assert_eq!(last.pixels_done, 20 * 12);
+  assert_eq!(last.fraction(), 1.);
+  assert_eq!(last.eta.as_secs_f64(), 0.);
+}
+
+#[test]
+fn a_cancelled_render_stops_early() {
+  let world = World::default_world();
+  let mut c = Camera::new(64, 64, f64::consts::PI / 2.);
+  c.tile_size = 4;
+  let cancel = CancelToken::new();
+
+  let observer = |_: &Progress| cancel.cancel();
+  let (_image, stats) = c.render_with_progress(&world, &observer, &cancel);
+
+  assert_eq!(cancel.is_cancelled(), true);
+  assert_eq!(stats.len() >= 1, true);
+  assert_eq!(stats.len() < 16 * 16, true);
+
+  let before = CancelToken::new();
+  before.cancel();
+  let (_image, stats) = c.render_with_progress(&world, &|_: &Progress| {}, &before);
+  assert_eq!(stats.len(), 0);
+}
+
+#[test]
+fn a_panicking_observer_leaves_the_other_tiles_alone() {
+  use std::panic::{catch_unwind, AssertUnwindSafe};
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  let world = World::default_world();
+  let mut c = Camera::new(8, 8, f64::consts::PI / 2.);
+  c.tile_size = 4;
+  let calls = AtomicUsize::new(0);
+
+  let observer = |_: &Progress| {
+    if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+      panic!("observer failed");
+    }
+  };
+  let result = catch_unwind(AssertUnwindSafe(|| {
+    c.render_with_progress(&world, &observer, &CancelToken::new())
+  }));
+
+  // the observer's own panic comes out, not a poisoned lock's
+  let payload = result.err().unwrap();
+  assert_eq!(payload.downcast_ref::<&str>(), Some(&"observer failed"));
+  assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
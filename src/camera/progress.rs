@@ -0,0 +1,79 @@
+use crate::camera::tiles::TileStats;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Where a render has got to, as of the tile that just finished.
+#[derive(Copy, Clone, Debug)]
+pub struct Progress {
+  pub tile: TileStats,
+  pub tiles_done: usize,
+  pub tiles_total: usize,
+  pub pixels_done: usize,
+  pub pixels_total: usize,
+  pub elapsed: Duration,
+  // a sample traced at several wavelengths counts once for each; the rays
+  // they spawn at each hit are not counted
+  pub camera_rays_per_second: f64,
+  // assuming the rest of the image costs what the finished part did
+  pub eta: Duration,
+}
+
+impl Progress {
+  pub fn fraction(&self) -> f64 {
+    return self.pixels_done as f64 / self.pixels_total as f64;
+  }
+}
+
+// Told about each tile as it finishes. Calls come from a thread of their
+// own, one at a time, with tiles_done counting up by one each call. The
+// workers carry on meanwhile, but the render only returns once the
+// observer has caught up.
+//
+// Closures taking a Progress are observers too.
+pub trait RenderObserver: Send + Sync {
+  fn tile_done(&self, progress: &Progress);
+}
+
+impl<F> RenderObserver for F
+where
+  F: Fn(&Progress) + Send + Sync,
+{
+  fn tile_done(&self, progress: &Progress) {
+    self(progress);
+  }
+}
+
+// Stops a render from another thread, or from an observer. Clones share
+// the flag. Workers check it between rows, so a cancelled render returns
+// soon after with only the tiles finished so far filled in.
+#[derive(Clone)]
+pub struct CancelToken {
+  cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+  pub fn new() -> CancelToken {
+    return CancelToken {
+      cancelled: Arc::new(AtomicBool::new(false)),
+    };
+  }
+
+  pub fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    return self.cancelled.load(Ordering::Relaxed);
+  }
+}
+
+#[test]
+fn clones_of_a_token_share_cancellation() {
+  let token = CancelToken::new();
+  let clone = token.clone();
+  assert_eq!(token.is_cancelled(), false);
+
+  clone.cancel();
+  assert_eq!(token.is_cancelled(), true);
+}
@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+use std::time::Duration;
+
+// The order tiles are handed out in. Neighbouring tiles share much of the
+// scene, so orders that keep them together keep it in cache; spiral also
+// fills in the middle of the image, usually the subject, first.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TileOrder {
+  Scanline,
+  // along a Z-order curve, which visits the tiles in small square groups
+  Morton,
+  // in rings outwards from the center tile
+  Spiral,
+}
+
+// A rectangle of pixels rendered as one piece of work. Tiles on the right
+// and bottom edges are cut short where the image ends.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile {
+  pub x: usize,
+  pub y: usize,
+  pub width: usize,
+  pub height: usize,
+}
+
+impl Tile {
+  pub fn pixels(&self) -> usize {
+    return self.width * self.height;
+  }
+}
+
+// How a tile went, for spotting the expensive parts of a scene.
+#[derive(Copy, Clone, Debug)]
+pub struct TileStats {
+  pub tile: Tile,
+  pub elapsed: Duration,
+  pub camera_rays: usize,
+}
+
+// Splits a width x height image into square tiles of the given size, in the
+// given order.
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+  let size = size.max(1);
+  let columns = (width + size - 1) / size;
+  let rows = (height + size - 1) / size;
+
+  let mut grid: Vec<(usize, usize)> = vec![];
+  for row in 0..rows {
+    for column in 0..columns {
+      grid.push((column, row));
+    }
+  }
+
+  match order {
+    TileOrder::Scanline => {}
+    TileOrder::Morton => grid.sort_by_key(|&(column, row)| morton(column, row)),
+    TileOrder::Spiral => {
+      let cx = (columns as f64 - 1.) / 2.;
+      let cy = (rows as f64 - 1.) / 2.;
+      let key = |&(column, row): &(usize, usize)| {
+        let dx = column as f64 - cx;
+        let dy = row as f64 - cy;
+        return (dx.abs().max(dy.abs()), dy.atan2(dx));
+      };
+      grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+    }
+  }
+
+  return grid
+    .into_iter()
+    .map(|(column, row)| Tile {
+      x: column * size,
+      y: row * size,
+      width: size.min(width - column * size),
+      height: size.min(height - row * size),
+    })
+    .collect();
+}
+
+// Interleaves the bits of x and y, x taking the lower bit of each pair.
+fn morton(x: usize, y: usize) -> u64 {
+  let mut code = 0;
+  for bit in 0..32 {
+    code |= ((x as u64 >> bit) & 1) << (2 * bit);
+    code |= ((y as u64 >> bit) & 1) << (2 * bit + 1);
+  }
+  return code;
+}
+
+#[test]
+fn tiles_cover_every_pixel_once() {
+  for order in vec![TileOrder::Scanline, TileOrder::Morton, TileOrder::Spiral] {
+    let mut covered = vec![0; 37 * 23];
+    for tile in tiles(37, 23, 8, order) {
+      for y in tile.y..tile.y + tile.height {
+        for x in tile.x..tile.x + tile.width {
+          covered[x + y * 37] += 1;
+        }
+      }
+    }
+    assert_eq!(covered.iter().all(|&c| c == 1), true);
+  }
+}
+
+#[test]
+fn morton_order_visits_tiles_in_square_groups() {
+  let order: Vec<(usize, usize)> = tiles(32, 32, 8, TileOrder::Morton)
+    .iter()
+    .take(5)
+    .map(|t| (t.x / 8, t.y / 8))
+    .collect();
+
+  assert_eq!(order, vec![(0, 0), (1, 0), (0, 1), (1, 1), (2, 0)]);
+}
+
+#[test]
+fn spiral_order_starts_in_the_middle() {
+  let order = tiles(50, 50, 10, TileOrder::Spiral);
+
+  assert_eq!((order[0].x, order[0].y), (20, 20));
+  // the eight tiles around it come next
+  for tile in &order[1..9] {
+    assert_eq!(
+      tile.x >= 10 && tile.x <= 30 && tile.y >= 10 && tile.y <= 30,
+      true
+    );
+  }
+}
@@ -1,9 +1,12 @@
 use crate::colors::Color;
 use std::error::Error;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::path::Path;
+use std::slice::ChunksMut;
 
+#[derive(Clone, Debug)]
 pub struct Canvas {
   width: usize,
   height: usize,
@@ -25,6 +28,88 @@ impl Canvas {
     };
   }
 
+  pub fn load(path: &str) -> io::Result<Canvas> {
+    let mut file = File::open(Path::new(path))?;
+    let mut bytes: Vec<u8> = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    return Canvas::from_ppm(&bytes);
+  }
+
+  // Parses a plain (P3) or binary (P6) ppm image. Channels are scaled by the
+  // file's maximum value into [0, 1].
+  pub fn from_ppm(bytes: &[u8]) -> io::Result<Canvas> {
+    let mut pos = 0;
+    let magic = read_token(bytes, &mut pos)?;
+    let binary = match &magic[..] {
+      "P3" => false,
+      "P6" => true,
+      _ => return Err(invalid_data("not a ppm image")),
+    };
+    let width = parse_number(&read_token(bytes, &mut pos)?)?;
+    let height = parse_number(&read_token(bytes, &mut pos)?)?;
+    let max = parse_number(&read_token(bytes, &mut pos)?)?;
+    if width == 0 || height == 0 || max == 0 || max > 65535 {
+      return Err(invalid_data("bad ppm header"));
+    }
+
+    // nothing is allocated for the header's size until the rest of the
+    // input could hold that many channels: binary ones take one or two
+    // bytes each, plain ones at least a digit and a separator
+    let count = match width.checked_mul(height).and_then(|n| n.checked_mul(3)) {
+      Some(count) => count,
+      None => return Err(invalid_data("ppm image too large")),
+    };
+    let size = if max < 256 { 1 } else { 2 };
+    let smallest = if binary { count.checked_mul(size) } else { count.checked_mul(2) };
+    match smallest {
+      Some(n) if n <= bytes.len() - pos + 1 => {}
+      _ => return Err(invalid_data("ppm size larger than its pixel data")),
+    }
+
+    let mut canvas = Canvas::new(width, height);
+    let mut channels: Vec<f64> = Vec::with_capacity(count);
+
+    if binary {
+      // a single whitespace byte separates the header from the pixels
+      pos += 1;
+      for _i in 0..count {
+        if pos + size > bytes.len() {
+          return Err(invalid_data("truncated ppm pixel data"));
+        }
+        let value = if size == 1 {
+          bytes[pos] as usize
+        } else {
+          (bytes[pos] as usize) << 8 | bytes[pos + 1] as usize
+        };
+        pos += size;
+        channels.push(value as f64 / max as f64);
+      }
+    } else {
+      for _i in 0..count {
+        let value = parse_number(&read_token(bytes, &mut pos)?)?;
+        channels.push(value as f64 / max as f64);
+      }
+    }
+
+    for i in 0..width * height {
+      canvas.canvas[i] = Color::new(channels[3 * i], channels[3 * i + 1], channels[3 * i + 2]);
+    }
+    return Ok(canvas);
+  }
+
+  pub fn width(&self) -> usize {
+    return self.width;
+  }
+
+  pub fn height(&self) -> usize {
+    return self.height;
+  }
+
+  pub fn pixel_at(&self, x: usize, y: usize) -> Color {
+    return self.canvas[x + y * self.width];
+  }
+
   pub fn get(&mut self, x: usize, y: usize) -> Color {
     return self.canvas[x + y * self.width];
   }
@@ -33,6 +118,12 @@ impl Canvas {
     self.canvas[x + y * self.width] = c;
   }
 
+  // The pixels row by row, as disjoint slices that can be handed to
+  // different threads.
+  pub fn rows_mut(&mut self) -> ChunksMut<'_, Color> {
+    return self.canvas.chunks_mut(self.width);
+  }
+
   pub fn write(&self) {
     let path = Path::new("./test-output.ppm");
     let display = path.display();
@@ -86,6 +177,41 @@ impl Canvas {
   }
 }
 
+fn invalid_data(message: &str) -> io::Error {
+  return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+// The next whitespace separated token, skipping # comments.
+fn read_token(bytes: &[u8], pos: &mut usize) -> io::Result<String> {
+  loop {
+    while *pos < bytes.len() && (bytes[*pos] as char).is_ascii_whitespace() {
+      *pos += 1;
+    }
+    if *pos < bytes.len() && bytes[*pos] == b'#' {
+      while *pos < bytes.len() && bytes[*pos] != b'\n' {
+        *pos += 1;
+      }
+    } else {
+      break;
+    }
+  }
+
+  let start = *pos;
+  while *pos < bytes.len() && !(bytes[*pos] as char).is_ascii_whitespace() {
+    *pos += 1;
+  }
+  if start == *pos {
+    return Err(invalid_data("truncated ppm image"));
+  }
+  return Ok(String::from_utf8_lossy(&bytes[start..*pos]).to_string());
+}
+
+fn parse_number(s: &str) -> io::Result<usize> {
+  return s
+    .parse::<usize>()
+    .map_err(|_| invalid_data("bad number in ppm image"));
+}
+
 #[test]
 fn it_creates_a_canvas() {
   let width = 10;
@@ -119,3 +245,53 @@ fn it_writes_to_ppm() {
 
   c.write();
 }
+
+#[test]
+fn reading_a_plain_ppm() {
+  let ppm = "P3\n# a comment\n2 2\n255\n255 0 0  0 255 0\n0 0 255  255 255 255\n";
+  let c = Canvas::from_ppm(ppm.as_bytes()).unwrap();
+
+  assert_eq!(c.width(), 2);
+  assert_eq!(c.height(), 2);
+  assert_eq!(Color::equals(c.pixel_at(0, 0), Color::new(1., 0., 0.)), true);
+  assert_eq!(Color::equals(c.pixel_at(1, 0), Color::new(0., 1., 0.)), true);
+  assert_eq!(Color::equals(c.pixel_at(0, 1), Color::new(0., 0., 1.)), true);
+  assert_eq!(Color::equals(c.pixel_at(1, 1), Color::new(1., 1., 1.)), true);
+}
+
+#[test]
+fn reading_a_binary_ppm_with_a_different_scale() {
+  let mut ppm = b"P6\n1 1\n100\n".to_vec();
+  ppm.extend_from_slice(&[100, 50, 0]);
+  let c = Canvas::from_ppm(&ppm).unwrap();
+
+  assert_eq!(Color::equals(c.pixel_at(0, 0), Color::new(1., 0.5, 0.)), true);
+}
+
+#[test]
+fn reading_a_broken_ppm_fails() {
+  assert_eq!(Canvas::from_ppm(b"P5\n1 1\n255\n0").is_err(), true);
+  assert_eq!(Canvas::from_ppm(b"P3\n2 2\n255\n0 0 0").is_err(), true);
+}
+
+#[test]
+fn reading_a_ppm_larger_than_its_data_fails() {
+  assert_eq!(Canvas::from_ppm(b"P6\n100000 100000\n255\n\0\0\0").is_err(), true);
+  let huge = format!("P3\n{} 3\n255\n0 0 0\n", usize::max_value() / 2);
+  assert_eq!(Canvas::from_ppm(huge.as_bytes()).is_err(), true);
+}
+
+#[test]
+fn writing_pixels_through_the_rows() {
+  let mut c = Canvas::new(4, 3);
+  let red = Color::new(1., 0., 0.);
+  for row in c.rows_mut().skip(1) {
+    row[1] = red;
+    row[2] = red;
+  }
+
+  assert_eq!(Color::equals(c.pixel_at(1, 1), red), true);
+  assert_eq!(Color::equals(c.pixel_at(2, 2), red), true);
+  assert_eq!(Color::equals(c.pixel_at(0, 1), Color::new(0., 0., 0.)), true);
+  assert_eq!(Color::equals(c.pixel_at(3, 2), Color::new(0., 0., 0.)), true);
+}
@@ -1,3 +1,5 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+
 #[derive(Copy, Clone, Debug)]
 pub struct Color {
   pub r: f64,
@@ -22,7 +24,8 @@ impl Color {
   pub fn mult(c: Color, a: f64) -> Color {
     return Color::new(c.r * a, c.g * a, c.b * a);
   }
-  pub fn dot(a: Color, b: Color) -> Color {
+  // Component by component, as when light is filtered by a surface.
+  pub fn hadamard(a: Color, b: Color) -> Color {
     return Color::new(a.r * b.r, a.g * b.g, a.b * b.b);
   }
   pub fn div(c: Color, a: f64) -> Color {
@@ -36,6 +39,62 @@ impl Color {
   }
 }
 
+impl Add for Color {
+  type Output = Color;
+  fn add(self, b: Color) -> Color {
+    return Color::add(self, b);
+  }
+}
+
+impl AddAssign for Color {
+  fn add_assign(&mut self, b: Color) {
+    *self = Color::add(*self, b);
+  }
+}
+
+impl Sub for Color {
+  type Output = Color;
+  fn sub(self, b: Color) -> Color {
+    return Color::sub(self, b);
+  }
+}
+
+impl Neg for Color {
+  type Output = Color;
+  fn neg(self) -> Color {
+    return self.negate();
+  }
+}
+
+impl Mul<f64> for Color {
+  type Output = Color;
+  fn mul(self, a: f64) -> Color {
+    return Color::mult(self, a);
+  }
+}
+
+impl Mul<Color> for f64 {
+  type Output = Color;
+  fn mul(self, c: Color) -> Color {
+    return Color::mult(c, self);
+  }
+}
+
+// Colors multiply component by component.
+impl Mul for Color {
+  type Output = Color;
+  fn mul(self, b: Color) -> Color {
+    return Color::hadamard(self, b);
+  }
+}
+
+impl Div<f64> for Color {
+  type Output = Color;
+  fn div(self, a: f64) -> Color {
+    return Color::div(self, a);
+  }
+}
+
 #[test]
 fn it_adds_color() {
   let c1 = Color::new(0.9, 0.6, 0.75);
@@ -67,7 +126,24 @@ fn it_scales_a_color() {
 fn it_multiplies_two_colors() {
   let c1 = Color::new(1.0, 0.2, 0.4);
   let c2 = Color::new(0.9, 1.0, 0.1);
-  let product = Color::dot(c1, c2);
+  let product = Color::hadamard(c1, c2);
   let expected = Color::new(0.9, 0.2, 0.04);
   assert_eq!(Color::equals(product, expected), true);
 }
+
+#[test]
+fn colors_work_with_operators() {
+  let c1 = Color::new(1.0, 0.2, 0.4);
+  let c2 = Color::new(0.9, 1.0, 0.1);
+
+  assert_eq!(Color::equals(c1 + c2, Color::add(c1, c2)), true);
+  assert_eq!(Color::equals(c1 - c2, Color::sub(c1, c2)), true);
+  assert_eq!(Color::equals(c1 * c2, Color::new(0.9, 0.2, 0.04)), true);
+  assert_eq!(Color::equals(c1 * 2., 2. * c1), true);
+  assert_eq!(Color::equals(c1 / 2., Color::new(0.5, 0.1, 0.2)), true);
+  assert_eq!(Color::equals(-c1, c1.negate()), true);
+
+  let mut sum = c1;
+  sum += c2;
+  assert_eq!(Color::equals(sum, Color::new(1.9, 1.2, 0.5)), true);
+}
@@ -0,0 +1,455 @@
+use crate::colors::Color;
+use crate::vectors::typed::Vector3;
+use std::f64;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+// An equirectangular (latitude/longitude) radiance map. Rows run from the
+// +y pole (v = 0) to the -y pole (v = 1), columns wrap around the y axis
+// starting and ending at +z.
+#[derive(Clone)]
+pub struct EnvironmentMap {
+  width: usize,
+  height: usize,
+  pixels: Vec<Color>,
+  marginal_cdf: Vec<f64>,
+  conditional_cdf: Vec<f64>,
+  total_weight: f64,
+  pub intensity: f64,
+  pub samples: usize,
+}
+
+impl EnvironmentMap {
+  pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> EnvironmentMap {
+    if width == 0 || height == 0 || pixels.len() != width * height {
+      panic!("bad environment map dimensions");
+    }
+
+    // each texel is weighted by its luminance and by the solid angle it
+    // covers, so sampling follows where the light actually comes from
+    let mut conditional_cdf: Vec<f64> = Vec::with_capacity(width * height);
+    let mut row_weights: Vec<f64> = Vec::with_capacity(height);
+
+    for y in 0..height {
+      let sin_theta = ((y as f64 + 0.5) / height as f64 * f64::consts::PI).sin();
+      let mut sum = 0.;
+      for x in 0..width {
+        sum += luminance(pixels[x + y * width]) * sin_theta;
+        conditional_cdf.push(sum);
+      }
+      row_weights.push(sum);
+    }
+
+    let mut marginal_cdf: Vec<f64> = Vec::with_capacity(height);
+    let mut total_weight = 0.;
+    for w in &row_weights {
+      total_weight += w;
+      marginal_cdf.push(total_weight);
+    }
+
+    return EnvironmentMap {
+      width: width,
+      height: height,
+      pixels: pixels,
+      marginal_cdf: marginal_cdf,
+      conditional_cdf: conditional_cdf,
+      total_weight: total_weight,
+      intensity: 1.0,
+      samples: 16,
+    };
+  }
+
+  pub fn uniform(color: Color) -> EnvironmentMap {
+    return EnvironmentMap::new(1, 1, vec![color]);
+  }
+
+  pub fn load(path: &str) -> io::Result<EnvironmentMap> {
+    let mut file = File::open(Path::new(path))?;
+    let mut bytes: Vec<u8> = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    return EnvironmentMap::from_hdr(&bytes);
+  }
+
+  // Parses a Radiance RGBE (.hdr) image, either flat or new-style run
+  // length encoded scanlines.
+  pub fn from_hdr(bytes: &[u8]) -> io::Result<EnvironmentMap> {
+    let mut pos = 0;
+    let mut saw_format = false;
+
+    // header lines up to the first blank line
+    loop {
+      let line = read_line(bytes, &mut pos)?;
+      if line.is_empty() {
+        break;
+      }
+      if line.starts_with("FORMAT=") {
+        if line != "FORMAT=32-bit_rle_rgbe" {
+          return Err(invalid_data("unsupported hdr pixel format"));
+        }
+        saw_format = true;
+      }
+    }
+    if !saw_format {
+      return Err(invalid_data("missing hdr format line"));
+    }
+
+    let resolution = read_line(bytes, &mut pos)?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    if fields.len() != 4 || fields[0] != "-Y" || fields[2] != "+X" {
+      return Err(invalid_data("unsupported hdr orientation"));
+    }
+    let height = parse_dimension(fields[1])?;
+    let width = parse_dimension(fields[3])?;
+
+    // the header is not trusted with an allocation until the rest of the
+    // file could hold that many pixels
+    let smallest_image = min_scanline_bytes(width).and_then(|n| n.checked_mul(height));
+    match smallest_image {
+      Some(n) if n <= bytes.len() - pos => {}
+      _ => return Err(invalid_data("hdr resolution larger than its pixel data")),
+    }
+
+    let mut pixels: Vec<Color> = Vec::new();
+    let mut scanline: Vec<[u8; 4]> = vec![[0; 4]; width];
+
+    for _y in 0..height {
+      read_scanline(bytes, &mut pos, &mut scanline)?;
+      for rgbe in &scanline {
+        pixels.push(rgbe_to_color(*rgbe));
+      }
+    }
+
+    return Ok(EnvironmentMap::new(width, height, pixels));
+  }
+
+  pub fn color_in_direction(&self, direction: Vector3) -> Color {
+    let (u, v) = direction_to_uv(direction.norm());
+    let x = ((u * self.width as f64) as usize).min(self.width - 1);
+    let y = ((v * self.height as f64) as usize).min(self.height - 1);
+
+    return Color::mult(self.pixels[x + y * self.width], self.intensity);
+  }
+
+  // Picks a direction proportionally to the map's radiance, using two
+  // uniform random numbers. Returns the direction, the radiance arriving
+  // from it and the solid angle pdf of having chosen it.
+  pub fn sample(&self, u1: f64, u2: f64) -> (Vector3, Color, f64) {
+    if self.total_weight <= 0. {
+      // a black map: fall back to uniform sphere sampling
+      let direction = uv_to_direction(u1, u2);
+      return (direction, Color::new(0., 0., 0.), 1. / (4. * f64::consts::PI));
+    }
+
+    let y = find_interval(&self.marginal_cdf, u1 * self.total_weight);
+    let row = &self.conditional_cdf[y * self.width..(y + 1) * self.width];
+    let row_total = row[self.width - 1];
+    let x = find_interval(row, u2 * row_total);
+
+    // jitter within the chosen texel so directions are continuous
+    let row_start = if x == 0 { 0. } else { row[x - 1] };
+    let texel_weight = row[x] - row_start;
+    let du = if texel_weight > 0. {
+      ((u2 * row_total - row_start) / texel_weight).max(0.).min(1.)
+    } else {
+      0.5
+    };
+    let row_below = if y == 0 { 0. } else { self.marginal_cdf[y - 1] };
+    let dv = ((u1 * self.total_weight - row_below) / row_total).max(0.).min(1.);
+
+    let u = (x as f64 + du) / self.width as f64;
+    let v = (y as f64 + dv) / self.height as f64;
+    let direction = uv_to_direction(u, v);
+
+    return (direction, self.color_in_direction(direction), self.pdf(direction));
+  }
+
+  pub fn pdf(&self, direction: Vector3) -> f64 {
+    if self.total_weight <= 0. {
+      return 1. / (4. * f64::consts::PI);
+    }
+
+    let (u, v) = direction_to_uv(direction.norm());
+    let x = ((u * self.width as f64) as usize).min(self.width - 1);
+    let y = ((v * self.height as f64) as usize).min(self.height - 1);
+    let row = &self.conditional_cdf[y * self.width..(y + 1) * self.width];
+    let texel_weight = if x == 0 { row[0] } else { row[x] - row[x - 1] };
+
+    let sin_theta = (v * f64::consts::PI).sin();
+    if sin_theta <= 0. {
+      return 0.;
+    }
+
+    // the pdf over the unit square, converted to solid angle
+    let pdf_uv = texel_weight / self.total_weight * (self.width * self.height) as f64;
+    return pdf_uv / (2. * f64::consts::PI * f64::consts::PI * sin_theta);
+  }
+}
+
+pub fn luminance(c: Color) -> f64 {
+  return 0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b;
+}
+
+pub fn direction_to_uv(direction: Vector3) -> (f64, f64) {
+  let u = 0.5 + direction.x.atan2(-direction.z) / (2. * f64::consts::PI);
+  let v = direction.y.max(-1.).min(1.).acos() / f64::consts::PI;
+  return (u - u.floor(), v);
+}
+
+pub fn uv_to_direction(u: f64, v: f64) -> Vector3 {
+  let theta = v * f64::consts::PI;
+  let phi = (u - 0.5) * 2. * f64::consts::PI;
+  return Vector3::new(
+    theta.sin() * phi.sin(),
+    theta.cos(),
+    -theta.sin() * phi.cos(),
+  );
+}
+
+// index of the first cdf entry strictly greater than value
+fn find_interval(cdf: &[f64], value: f64) -> usize {
+  let mut lo = 0;
+  let mut hi = cdf.len() - 1;
+  while lo < hi {
+    let mid = (lo + hi) / 2;
+    if cdf[mid] > value {
+      hi = mid;
+    } else {
+      lo = mid + 1;
+    }
+  }
+  return lo;
+}
+
+fn rgbe_to_color(rgbe: [u8; 4]) -> Color {
+  if rgbe[3] == 0 {
+    return Color::new(0., 0., 0.);
+  }
+  let f = 2.0f64.powi(rgbe[3] as i32 - 136);
+  return Color::new(
+    rgbe[0] as f64 * f,
+    rgbe[1] as f64 * f,
+    rgbe[2] as f64 * f,
+  );
+}
+
+fn invalid_data(message: &str) -> io::Error {
+  return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+fn read_line(bytes: &[u8], pos: &mut usize) -> io::Result<String> {
+  let start = *pos;
+  while *pos < bytes.len() && bytes[*pos] != b'\n' {
+    *pos += 1;
+  }
+  if *pos >= bytes.len() {
+    return Err(invalid_data("truncated hdr header"));
+  }
+  let line = String::from_utf8_lossy(&bytes[start..*pos]).trim().to_string();
+  *pos += 1;
+  return Ok(line);
+}
+
+fn parse_dimension(s: &str) -> io::Result<usize> {
+  return match s.parse::<usize>() {
+    Ok(n) if n > 0 => Ok(n),
+    _ => Err(invalid_data("bad hdr resolution")),
+  };
+}
+
+// The fewest bytes a scanline can take up: one run of 128 per channel for
+// run length encoded widths, four bytes per pixel otherwise.
+fn min_scanline_bytes(width: usize) -> Option<usize> {
+  if width >= 8 && width < 0x8000 {
+    return Some(4 + 4 * 2 * ((width + 127) / 128));
+  }
+  return width.checked_mul(4);
+}
+
+fn read_byte(bytes: &[u8], pos: &mut usize) -> io::Result<u8> {
+  if *pos >= bytes.len() {
+    return Err(invalid_data("truncated hdr pixel data"));
+  }
+  let b = bytes[*pos];
+  *pos += 1;
+  return Ok(b);
+}
+
+fn read_scanline(bytes: &[u8], pos: &mut usize, scanline: &mut Vec<[u8; 4]>) -> io::Result<()> {
+  let width = scanline.len();
+  let is_rle = width >= 8
+    && width < 0x8000
+    && *pos + 4 <= bytes.len()
+    && bytes[*pos] == 2
+    && bytes[*pos + 1] == 2
+    && bytes[*pos + 2] & 0x80 == 0;
+
+  if !is_rle {
+    for pixel in scanline.iter_mut() {
+      for channel in pixel.iter_mut() {
+        *channel = read_byte(bytes, pos)?;
+      }
+    }
+    return Ok(());
+  }
+
+  let encoded_width = ((bytes[*pos + 2] as usize) << 8) | bytes[*pos + 3] as usize;
+  if encoded_width != width {
+    return Err(invalid_data("hdr scanline width mismatch"));
+  }
+  *pos += 4;
+
+  // each of the four channels is run length encoded separately
+  for channel in 0..4 {
+    let mut x = 0;
+    while x < width {
+      let count = read_byte(bytes, pos)? as usize;
+      if count > 128 {
+        let run = count - 128;
+        if x + run > width {
+          return Err(invalid_data("bad hdr run length"));
+        }
+        let value = read_byte(bytes, pos)?;
+        for _i in 0..run {
+          scanline[x][channel] = value;
+          x += 1;
+        }
+      } else {
+        if count == 0 || x + count > width {
+          return Err(invalid_data("bad hdr run length"));
+        }
+        for _i in 0..count {
+          scanline[x][channel] = read_byte(bytes, pos)?;
+          x += 1;
+        }
+      }
+    }
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+fn hdr_header(width: usize, height: usize) -> Vec<u8> {
+  return format!(
+    "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+    height, width
+  )
+  .into_bytes();
+}
+
+#[test]
+fn it_parses_a_flat_hdr_image() {
+  let mut bytes = hdr_header(2, 1);
+  // 1.0 is mantissa 128 with exponent 129, 0.5 is 128 with exponent 128
+  bytes.extend_from_slice(&[128, 128, 128, 129, 128, 0, 0, 128]);
+
+  let map = EnvironmentMap::from_hdr(&bytes).unwrap();
+
+  assert_eq!(map.width, 2);
+  assert_eq!(map.height, 1);
+  assert_eq!(
+    Color::approx_equals(map.pixels[0], Color::new(1., 1., 1.)),
+    true
+  );
+  assert_eq!(
+    Color::approx_equals(map.pixels[1], Color::new(0.5, 0., 0.)),
+    true
+  );
+}
+
+#[test]
+fn it_parses_a_run_length_encoded_hdr_image() {
+  let mut bytes = hdr_header(8, 1);
+  bytes.extend_from_slice(&[2, 2, 0, 8]);
+  // red, green and blue as runs, exponent as literal values
+  bytes.extend_from_slice(&[136, 128]);
+  bytes.extend_from_slice(&[136, 0]);
+  bytes.extend_from_slice(&[136, 128]);
+  bytes.extend_from_slice(&[8, 129, 129, 129, 129, 128, 128, 128, 128]);
+
+  let map = EnvironmentMap::from_hdr(&bytes).unwrap();
+
+  assert_eq!(
+    Color::approx_equals(map.pixels[0], Color::new(1., 0., 1.)),
+    true
+  );
+  assert_eq!(
+    Color::approx_equals(map.pixels[7], Color::new(0.5, 0., 0.5)),
+    true
+  );
+}
+
+#[test]
+fn it_rejects_a_truncated_hdr_image() {
+  let mut bytes = hdr_header(2, 2);
+  bytes.extend_from_slice(&[128, 128, 128, 129]);
+
+  assert_eq!(EnvironmentMap::from_hdr(&bytes).is_err(), true);
+}
+
+#[test]
+fn it_rejects_a_resolution_larger_than_the_file() {
+  let mut bytes = hdr_header(100000, 100000);
+  bytes.extend_from_slice(&[128, 128, 128, 129]);
+  assert_eq!(EnvironmentMap::from_hdr(&bytes).is_err(), true);
+
+  let bytes = hdr_header(usize::max_value() / 2, 3);
+  assert_eq!(EnvironmentMap::from_hdr(&bytes).is_err(), true);
+}
+
+#[test]
+fn it_loads_an_hdr_file_from_disk() {
+  let mut bytes = hdr_header(2, 1);
+  bytes.extend_from_slice(&[128, 128, 128, 129, 128, 0, 0, 128]);
+  let path = std::env::temp_dir().join(format!("environment-{}.hdr", std::process::id()));
+  std::fs::write(&path, &bytes).unwrap();
+
+  let map = EnvironmentMap::load(path.to_str().unwrap());
+  std::fs::remove_file(&path).unwrap();
+  let map = map.unwrap();
+
+  assert_eq!(map.width, 2);
+  assert_eq!(map.height, 1);
+  assert_eq!(
+    Color::approx_equals(map.pixels[0], Color::new(1., 1., 1.)),
+    true
+  );
+  assert_eq!(
+    Color::approx_equals(map.pixels[1], Color::new(0.5, 0., 0.)),
+    true
+  );
+  assert_eq!(EnvironmentMap::load("no/such/map.hdr").is_err(), true);
+}
+
+#[test]
+fn directions_round_trip_through_uv() {
+  let d = Vector3::new(0.3, -0.5, 0.8).norm();
+  let (u, v) = direction_to_uv(d);
+
+  assert_eq!(uv_to_direction(u, v).to_tuple().equals(d.to_tuple()), true);
+}
+
+#[test]
+fn a_uniform_map_has_the_same_radiance_everywhere() {
+  let map = EnvironmentMap::uniform(Color::new(0.5, 0.25, 1.));
+
+  let c = map.color_in_direction(Vector3::new(1., 2., -3.));
+
+  assert_eq!(Color::equals(c, Color::new(0.5, 0.25, 1.)), true);
+}
+
+#[test]
+fn sampling_concentrates_on_bright_texels() {
+  let mut pixels = vec![Color::new(0., 0., 0.); 8 * 4];
+  pixels[5 + 1 * 8] = Color::new(10., 10., 10.);
+  let map = EnvironmentMap::new(8, 4, pixels);
+
+  for (u1, u2) in vec![(0.1, 0.9), (0.5, 0.5), (0.99, 0.01)] {
+    let (direction, radiance, pdf) = map.sample(u1, u2);
+    assert_eq!(Color::equals(radiance, Color::new(10., 10., 10.)), true);
+    assert_eq!(pdf > 0., true);
+    assert_eq!((map.pdf(direction) - pdf).abs() < 1e-9, true);
+  }
+}
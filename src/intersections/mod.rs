@@ -1,18 +1,23 @@
-use crate::ray::Ray;
-use crate::shape::plane::Plane;
-use crate::shape::sphere::Sphere;
+use crate::colors::Color;
+use crate::material::Material;
+use crate::light::surface_color;
+use crate::ray::{Ray, RayDifferential};
 use crate::shape::{Shape, ShapeType};
 use crate::transform::Transform;
-use crate::vectors::{dot, point, reflect, vector, Tuple};
-
-#[derive(Clone)]
-pub struct Intersection {
+use crate::vectors::typed::{Normal3, Point3, Vector3};
+use crate::vectors::{point, vector};
+use std::borrow::Cow;
+
+// Intersections borrow the shape they hit, so tracing a ray copies no
+// shapes or materials.
+#[derive(Copy, Clone)]
+pub struct Intersection<'a> {
   pub t: f64,
-  pub object: Shape,
+  pub object: &'a Shape,
 }
 
-impl Intersection {
-  pub fn new(t: f64, object: Shape) -> Intersection {
+impl<'a> Intersection<'a> {
+  pub fn new(t: f64, object: &'a Shape) -> Intersection<'a> {
     Intersection {
       t: t,
       object: object,
@@ -21,45 +26,159 @@ impl Intersection {
 }
 
 #[derive(Clone)]
-pub struct Intersections {
-  pub intersections: Vec<Intersection>,
+pub struct Intersections<'a> {
+  pub intersections: Vec<Intersection<'a>>,
 }
 
+// The hit object and the materials around it are borrowed from the world,
+// and only copied when a ray's wavelength changes how they look.
 #[derive(Clone)]
-pub struct Computations {
+pub struct Computations<'a> {
   pub t: f64,
-  pub object: Shape,
-  pub point: Tuple,
-  pub eyev: Tuple,
-  pub normalv: Tuple,
-  pub reflectv: Tuple,
+  pub object: Cow<'a, Shape>,
+  pub point: Point3,
+  pub eyev: Vector3,
+  pub normalv: Normal3,
+  pub reflectv: Vector3,
   pub inside: bool,
-  pub over_point: Tuple,
-  pub under_point: Tuple,
+  pub over_point: Point3,
+  pub under_point: Point3,
   pub n1: f64,
   pub n2: f64,
+  // the materials of the objects on either side of the hit, None outside
+  // every object
+  pub n1_material: Option<Cow<'a, Material>>,
+  pub n2_material: Option<Cow<'a, Material>>,
+  pub wavelength: Option<f64>,
+  pub differential: Option<RayDifferential>,
+  // whether the ray had already been scattered by a rough surface
+  pub scattered: bool,
+  // how far the hit point moves on the surface between neighbouring pixels,
+  // for rays that carry differentials
+  pub footprint: Option<(Vector3, Vector3)>,
+  // the object's pattern filtered over that footprint, looked up once here
+  // and used in place of the pattern at the hit point
+  pub filtered_color: Option<Color>,
+}
+
+impl<'a> Computations<'a> {
+  // The color of the surface at the hit, filtered when the ray carried
+  // differentials.
+  pub fn surface_color(&self) -> Color {
+    return match self.filtered_color {
+      Some(color) => color,
+      None => surface_color(&self.object.material, &self.object, self.point),
+    };
+  }
+
+  // The offset rays mirrored at points one pixel away, using the normals
+  // there so that curved mirrors spread the footprint out.
+  pub fn reflected_differential(&self) -> Option<RayDifferential> {
+    let d = self.differential?;
+    let (dpdx, dpdy) = self.footprint?;
+    let px = self.point + dpdx;
+    let py = self.point + dpdy;
+    return Some(RayDifferential {
+      rx_origin: px,
+      rx_direction: d.rx_direction.reflect(self.normal_near(px)),
+      ry_origin: py,
+      ry_direction: d.ry_direction.reflect(self.normal_near(py)),
+    });
+  }
+
+  // The offset rays refracted at points one pixel away. An offset ray that
+  // is totally internally reflected follows the mirror direction instead.
+  pub fn refracted_differential(&self) -> Option<RayDifferential> {
+    let d = self.differential?;
+    let (dpdx, dpdy) = self.footprint?;
+    let n_ratio = self.n1 / self.n2;
+    let bend = |direction: Vector3, normal: Normal3| {
+      match (-direction.norm()).refract(normal, n_ratio) {
+        Some(refracted) => refracted,
+        None => direction.reflect(normal),
+      }
+    };
+    let px = self.point + dpdx;
+    let py = self.point + dpdy;
+    return Some(RayDifferential {
+      rx_origin: px,
+      rx_direction: bend(d.rx_direction, self.normal_near(px)),
+      ry_origin: py,
+      ry_direction: bend(d.ry_direction, self.normal_near(py)),
+    });
+  }
+
+  fn normal_near(&self, p: Point3) -> Normal3 {
+    let normal = self.object.normal_at(p);
+    if self.inside {
+      return -normal;
+    }
+    return normal;
+  }
+
+  // The indices of refraction on either side of the hit for light of the
+  // given wavelength.
+  pub fn indices_at(&self, wavelength: Option<f64>) -> (f64, f64) {
+    let index = |m: &Option<Cow<Material>>| match m {
+      Some(m) => m.refractive_index_at(wavelength),
+      None => 1.0,
+    };
+    return (index(&self.n1_material), index(&self.n2_material));
+  }
+
+  pub fn is_dispersive(&self) -> bool {
+    let dispersive = |m: &Option<Cow<Material>>| match m {
+      Some(m) => m.is_dispersive(),
+      None => false,
+    };
+    return dispersive(&self.n1_material) || dispersive(&self.n2_material);
+  }
+}
+
+// Hit points are only as precise as the coordinates they are worked out
+// from, so rays leaving a surface start this far from it relative to the
+// size of those coordinates, rather than a fixed distance that is too small
+// for large scenes.
+const OFFSET_EPSILON: f64 = 1.0e-11;
+
+fn surface_offset(point: Point3, origin: Point3) -> f64 {
+  let largest = |p: Point3| p.x.abs().max(p.y.abs()).max(p.z.abs());
+  return OFFSET_EPSILON * (1. + largest(point).max(largest(origin)));
 }
 
-pub fn prepare_computations(i: Intersection, r: Ray, xs: Intersections) -> Computations {
+pub fn prepare_computations<'a>(
+  i: Intersection<'a>,
+  r: Ray,
+  xs: Intersections<'a>,
+) -> Computations<'a> {
   let t = i.t;
   let point = r.position(t);
-  let mut normalv = i.object.normal_at(point);
-  let eyev = r.direction.negate();
-  let mut inside = dot(normalv, eyev) < 0.;
+  // which side the ray is on, and where to start rays leaving the surface,
+  // depends on the true surface; shading uses the bumped normal
+  let mut geometric_normalv = i.object.normal_at(point);
+  let mut normalv = i.object.shading_normal_at(point);
+  let eyev = -r.direction;
+  let inside = geometric_normalv.dot(eyev) < 0.;
   if inside {
-    normalv = normalv.negate();
-  } else {
-    inside = false;
+    geometric_normalv = -geometric_normalv;
+    normalv = -normalv;
   }
-  let over_point = point.add(normalv.mult(1.0e-10));
-  let under_point = point.sub(normalv.mult(1.0e-10));
-
-  let reflectv = reflect(r.direction, normalv);
+  let offset = surface_offset(point, r.origin);
+  let over_point = point + geometric_normalv.to_vector() * offset;
+  let under_point = point - geometric_normalv.to_vector() * offset;
+
+  let reflectv = r.direction.reflect(normalv);
+  let footprint = match r.differential {
+    Some(d) => surface_footprint(point, geometric_normalv, d),
+    None => None,
+  };
 
-  let mut containers: Vec<Shape> = vec![];
+  let mut containers: Vec<&Shape> = vec![];
 
   let mut n1 = 1.0;
   let mut n2 = 1.0;
+  let mut n1_material = None;
+  let mut n2_material = None;
 
   let hit = xs.hit();
 
@@ -68,7 +187,8 @@ pub fn prepare_computations(i: Intersection, r: Ray, xs: Intersections) -> Compu
       if containers.len() == 0 {
         n1 = 1.;
       } else {
-        n1 = containers[containers.len() - 1].material.refractive_index;
+        n1 = containers[containers.len() - 1].material.refractive_index_at(r.wavelength);
+        n1_material = Some(Cow::Borrowed(&containers[containers.len() - 1].material));
       }
     }
 
@@ -84,22 +204,42 @@ pub fn prepare_computations(i: Intersection, r: Ray, xs: Intersections) -> Compu
     if index > 0 {
       containers.remove(index - 1);
     } else {
-      containers.push(intersect.object.clone());
+      containers.push(intersect.object);
     }
 
     if intersect.t == i.t {
       if containers.len() == 0 {
         n2 = 1.0;
       } else {
-        n2 = containers[containers.len() - 1].material.refractive_index;
+        n2 = containers[containers.len() - 1].material.refractive_index_at(r.wavelength);
+        n2_material = Some(Cow::Borrowed(&containers[containers.len() - 1].material));
       }
       break;
     }
   }
 
+  // rays carrying a wavelength see every color at that wavelength only
+  let object = match r.wavelength {
+    Some(w) => {
+      n1_material = n1_material.map(|m| Cow::Owned(m.at_wavelength(w)));
+      n2_material = n2_material.map(|m| Cow::Owned(m.at_wavelength(w)));
+      Cow::Owned(i.object.at_wavelength(w))
+    }
+    None => Cow::Borrowed(i.object),
+  };
+
+  // with a footprint, the pattern is averaged over the area of the surface
+  // the pixel covers
+  let filtered_color = match (&object.material.pattern, footprint) {
+    (Some(pattern), Some((dpdx, dpdy))) => {
+      Some(pattern.pattern_at_object_filtered(&object, point, dpdx, dpdy))
+    }
+    _ => None,
+  };
+
   return Computations {
     t: i.t,
-    object: i.object,
+    object: object,
     point: point,
     eyev: eyev,
     normalv: normalv,
@@ -109,43 +249,68 @@ pub fn prepare_computations(i: Intersection, r: Ray, xs: Intersections) -> Compu
     under_point: under_point,
     n1: n1,
     n2: n2,
+    n1_material: n1_material,
+    n2_material: n2_material,
+    wavelength: r.wavelength,
+    differential: r.differential,
+    scattered: r.scattered,
+    footprint: footprint,
+    filtered_color: filtered_color,
   };
 }
 
-impl Intersections {
-  pub fn new(intersections: Vec<Intersection>) -> Intersections {
+// Where the offset rays cross the plane tangent to the surface at the hit,
+// relative to the hit point.
+fn surface_footprint(
+  point: Point3,
+  normalv: Normal3,
+  d: RayDifferential,
+) -> Option<(Vector3, Vector3)> {
+  let cross = |origin: Point3, direction: Vector3| {
+    let denom = normalv.dot(direction);
+    if denom.abs() < 1e-12 {
+      return None;
+    }
+    let t = normalv.dot(point - origin) / denom;
+    return Some(origin + direction * t - point);
+  };
+
+  let dpdx = cross(d.rx_origin, d.rx_direction)?;
+  let dpdy = cross(d.ry_origin, d.ry_direction)?;
+  return Some((dpdx, dpdy));
+}
+
+impl<'a> Intersections<'a> {
+  pub fn new(intersections: Vec<Intersection<'a>>) -> Intersections<'a> {
     Intersections {
       intersections: intersections,
     }
   }
 
-  pub fn hit(&self) -> Intersections {
+  pub fn hit(&self) -> Intersections<'a> {
     let len = self.intersections.len();
 
     if len == 0 {
       return Intersections::new(vec![]);
     }
-    let mut hit = Intersection::new(
-      self.intersections[0].t,
-      self.intersections[0].object.clone(),
-    );
+    let mut hit = self.intersections[0];
 
     for intersect in &self.intersections {
       if hit.t < 0. || intersect.t < hit.t && intersect.t >= 0. {
-        hit = Intersection::new(intersect.t, intersect.object.clone());
+        hit = *intersect;
       }
     }
 
     if hit.t >= 0. {
-      return Intersections::new(vec![Intersection::new(hit.t, hit.object)]);
+      return Intersections::new(vec![hit]);
     } else {
       return Intersections::new(vec![]);
     }
   }
 }
 
-pub fn schlick(comps: Computations) -> f64 {
-  let mut cos = dot(comps.eyev, comps.normalv);
+pub fn schlick(comps: &Computations) -> f64 {
+  let mut cos = comps.normalv.dot(comps.eyev);
 
   // total internal reflection can only occur if n1 > n2
   if comps.n1 > comps.n2 {
@@ -167,7 +332,7 @@ pub fn schlick(comps: Computations) -> f64 {
 fn intersection_encapsulates_a_t_and_object() {
   let s = Shape::new(ShapeType::Sphere);
   let handle = s.handle;
-  let i = Intersection::new(3.5, s);
+  let i = Intersection::new(3.5, &s);
 
   assert_eq!(i.t, 3.5);
   assert_eq!(i.object.handle, handle);
@@ -177,8 +342,8 @@ fn intersection_encapsulates_a_t_and_object() {
 fn intersections_aggregate() {
   let s = Shape::new(ShapeType::Sphere);
   let s2 = s.clone();
-  let i1 = Intersection::new(1., s);
-  let i2 = Intersection::new(2., s2);
+  let i1 = Intersection::new(1., &s);
+  let i2 = Intersection::new(2., &s2);
 
   let xs = Intersections::new(vec![i1, i2]);
 
@@ -189,7 +354,7 @@ fn intersections_aggregate() {
 
 #[test]
 fn intersections_aggregate_2() {
-  let r = Ray::new(point(0., 0., 5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., 5.), Vector3::new(0., 0., 1.));
   let s = Shape::new(ShapeType::Sphere);
 
   let xs = s.intersects(r);
@@ -204,9 +369,9 @@ fn intersections_hit_when_all_intersections_are_postive() {
   let s = Shape::new(ShapeType::Sphere);
   let s2 = s.clone();
 
-  let i1 = Intersection::new(1., s);
+  let i1 = Intersection::new(1., &s);
   let handle = i1.object.handle;
-  let i2 = Intersection::new(2., s2);
+  let i2 = Intersection::new(2., &s2);
 
   let xs = Intersections::new(vec![i2, i1]);
   let hit = xs.hit();
@@ -221,8 +386,8 @@ fn intersections_hit_when_intersections_are_inside() {
   let s = Shape::new(ShapeType::Sphere);
   let s2 = s.clone();
 
-  let i1 = Intersection::new(-1., s);
-  let i2 = Intersection::new(1., s2);
+  let i1 = Intersection::new(-1., &s);
+  let i2 = Intersection::new(1., &s2);
   let handle = i2.object.handle;
 
   let xs = Intersections::new(vec![i2, i1]);
@@ -239,8 +404,8 @@ fn intersections_hit_when_intersections_are_negative() {
   let s = Shape::new(ShapeType::Sphere);
   let s2 = s.clone();
 
-  let i1 = Intersection::new(-2., s);
-  let i2 = Intersection::new(-1., s2);
+  let i1 = Intersection::new(-2., &s);
+  let i2 = Intersection::new(-1., &s2);
 
   let xs = Intersections::new(vec![i2, i1]);
   let hit = xs.hit();
@@ -256,10 +421,10 @@ fn intersections_hit_is_lowest_non_negative() {
   let s3 = s.clone();
   let s4 = s.clone();
 
-  let i1 = Intersection::new(5., s);
-  let i2 = Intersection::new(7., s2);
-  let i3 = Intersection::new(-3., s3);
-  let i4 = Intersection::new(2., s4);
+  let i1 = Intersection::new(5., &s);
+  let i2 = Intersection::new(7., &s2);
+  let i3 = Intersection::new(-3., &s3);
+  let i4 = Intersection::new(2., &s4);
   let handle = i4.object.handle;
 
   let xs = Intersections::new(vec![i1, i2, i3, i4]);
@@ -274,25 +439,25 @@ fn intersections_hit_is_lowest_non_negative() {
 #[test]
 
 fn precompute_the_state_of_an_intersection() {
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let s = Shape::new(ShapeType::Sphere);
-  let i = Intersection::new(4., s);
+  let i = Intersection::new(4., &s);
   let t = i.t;
   let xs = Intersections::new(vec![i.clone()]);
 
   let comps = prepare_computations(i, r, xs);
 
   assert_eq!(comps.t, t);
-  assert_eq!(comps.point.equals(point(0., 0., -1.)), true);
-  assert_eq!(comps.eyev.equals(vector(0., 0., -1.)), true);
-  assert_eq!(comps.normalv.equals(vector(0., 0., -1.)), true);
+  assert_eq!(comps.point.to_tuple().equals(point(0., 0., -1.)), true);
+  assert_eq!(comps.eyev.to_tuple().equals(vector(0., 0., -1.)), true);
+  assert_eq!(comps.normalv.to_tuple().equals(vector(0., 0., -1.)), true);
 }
 
 #[test]
 fn the_hit_when_an_intersection_occurs_on_outside() {
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let s = Shape::new(ShapeType::Sphere);
-  let i = Intersection::new(4., s);
+  let i = Intersection::new(4., &s);
   let xs = Intersections::new(vec![i.clone()]);
 
   let comps = prepare_computations(i, r, xs);
@@ -302,25 +467,25 @@ fn the_hit_when_an_intersection_occurs_on_outside() {
 
 #[test]
 fn the_hit_when_an_intersection_occurs_on_inside() {
-  let r = Ray::new(point(0., 0., 0.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
   let s = Shape::new(ShapeType::Sphere);
-  let i = Intersection::new(1., s);
+  let i = Intersection::new(1., &s);
   let xs = Intersections::new(vec![i.clone()]);
 
   let comps = prepare_computations(i, r, xs);
 
   assert_eq!(comps.inside, true);
-  assert_eq!(comps.point.equals(point(0., 0., 1.)), true);
-  assert_eq!(comps.eyev.equals(vector(0., 0., -1.)), true);
-  assert_eq!(comps.normalv.equals(vector(0., 0., -1.)), true);
+  assert_eq!(comps.point.to_tuple().equals(point(0., 0., 1.)), true);
+  assert_eq!(comps.eyev.to_tuple().equals(vector(0., 0., -1.)), true);
+  assert_eq!(comps.normalv.to_tuple().equals(vector(0., 0., -1.)), true);
 }
 
 #[test]
 fn the_hit_should_offset_the_point() {
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let mut s = Shape::new(ShapeType::Sphere);
-  s.transform = Transform::new().translate(0., 0., 1.).transform;
-  let i = Intersection::new(5., s);
+  s.set_transform(Transform::new().translate(0., 0., 1.).transform);
+  let i = Intersection::new(5., &s);
   let xs = Intersections::new(vec![i.clone()]);
 
   let comps = prepare_computations(i, r, xs);
@@ -332,27 +497,27 @@ fn the_hit_should_offset_the_point() {
 #[test]
 fn precompute_the_reflection_vector() {
   let half_root2 = 2.0f64.sqrt() / 2.0;
-  let r = Ray::new(point(0., 1., -1.), vector(0., -half_root2, half_root2));
+  let r = Ray::new(Point3::new(0., 1., -1.), Vector3::new(0., -half_root2, half_root2));
   let shape = Shape::new(ShapeType::Plane);
 
-  let i = Intersection::new(half_root2, shape);
+  let i = Intersection::new(half_root2, &shape);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
 
   assert_eq!(
-    comps.reflectv.equals(vector(0.0, half_root2, half_root2)),
+    comps.reflectv.to_tuple().equals(vector(0.0, half_root2, half_root2)),
     true
   );
 }
 
 #[test]
 fn the_under_point_is_offset_below_the_surface() {
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.0));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.0));
   let mut shape = Shape::glass_sphere();
   let tx = Transform::new().translate(0., 0., 1.).transform;
   shape.set_transform(tx);
 
-  let i = Intersection::new(5.0, shape);
+  let i = Intersection::new(5.0, &shape);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
 
@@ -362,12 +527,12 @@ fn the_under_point_is_offset_below_the_surface() {
 #[test]
 fn schlick_approximation_under_total_internal_reflection() {
   let shape = Shape::glass_sphere();
-  let r = Ray::new(point(0., 0., 2.0f64.sqrt() / 2.), vector(0., 1., 0.));
-  let i1 = Intersection::new(-2.0f64.sqrt() / 2., shape.clone());
-  let i2 = Intersection::new(2.0f64.sqrt() / 2., shape.clone());
+  let r = Ray::new(Point3::new(0., 0., 2.0f64.sqrt() / 2.), Vector3::new(0., 1., 0.));
+  let i1 = Intersection::new(-2.0f64.sqrt() / 2., &shape);
+  let i2 = Intersection::new(2.0f64.sqrt() / 2., &shape);
   let xs = Intersections::new(vec![i1, i2]);
   let comps = prepare_computations(xs.intersections[1].clone(), r, xs);
-  let reflectance = schlick(comps);
+  let reflectance = schlick(&comps);
 
   assert_eq!(reflectance, 1.0);
 }
@@ -375,12 +540,12 @@ fn schlick_approximation_under_total_internal_reflection() {
 #[test]
 fn schlick_approximation_under_a_perpendicular_viewing_angle() {
   let shape = Shape::glass_sphere();
-  let r = Ray::new(point(0., 0., 0.), vector(0., 1., 0.));
-  let i1 = Intersection::new(-1., shape.clone());
-  let i2 = Intersection::new(1., shape.clone());
+  let r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 1., 0.));
+  let i1 = Intersection::new(-1., &shape);
+  let i2 = Intersection::new(1., &shape);
   let xs = Intersections::new(vec![i1, i2]);
   let comps = prepare_computations(xs.intersections[1].clone(), r, xs);
-  let reflectance = schlick(comps);
+  let reflectance = schlick(&comps);
 
   assert_eq!((reflectance - 0.04).abs() < 0.001, true);
 }
@@ -388,11 +553,177 @@ fn schlick_approximation_under_a_perpendicular_viewing_angle() {
 #[test]
 fn schlick_approximation_with_small_angle_and_n2_greater_than_n1() {
   let shape = Shape::glass_sphere();
-  let r = Ray::new(point(0., 0.99, -2.), vector(0., 0., 1.));
-  let i1 = Intersection::new(1.8589, shape.clone());
+  let r = Ray::new(Point3::new(0., 0.99, -2.), Vector3::new(0., 0., 1.));
+  let i1 = Intersection::new(1.8589, &shape);
   let xs = Intersections::new(vec![i1]);
   let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
-  let reflectance = schlick(comps);
+  let reflectance = schlick(&comps);
 
   assert_eq!(reflectance, 0.4887308101221217);
 }
+
+#[test]
+fn the_materials_on_either_side_of_a_hit() {
+  let mut shape = Shape::glass_sphere();
+  shape.material.absorption = Color::new(0.5, 0., 0.);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let xs = Intersections::new(vec![
+    Intersection::new(4., &shape),
+    Intersection::new(6., &shape),
+  ]);
+
+  let entering = prepare_computations(xs.intersections[0].clone(), r, xs.clone());
+  let leaving = prepare_computations(xs.intersections[1].clone(), r, xs);
+
+  assert_eq!(entering.n1_material.is_none(), true);
+  assert_eq!(entering.n2_material.unwrap().absorption.r, 0.5);
+  assert_eq!(leaving.n1_material.unwrap().absorption.r, 0.5);
+  assert_eq!(leaving.n2_material.is_none(), true);
+}
+
+#[test]
+fn a_ray_with_a_wavelength_sees_the_dispersed_index() {
+  let mut shape = Shape::glass_sphere();
+  shape.material.set_dispersion(crate::material::dispersion::Dispersion::bk7());
+  let xs = Intersections::new(vec![Intersection::new(4., &shape)]);
+
+  let white = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let blue = Ray::with_wavelength(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.), Some(450.));
+  let white_comps = prepare_computations(xs.intersections[0].clone(), white, xs.clone());
+  let blue_comps = prepare_computations(xs.intersections[0].clone(), blue, xs.clone());
+
+  assert_eq!(white_comps.is_dispersive(), true);
+  assert_eq!(white_comps.n2, shape.material.refractive_index);
+  assert_eq!(blue_comps.n2 > white_comps.n2, true);
+  assert_eq!(white_comps.indices_at(Some(450.)), (1.0, blue_comps.n2));
+}
+
+#[test]
+fn the_footprint_of_a_pixel_grows_with_distance() {
+  let shape = Shape::new(ShapeType::Plane);
+  let footprint_at = |height: f64| {
+    let mut r = Ray::new(Point3::new(0., height, 0.), Vector3::new(0., -1., 0.));
+    r.differential = Some(RayDifferential {
+      rx_origin: r.origin,
+      rx_direction: Vector3::new(0.01, -1., 0.),
+      ry_origin: r.origin,
+      ry_direction: Vector3::new(0., -1., 0.01),
+    });
+    let i = Intersection::new(height, &shape);
+    let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+    return comps.footprint.unwrap();
+  };
+
+  let (near_x, near_y) = footprint_at(1.);
+  let (far_x, _) = footprint_at(10.);
+
+  assert_eq!(near_x.to_tuple().equals(vector(0.01, 0., 0.)), true);
+  assert_eq!(near_y.to_tuple().equals(vector(0., 0., 0.01)), true);
+  assert_eq!(far_x.to_tuple().equals(vector(0.1, 0., 0.)), true);
+}
+
+#[test]
+fn a_footprint_filters_the_pattern_without_copying_the_object() {
+  use crate::pattern::{Pattern, PatternType};
+
+  let mut shape = Shape::new(ShapeType::Plane);
+  shape.material.pattern = Some(Pattern::new(
+    PatternType::Checker,
+    Color::new(1., 1., 1.),
+    Color::new(0., 0., 0.),
+  ));
+  let mut r = Ray::new(Point3::new(0.5, 100., 0.5), Vector3::new(0., -1., 0.));
+  r.differential = Some(RayDifferential {
+    rx_origin: r.origin,
+    rx_direction: Vector3::new(0.5, -1., 0.),
+    ry_origin: r.origin,
+    ry_direction: Vector3::new(0., -1., 0.5),
+  });
+  let i = Intersection::new(100., &shape);
+  let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+
+  assert_eq!(comps.filtered_color.is_some(), true);
+  assert_eq!(Color::equals(comps.surface_color(), comps.filtered_color.unwrap()), true);
+  assert_eq!(comps.object.material.pattern.is_some(), true);
+  assert_eq!(
+    match comps.object {
+      Cow::Borrowed(_) => true,
+      Cow::Owned(_) => false,
+    },
+    true
+  );
+}
+
+#[test]
+fn rays_without_differentials_have_no_footprint() {
+  let shape = Shape::new(ShapeType::Sphere);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let i = Intersection::new(4., &shape);
+  let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+
+  assert_eq!(comps.footprint.is_none(), true);
+  assert_eq!(comps.reflected_differential().is_none(), true);
+}
+
+#[test]
+fn a_curved_mirror_spreads_the_reflected_differentials() {
+  let shape = Shape::new(ShapeType::Sphere);
+  let mut r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  r.differential = Some(RayDifferential {
+    rx_origin: r.origin,
+    rx_direction: Vector3::new(0.01, 0., 1.),
+    ry_origin: r.origin,
+    ry_direction: Vector3::new(0., 0.01, 1.),
+  });
+  let i = Intersection::new(4., &shape);
+  let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+  let d = comps.reflected_differential().unwrap();
+
+  // a flat mirror would only flip the 0.01 spread; the bulge adds to it
+  let spread = d.rx_direction.norm().x;
+  assert_eq!(spread > 0.02, true);
+  assert_eq!(d.rx_origin.x > 0., true);
+}
+
+#[test]
+fn a_bump_map_tilts_the_shading_normal_but_not_the_offset() {
+  let mut shape = Shape::new(ShapeType::Plane);
+  shape
+    .material
+    .set_bump(crate::material::bump::BumpMap::noise(0.8, 3.));
+  let r = Ray::new(Point3::new(0.37, 1., 0.81), Vector3::new(0., -1., 0.));
+  let i = Intersection::new(1., &shape);
+  let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+
+  assert_eq!(comps.inside, false);
+  assert_eq!(comps.normalv.to_tuple().equals(vector(0., 1., 0.)), false);
+  assert_eq!(comps.normalv.dot(Vector3::new(0., 1., 0.)) > 0., true);
+  assert_eq!(comps.over_point.y > 0. && comps.under_point.y < 0., true);
+}
+
+#[test]
+fn intersections_and_computations_borrow_the_hit_shape() {
+  let shape = Shape::new(ShapeType::Sphere);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let xs = shape.intersects(r);
+
+  assert_eq!(std::ptr::eq(xs.intersections[0].object, &shape), true);
+
+  let comps = prepare_computations(xs.intersections[0], r, xs.clone());
+  let borrowed = match comps.object {
+    Cow::Borrowed(object) => std::ptr::eq(object, &shape),
+    Cow::Owned(_) => false,
+  };
+  assert_eq!(borrowed, true);
+
+  // a single wavelength needs its own copy of the material
+  let r = Ray::with_wavelength(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.), Some(550.));
+  let comps = prepare_computations(xs.intersections[0], r, xs.clone());
+  assert_eq!(
+    match comps.object {
+      Cow::Owned(_) => true,
+      Cow::Borrowed(_) => false,
+    },
+    true
+  );
+}
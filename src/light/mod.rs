@@ -1,44 +1,110 @@
 use crate::colors::Color;
-use crate::material::Material;
+use crate::material::{microfacet, Material, ShadingModel};
 use crate::shape::{Shape, ShapeType};
-use crate::vectors::{dot, point, reflect, vector, Tuple};
+use crate::spectrum::project;
+use crate::vectors::typed::{Normal3, Point3, Vector3};
 
 #[derive(Copy, Clone)]
 pub struct PointLight {
-  pub position: Tuple,
+  pub position: Point3,
   pub intensity: Color,
 }
 
 impl PointLight {
-  pub fn new(position: Tuple, intensity: Color) -> PointLight {
+  pub fn new(position: Point3, intensity: Color) -> PointLight {
     PointLight {
       position: position,
       intensity: intensity,
     }
   }
+
+  // The light's emission at a single wavelength, when the ray carries one.
+  pub fn at_wavelength(&self, wavelength: Option<f64>) -> PointLight {
+    return PointLight::new(self.position, project(self.intensity, wavelength));
+  }
+}
+
+pub fn surface_color(m: &Material, o: &Shape, position: Point3) -> Color {
+  return match &m.pattern {
+    Some(pattern) => pattern.pattern_at_object(o, position),
+    None => m.color,
+  };
+}
+
+// What lighting_with_options needs beyond the book's arguments.
+#[derive(Copy, Clone)]
+pub struct LightingOptions {
+  pub in_shadow: bool,
+  // how much of the material's ambient term to keep: 1 - occlusion for
+  // ambient occlusion, 0 where other lighting stands in for it
+  pub ambient_scale: f64,
+  // the surface color when the caller already has it, such as a pattern
+  // filtered over a pixel footprint; otherwise it is looked up at position
+  pub color: Option<Color>,
+}
+
+impl LightingOptions {
+  pub fn new(in_shadow: bool) -> LightingOptions {
+    return LightingOptions {
+      in_shadow: in_shadow,
+      ambient_scale: 1.,
+      color: None,
+    };
+  }
 }
 
 pub fn lighting(
-  m: Material,
-  o: Shape,
+  m: &Material,
+  o: &Shape,
   l: PointLight,
-  position: Tuple,
-  eyev: Tuple,
-  normalv: Tuple,
+  position: Point3,
+  eyev: Vector3,
+  normalv: Normal3,
   is_in_shadow: bool,
+) -> Color {
+  let options = LightingOptions::new(is_in_shadow);
+  return lighting_with_options(m, o, l, position, eyev, normalv, options);
+}
+
+// Same as lighting(), with the shadow, the ambient term and the surface
+// color given through options.
+pub fn lighting_with_options(
+  m: &Material,
+  o: &Shape,
+  l: PointLight,
+  position: Point3,
+  eyev: Vector3,
+  normalv: Normal3,
+  options: LightingOptions,
 ) -> Color {
   let mut diffuse;
   let mut specular;
+  let is_in_shadow = options.in_shadow;
+  let ambient_scale = options.ambient_scale;
 
-  let color = match m.pattern {
-    Some(_) => m.pattern.unwrap().pattern_at_object(o, position),
-    None => m.color,
+  let color = match options.color {
+    Some(color) => color,
+    None => surface_color(m, o, position),
   };
 
-  let effective_color = Color::dot(color, l.intensity);
-  let lightv = l.position.sub(position).norm();
-  let ambient = Color::mult(effective_color, m.ambient);
-  let light_dot_normal = dot(lightv, normalv);
+  let effective_color = Color::hadamard(color, l.intensity);
+  let lightv = (l.position - position).norm();
+
+  if m.shading_model == ShadingModel::Microfacet {
+    // metals have no diffuse lobe for ambient light to stand in for
+    let ambient = Color::mult(
+      effective_color,
+      m.ambient * ambient_scale * (1. - m.metallic),
+    );
+    if is_in_shadow {
+      return ambient;
+    }
+    let reflected = microfacet::shade(m, color, l.intensity, lightv, eyev, normalv);
+    return Color::add(ambient, reflected);
+  }
+
+  let ambient = Color::mult(effective_color, m.ambient * ambient_scale);
+  let light_dot_normal = normalv.dot(lightv);
 
   if light_dot_normal < 0. {
     diffuse = Color::new(0., 0., 0.);
@@ -46,9 +112,9 @@ pub fn lighting(
   } else {
     diffuse = Color::mult(effective_color, m.diffuse * light_dot_normal);
 
-    let neg_lightv = lightv.negate();
-    let reflectv = reflect(neg_lightv, normalv);
-    let reflect_dot_eye = dot(reflectv, eyev);
+    let neg_lightv = -lightv;
+    let reflectv = neg_lightv.reflect(normalv);
+    let reflect_dot_eye = reflectv.dot(eyev);
     if reflect_dot_eye <= 0. {
       specular = Color::new(0., 0., 0.);
     } else {
@@ -67,7 +133,7 @@ pub fn lighting(
 #[test]
 fn a_point_light_has_position_and_intensity() {
   let intensity = Color::new(1., 1., 1.);
-  let position = point(0., 0., 0.);
+  let position = Point3::new(0., 0., 0.);
 
   let light = PointLight::new(position, intensity);
 
@@ -77,14 +143,14 @@ fn a_point_light_has_position_and_intensity() {
 #[test]
 fn lighting_with_eye_between_light_and_surface() {
   let m = Material::new();
-  let position = point(0., 0., 0.);
+  let position = Point3::new(0., 0., 0.);
   let intensity = Color::new(1., 1., 1.);
-  let l = PointLight::new(point(0., 0., -10.), intensity);
-  let eyev = vector(0., 0., -1.);
-  let normalv = vector(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 0., -10.), intensity);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
   let o = Shape::new(ShapeType::Sphere);
 
-  let light = lighting(m, o, l, position, eyev, normalv, false);
+  let light = lighting(&m, &o, l, position, eyev, normalv, false);
 
   assert_eq!(Color::equals(light, Color::new(1.9, 1.9, 1.9)), true);
 }
@@ -92,14 +158,14 @@ fn lighting_with_eye_between_light_and_surface() {
 #[test]
 fn lighting_with_eye_between_light_and_surface_eye_at_45() {
   let m = Material::new();
-  let position = point(0., 0., 0.);
+  let position = Point3::new(0., 0., 0.);
   let intensity = Color::new(1., 1., 1.);
-  let l = PointLight::new(point(0., 0., -10.), intensity);
-  let eyev = vector(0., 2.0f64.sqrt() / 2., -2.0f64.sqrt() / 2.);
-  let normalv = vector(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 0., -10.), intensity);
+  let eyev = Vector3::new(0., 2.0f64.sqrt() / 2., -2.0f64.sqrt() / 2.);
+  let normalv = Normal3::new(0., 0., -1.);
   let o = Shape::new(ShapeType::Sphere);
 
-  let light = lighting(m, o, l, position, eyev, normalv, false);
+  let light = lighting(&m, &o, l, position, eyev, normalv, false);
 
   assert_eq!(Color::equals(light, Color::new(1.0, 1.0, 1.0)), true);
 }
@@ -107,14 +173,14 @@ fn lighting_with_eye_between_light_and_surface_eye_at_45() {
 #[test]
 fn lighting_with_eye_between_light_and_surface_light_at_45() {
   let m = Material::new();
-  let position = point(0., 0., 0.);
+  let position = Point3::new(0., 0., 0.);
   let intensity = Color::new(1., 1., 1.);
-  let l = PointLight::new(point(0., 10., -10.), intensity);
-  let eyev = vector(0., 0., -1.);
-  let normalv = vector(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 10., -10.), intensity);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
   let o = Shape::new(ShapeType::Sphere);
 
-  let light = lighting(m, o, l, position, eyev, normalv, false);
+  let light = lighting(&m, &o, l, position, eyev, normalv, false);
 
   assert_eq!(
     Color::approx_equals(light, Color::new(0.7364, 0.7364, 0.7364)),
@@ -125,14 +191,14 @@ fn lighting_with_eye_between_light_and_surface_light_at_45() {
 #[test]
 fn lighting_with_eye_in_path_of_reflection() {
   let m = Material::new();
-  let position = point(0., 0., 0.);
+  let position = Point3::new(0., 0., 0.);
   let intensity = Color::new(1., 1., 1.);
-  let l = PointLight::new(point(0., 10., -10.), intensity);
-  let eyev = vector(0., -2.0f64.sqrt() / 2., -2.0f64.sqrt() / 2.);
-  let normalv = vector(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 10., -10.), intensity);
+  let eyev = Vector3::new(0., -2.0f64.sqrt() / 2., -2.0f64.sqrt() / 2.);
+  let normalv = Normal3::new(0., 0., -1.);
   let o = Shape::new(ShapeType::Sphere);
 
-  let light = lighting(m, o, l, position, eyev, normalv, false);
+  let light = lighting(&m, &o, l, position, eyev, normalv, false);
 
   assert_eq!(
     Color::approx_equals(light, Color::new(1.6364, 1.6364, 1.6364)),
@@ -143,14 +209,14 @@ fn lighting_with_eye_in_path_of_reflection() {
 #[test]
 fn lighting_with_light_behind_surface() {
   let m = Material::new();
-  let position = point(0., 0., 0.);
+  let position = Point3::new(0., 0., 0.);
   let intensity = Color::new(1., 1., 1.);
-  let l = PointLight::new(point(0., 0., 10.), intensity);
-  let eyev = vector(0., 0., -1.);
-  let normalv = vector(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 0., 10.), intensity);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
   let o = Shape::new(ShapeType::Sphere);
 
-  let light = lighting(m, o, l, position, eyev, normalv, false);
+  let light = lighting(&m, &o, l, position, eyev, normalv, false);
 
   assert_eq!(Color::equals(light, Color::new(0.1, 0.1, 0.1)), true);
 }
@@ -158,15 +224,64 @@ fn lighting_with_light_behind_surface() {
 #[test]
 fn lighting_with_with_the_surface_in_shadow() {
   let m = Material::new();
-  let position = point(0., 0., 0.);
-  let eyev = vector(0., 0., -1.);
-  let normalv = vector(0., 0., -1.);
+  let position = Point3::new(0., 0., 0.);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
   let intensity = Color::new(1., 1., 1.);
-  let l = PointLight::new(point(0., 0., -10.), intensity);
+  let l = PointLight::new(Point3::new(0., 0., -10.), intensity);
   let is_in_shadow = true;
   let o = Shape::new(ShapeType::Sphere);
 
-  let light = lighting(m, o, l, position, eyev, normalv, is_in_shadow);
+  let light = lighting(&m, &o, l, position, eyev, normalv, is_in_shadow);
 
   assert_eq!(Color::equals(light, Color::new(0.1, 0.1, 0.1)), true);
 }
+
+#[test]
+fn occlusion_darkens_the_ambient_term() {
+  let m = Material::new();
+  let position = Point3::new(0., 0., 0.);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 0., -10.), Color::new(1., 1., 1.));
+  let o = Shape::new(ShapeType::Sphere);
+
+  let mut options = LightingOptions::new(true);
+  options.ambient_scale = 0.5;
+  let half = lighting_with_options(&m, &o, l, position, eyev, normalv, options);
+  options.ambient_scale = 0.;
+  let full = lighting_with_options(&m, &o, l, position, eyev, normalv, options);
+
+  assert_eq!(Color::equals(half, Color::new(0.05, 0.05, 0.05)), true);
+  assert_eq!(Color::equals(full, Color::new(0., 0., 0.)), true);
+}
+
+#[test]
+fn lighting_a_rough_dielectric_head_on() {
+  let m = Material::pbr(Color::new(1., 1., 1.), 0.0, 1.0);
+  let position = Point3::new(0., 0., 0.);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 0., -10.), Color::new(1., 1., 1.));
+  let o = Shape::new(ShapeType::Sphere);
+
+  let c = lighting(&m, &o, l, position, eyev, normalv, false);
+
+  // ambient + 96% lambertian + a broad, dim GGX lobe
+  assert_eq!(c.r > 0.1 + 0.96, true);
+  assert_eq!(c.r < 0.1 + 1.0, true);
+}
+
+#[test]
+fn lighting_a_shadowed_metal_leaves_no_ambient() {
+  let m = Material::pbr(Color::new(1., 1., 1.), 1.0, 0.5);
+  let position = Point3::new(0., 0., 0.);
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
+  let l = PointLight::new(Point3::new(0., 0., -10.), Color::new(1., 1., 1.));
+  let o = Shape::new(ShapeType::Sphere);
+
+  let c = lighting(&m, &o, l, position, eyev, normalv, true);
+
+  assert_eq!(Color::equals(c, Color::new(0., 0., 0.)), true);
+}
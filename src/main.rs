@@ -1,4 +1,5 @@
 mod vectors;
+use vectors::typed::Point3;
 use vectors::{point, vector};
 
 mod world;
@@ -8,7 +9,6 @@ mod colors;
 use colors::Color;
 
 mod canvas;
-use canvas::Canvas;
 
 mod matrix;
 
@@ -17,19 +17,23 @@ use transform::Transform;
 
 mod intersections;
 mod ray;
-use ray::Ray;
 mod shape;
 use shape::{Shape, ShapeType};
 use std::f64;
 mod light;
-use light::{lighting, PointLight};
+use light::PointLight;
 mod camera;
+use camera::progress::{CancelToken, Progress};
 use camera::Camera;
 mod material;
 use material::Material;
-use std::time::{Duration, Instant};
+use std::io::{self, Write};
+use std::time::Instant;
 mod pattern;
 use pattern::{Pattern, PatternType};
+mod environment;
+mod sampling;
+mod spectrum;
 
 fn world() {
     let width = 200;
@@ -40,7 +44,7 @@ fn world() {
 
     // setup light
     world.set_light(PointLight::new(
-        point(-5., 5., -10.),
+        Point3::new(-5., 5., -10.),
         Color::new(1., 1., 1.),
     ));
 
@@ -63,11 +67,13 @@ fn world() {
 
     // setup left wall
     let mut left_wall = Shape::new(ShapeType::Plane);
-    left_wall.transform = Transform::new()
-        .translate(-10., 0., 4.)
-        .rotate_y(3.0 * f64::consts::PI / 2.0)
-        .rotate_x(f64::consts::PI / 2.0)
-        .transform;
+    left_wall.set_transform(
+        Transform::new()
+            .translate(-10., 0., 4.)
+            .rotate_y(3.0 * f64::consts::PI / 2.0)
+            .rotate_x(f64::consts::PI / 2.0)
+            .transform,
+    );
     left_wall.material = Material::new();
     let mut pattern = Pattern::new(
         PatternType::Checker,
@@ -80,11 +86,13 @@ fn world() {
 
     // setup right wall
     let mut right_wall = Shape::new(ShapeType::Plane);
-    right_wall.transform = Transform::new()
-        .translate(0., 0., 3.)
-        .rotate_y(1.0 * f64::consts::PI / 4.0)
-        .rotate_x(f64::consts::PI / 2.0)
-        .transform;
+    right_wall.set_transform(
+        Transform::new()
+            .translate(0., 0., 3.)
+            .rotate_y(1.0 * f64::consts::PI / 4.0)
+            .rotate_x(f64::consts::PI / 2.0)
+            .transform,
+    );
     right_wall.material = Material::new();
     let mut pattern = Pattern::new(
         PatternType::Checker,
@@ -98,10 +106,12 @@ fn world() {
 
     // setup middle sphere
     let mut middle = Shape::new(ShapeType::Sphere);
-    middle.transform = Transform::new()
-        .scale(2.0, 2.0, 2.0)
-        .translate(-1., 1., -0.8)
-        .transform;
+    middle.set_transform(
+        Transform::new()
+            .scale(2.0, 2.0, 2.0)
+            .translate(-1., 1., -0.8)
+            .transform,
+    );
     middle.material = Material::new();
     middle.material.color = Color::new(0.0, 0.0, 0.0);
     middle.material.ambient = 0.0;
@@ -111,10 +121,12 @@ fn world() {
 
     // setup right sphere
     let mut right = Shape::new(ShapeType::Sphere);
-    right.transform = Transform::new()
-        .translate(0.5, 1., -5.)
-        .scale(0.5, 0.5, 0.5)
-        .transform;
+    right.set_transform(
+        Transform::new()
+            .translate(0.5, 1., -5.)
+            .scale(0.5, 0.5, 0.5)
+            .transform,
+    );
     right.material = Material::new();
     right.material.color = Color::new(0.0, 1.0, 0.0);
     right.material.reflectiveness = 0.5;
@@ -122,10 +134,12 @@ fn world() {
 
     // setup left sphere
     let mut left = Shape::new(ShapeType::Sphere);
-    left.transform = Transform::new()
-        .translate(-3.5, 2.3, -6.)
-        .scale(0.66, 0.66, 0.66)
-        .transform;
+    left.set_transform(
+        Transform::new()
+            .translate(-3.5, 2.3, -6.)
+            .scale(0.66, 0.66, 0.66)
+            .transform,
+    );
     left.material = Material::new();
     left.material.color = Color::new(1.0, 0.0, 0.0);
     left.material.reflectiveness = 0.5;
@@ -133,10 +147,12 @@ fn world() {
 
     // setup back sphere
     let mut back = Shape::new(ShapeType::Sphere);
-    back.transform = Transform::new()
-        .translate(2.8, 2.0, -6.2)
-        .scale(2.0, 2.0, 2.0)
-        .transform;
+    back.set_transform(
+        Transform::new()
+            .translate(2.8, 2.0, -6.2)
+            .scale(2.0, 2.0, 2.0)
+            .transform,
+    );
     back.material = Material::new();
     back.material.color = Color::new(0.0, 0.0, 0.0);
     back.material.transparency = 1.0;
@@ -146,10 +162,23 @@ fn world() {
 
     world.add_object(back);
 
-    camera.transform =
-        Transform::view_transform(point(0., 1.5, -14.), point(0., 1., 0.), vector(0., 1., 0.));
+    camera.set_transform(Transform::view_transform(
+        point(0., 1.5, -14.),
+        point(0., 1., 0.),
+        vector(0., 1., 0.),
+    ));
 
-    let image = camera.render(world);
+    let report = |p: &Progress| {
+        print!(
+            "\r{:5.1}%  {:.0} camera rays/s  eta {}s   ",
+            100. * p.fraction(),
+            p.camera_rays_per_second,
+            p.eta.as_secs()
+        );
+        io::stdout().flush().unwrap();
+    };
+    let (image, _stats) = camera.render_with_progress(&world, &report, &CancelToken::new());
+    println!();
     image.write();
 }
 
@@ -0,0 +1,150 @@
+use crate::canvas::Canvas;
+use crate::colors::Color;
+use crate::pattern::image::{ImageTexture, TextureFilter, TextureWrap};
+use crate::pattern::noise::noise;
+use crate::pattern::texture_map::{TextureMap, UvMapping, UvPattern};
+use crate::sampling::orthonormal_basis;
+use crate::vectors::typed::{Normal3, Point3, Vector3};
+
+// step used for the finite differences below, in object space units
+const DELTA: f64 = 1.0e-4;
+
+// Fine surface detail that only tilts the shading normal, leaving the
+// geometry alone. Both work in object space, so the detail moves and scales
+// with the shape.
+#[derive(Clone)]
+pub enum BumpMap {
+  // Bumps following the gradient of noise; scale sets how steep they are and
+  // frequency how many there are per unit.
+  Noise { scale: f64, frequency: f64 },
+  // A tangent space normal map, rgb in [0, 1] encoding xyz in [-1, 1] with z
+  // along the surface normal and x and y along increasing u and v.
+  NormalMap { map: TextureMap, strength: f64 },
+}
+
+impl BumpMap {
+  pub fn noise(scale: f64, frequency: f64) -> BumpMap {
+    return BumpMap::Noise {
+      scale: scale,
+      frequency: frequency,
+    };
+  }
+
+  pub fn normal_map(texture: ImageTexture, mapping: UvMapping, strength: f64) -> BumpMap {
+    return BumpMap::NormalMap {
+      map: TextureMap::new(UvPattern::Image(texture), mapping),
+      strength: strength,
+    };
+  }
+
+  // The object space normal n at p, tilted by the bump.
+  pub fn perturb(&self, p: Point3, n: Normal3) -> Normal3 {
+    let nv = n.to_vector();
+    return match self {
+      BumpMap::Noise { scale, frequency } => {
+        let height =
+          |x: f64, y: f64, z: f64| noise(Point3::new(x * frequency, y * frequency, z * frequency));
+        let gradient = Vector3::new(
+          height(p.x + DELTA, p.y, p.z) - height(p.x - DELTA, p.y, p.z),
+          height(p.x, p.y + DELTA, p.z) - height(p.x, p.y - DELTA, p.z),
+          height(p.x, p.y, p.z + DELTA) - height(p.x, p.y, p.z - DELTA),
+        ) * (1. / (2. * DELTA));
+        // only the slope along the surface tilts the normal
+        let along_surface = gradient - nv * n.dot(gradient);
+        Normal3::from_vector(nv - along_surface * *scale)
+      }
+      BumpMap::NormalMap { map, strength } => {
+        let (tangent, bitangent) = tangent_frame(map, p, n);
+        let c = map.pattern_at(p);
+        let local = Vector3::new(2. * c.r - 1., 2. * c.g - 1., 2. * c.b - 1.);
+        Normal3::from_vector(
+          tangent * (local.x * strength) + bitangent * (local.y * strength) + nv * local.z,
+        )
+      }
+    };
+  }
+}
+
+// The directions of increasing u and v on the surface at p, found by
+// stepping along two surface directions and inverting the change in uv.
+fn tangent_frame(map: &TextureMap, p: Point3, n: Normal3) -> (Vector3, Vector3) {
+  let (e1, e2) = orthonormal_basis(n);
+  let (u, v) = map.uv_at(p);
+  let (u1, v1) = map.uv_at(p + e1 * DELTA);
+  let (u2, v2) = map.uv_at(p + e2 * DELTA);
+  // uv wraps around, so a step across a seam is taken the short way
+  let wrap = |d: f64| d - d.round();
+  let (du1, dv1) = (wrap(u1 - u), wrap(v1 - v));
+  let (du2, dv2) = (wrap(u2 - u), wrap(v2 - v));
+
+  let det = du1 * dv2 - du2 * dv1;
+  if det.abs() < 1.0e-12 {
+    return (e1, e2);
+  }
+  let dpdu = (e1 * dv2 - e2 * dv1) * (1. / det);
+  let dpdv = (e2 * du1 - e1 * du2) * (1. / det);
+
+  let nv = n.to_vector();
+  let tangent = (dpdu - nv * n.dot(dpdu)).norm();
+  let bitangent = (dpdv - nv * n.dot(dpdv) - tangent * tangent.dot(dpdv)).norm();
+  return (tangent, bitangent);
+}
+
+#[cfg(test)]
+fn flat_normal_map(c: Color) -> ImageTexture {
+  let mut canvas = Canvas::new(1, 1);
+  canvas.set(0, 0, c);
+  return ImageTexture::with_options(canvas, TextureFilter::Nearest, TextureWrap::Repeat);
+}
+
+#[test]
+fn noise_bumps_tilt_the_normal() {
+  let n = Normal3::new(0., 1., 0.);
+  let p = Point3::new(0.37, 0., 0.81);
+
+  let flat = BumpMap::noise(0., 4.);
+  assert_eq!(flat.perturb(p, n).to_tuple().equals(n.to_tuple()), true);
+
+  let bumpy = BumpMap::noise(0.5, 4.);
+  let tilted = bumpy.perturb(p, n).to_vector();
+  assert_eq!((tilted.mag() - 1.).abs() < 1e-9, true);
+  assert_eq!(n.dot(tilted) < 1. - 1e-6, true);
+  assert_eq!(n.dot(tilted) > 0., true);
+}
+
+#[test]
+fn a_flat_normal_map_leaves_the_normal() {
+  let bump = BumpMap::normal_map(
+    flat_normal_map(Color::new(0.5, 0.5, 1.)),
+    UvMapping::Planar,
+    1.,
+  );
+  let n = Normal3::new(0., 1., 0.);
+  let bumped = bump.perturb(Point3::new(0.3, 0., 0.6), n);
+
+  assert_eq!(bumped.to_tuple().equals(n.to_tuple()), true);
+}
+
+#[test]
+fn a_normal_map_tilts_towards_increasing_u_and_v() {
+  let half = 0.5 + 0.5 * (0.5f64).sqrt();
+  let n = Normal3::new(0., 1., 0.);
+  let p = Point3::new(0.3, 0., 0.6);
+
+  // planar mapping has u along x and v along z
+  let towards_u = BumpMap::normal_map(
+    flat_normal_map(Color::new(half, 0.5, half)),
+    UvMapping::Planar,
+    1.,
+  );
+  let towards_v = BumpMap::normal_map(
+    flat_normal_map(Color::new(0.5, half, half)),
+    UvMapping::Planar,
+    1.,
+  );
+  let s = (0.5f64).sqrt();
+
+  let (u, v) = (towards_u.perturb(p, n), towards_v.perturb(p, n));
+  assert_eq!(u.to_tuple().equals(Normal3::new(s, s, 0.).to_tuple()), true);
+  assert_eq!(v.to_tuple().equals(Normal3::new(0., s, s).to_tuple()), true);
+}
@@ -0,0 +1,80 @@
+// Wavelength dependent index of refraction. Wavelengths are given in
+// nanometers; both formulas are conventionally written in micrometers.
+#[derive(Copy, Clone, Debug)]
+pub enum Dispersion {
+  // n = a + b / lambda^2
+  Cauchy { a: f64, b: f64 },
+  // n^2 = 1 + sum(b_i lambda^2 / (lambda^2 - c_i))
+  Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+// The wavelength at which a dispersive material reports its nominal index.
+pub const NOMINAL_WAVELENGTH: f64 = 550.;
+
+impl Dispersion {
+  pub fn cauchy(a: f64, b: f64) -> Dispersion {
+    return Dispersion::Cauchy { a: a, b: b };
+  }
+
+  pub fn sellmeier(b: [f64; 3], c: [f64; 3]) -> Dispersion {
+    return Dispersion::Sellmeier { b: b, c: c };
+  }
+
+  // Schott N-BK7, the common optical crown glass.
+  pub fn bk7() -> Dispersion {
+    return Dispersion::sellmeier(
+      [1.03961212, 0.231792344, 1.01046945],
+      [0.00600069867, 0.0200179144, 103.560653],
+    );
+  }
+
+  // Diamond, strongly dispersive.
+  pub fn diamond() -> Dispersion {
+    return Dispersion::sellmeier([0.3306, 4.3356, 0.], [0.030625, 0.011236, 0.]);
+  }
+
+  pub fn index_at(&self, wavelength: f64) -> f64 {
+    let um = wavelength / 1000.;
+    let um2 = um * um;
+
+    return match self {
+      Dispersion::Cauchy { a, b } => a + b / um2,
+      Dispersion::Sellmeier { b, c } => {
+        let mut n2 = 1.;
+        for i in 0..3 {
+          n2 += b[i] * um2 / (um2 - c[i]);
+        }
+        n2.sqrt()
+      }
+    };
+  }
+}
+
+#[test]
+fn bk7_matches_its_catalog_index() {
+  // the d line of helium
+  let n = Dispersion::bk7().index_at(587.6);
+
+  assert_eq!((n - 1.5168).abs() < 1e-4, true);
+}
+
+#[test]
+fn blue_light_bends_more_than_red() {
+  for d in vec![
+    Dispersion::cauchy(1.5046, 0.0042),
+    Dispersion::bk7(),
+    Dispersion::diamond(),
+  ] {
+    let red = d.index_at(650.);
+    let blue = d.index_at(450.);
+    assert_eq!(blue > red, true);
+    assert_eq!(red > 1., true);
+  }
+}
+
+#[test]
+fn diamond_is_denser_than_glass() {
+  let diamond = Dispersion::diamond().index_at(NOMINAL_WAVELENGTH);
+
+  assert_eq!((diamond - 2.42).abs() < 0.01, true);
+}
@@ -0,0 +1,230 @@
+use crate::colors::Color;
+use crate::intersections::{schlick, Computations};
+use crate::material::Material;
+use crate::sampling::to_world;
+use crate::vectors::typed::{Normal3, Vector3};
+use std::f64;
+
+// reflectance of common dielectrics (plastic, wood, stone) at normal incidence
+pub const DIELECTRIC_F0: f64 = 0.04;
+
+// GGX gets numerically unstable as roughness approaches a perfect mirror
+const MIN_ALPHA: f64 = 1.0e-3;
+
+pub fn alpha(roughness: f64) -> f64 {
+  return (roughness * roughness).max(MIN_ALPHA);
+}
+
+// GGX / Trowbridge-Reitz normal distribution.
+pub fn ggx_distribution(n_dot_h: f64, alpha: f64) -> f64 {
+  let a2 = alpha * alpha;
+  let d = n_dot_h * n_dot_h * (a2 - 1.) + 1.;
+  return a2 / (f64::consts::PI * d * d);
+}
+
+// A microfacet normal drawn from the GGX distribution around normalv, with
+// pdf D(h) cos(theta_h). Reflecting or refracting about it instead of about
+// normalv gives rough (glossy or frosted) scattering.
+pub fn sample_normal(normalv: Normal3, alpha: f64, u1: f64, u2: f64) -> Normal3 {
+  let tan2_theta = alpha * alpha * u1 / (1. - u1).max(1e-12);
+  let cos_theta = 1. / (1. + tan2_theta).sqrt();
+  let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+  let phi = 2. * f64::consts::PI * u2;
+  let local = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+  return Normal3::from_vector(to_world(local, normalv));
+}
+
+// Smith masking for one direction, matched to the GGX distribution.
+pub fn smith_g1(n_dot_v: f64, alpha: f64) -> f64 {
+  let a2 = alpha * alpha;
+  return 2. * n_dot_v / (n_dot_v + (a2 + (1. - a2) * n_dot_v * n_dot_v).sqrt());
+}
+
+pub fn smith_geometry(n_dot_v: f64, n_dot_l: f64, alpha: f64) -> f64 {
+  return smith_g1(n_dot_v, alpha) * smith_g1(n_dot_l, alpha);
+}
+
+pub fn fresnel_schlick(cos: f64, f0: Color) -> Color {
+  let f = (1. - cos.max(0.).min(1.)).powi(5);
+  return Color::add(f0, Color::mult(Color::sub(Color::new(1., 1., 1.), f0), f));
+}
+
+// Reflectance at normal incidence: a fixed 4% for dielectrics, the base
+// color for metals, blended by how metallic the material is.
+pub fn base_reflectance(m: &Material, color: Color) -> Color {
+  let dielectric = Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+  return Color::add(
+    Color::mult(dielectric, 1. - m.metallic),
+    Color::mult(color, m.metallic),
+  );
+}
+
+// Fresnel reflectance along the eye ray at a hit. Transparent dielectrics
+// use the hit's actual indices of refraction, so total internal reflection
+// is accounted for.
+pub fn fresnel_at_hit(m: &Material, color: Color, comps: &Computations) -> Color {
+  let cos = comps.normalv.dot(comps.eyev);
+  let dielectric = if m.transparency > 0. {
+    let r = schlick(comps);
+    Color::new(r, r, r)
+  } else {
+    fresnel_schlick(cos, Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0))
+  };
+  let metal = fresnel_schlick(cos, color);
+
+  return Color::add(
+    Color::mult(dielectric, 1. - m.metallic),
+    Color::mult(metal, m.metallic),
+  );
+}
+
+pub fn average(c: Color) -> f64 {
+  return (c.r + c.g + c.b) / 3.;
+}
+
+// The albedo of the diffuse lobe once the energy taken by specular
+// reflection, metalness and transmission has been removed.
+pub fn diffuse_albedo(m: &Material, color: Color, fresnel: Color) -> Color {
+  let remaining = Color::sub(Color::new(1., 1., 1.), fresnel);
+  return Color::mult(
+    Color::hadamard(color, remaining),
+    (1. - m.metallic) * (1. - m.transparency),
+  );
+}
+
+// Cook-Torrance reflection of a point light: the lambertian and GGX lobes,
+// already multiplied by cos(theta_l) and by pi to match the convention of
+// lighting(), where a light's intensity is the irradiance it delivers.
+pub fn shade(
+  m: &Material,
+  color: Color,
+  intensity: Color,
+  lightv: Vector3,
+  eyev: Vector3,
+  normalv: Normal3,
+) -> Color {
+  let n_dot_l = normalv.dot(lightv);
+  let n_dot_v = normalv.dot(eyev);
+  if n_dot_l <= 0. || n_dot_v <= 0. {
+    return Color::new(0., 0., 0.);
+  }
+
+  let halfv = (lightv + eyev).norm();
+  let n_dot_h = normalv.dot(halfv).max(0.);
+  let v_dot_h = eyev.dot(halfv).max(0.);
+
+  let a = alpha(m.roughness);
+  let fresnel = fresnel_schlick(v_dot_h, base_reflectance(m, color));
+  let specular = Color::mult(
+    fresnel,
+    ggx_distribution(n_dot_h, a) * smith_geometry(n_dot_v, n_dot_l, a) / (4. * n_dot_v * n_dot_l),
+  );
+  let diffuse = Color::div(diffuse_albedo(m, color, fresnel), f64::consts::PI);
+
+  return Color::mult(
+    Color::hadamard(Color::add(diffuse, specular), intensity),
+    f64::consts::PI * n_dot_l,
+  );
+}
+
+#[test]
+fn the_ggx_distribution_integrates_to_one_over_the_hemisphere() {
+  // integral of D(h) cos(theta_h) over the hemisphere, by the midpoint rule
+  let a = alpha(0.5);
+  let steps = 2000;
+  let mut sum = 0.;
+  for i in 0..steps {
+    let theta = (i as f64 + 0.5) / steps as f64 * f64::consts::PI / 2.;
+    sum += ggx_distribution(theta.cos(), a) * theta.cos() * theta.sin();
+  }
+  sum *= 2. * f64::consts::PI * (f64::consts::PI / 2.) / steps as f64;
+
+  assert_eq!((sum - 1.).abs() < 1e-3, true);
+}
+
+#[test]
+fn smith_masking_is_one_head_on_and_zero_at_grazing() {
+  assert_eq!((smith_g1(1., alpha(0.7)) - 1.).abs() < 1e-10, true);
+  assert_eq!(smith_g1(0., alpha(0.7)), 0.);
+}
+
+#[test]
+fn fresnel_goes_from_f0_to_white() {
+  let f0 = Color::new(0.9, 0.6, 0.2);
+
+  assert_eq!(Color::equals(fresnel_schlick(1., f0), f0), true);
+  assert_eq!(
+    Color::equals(fresnel_schlick(0., f0), Color::new(1., 1., 1.)),
+    true
+  );
+}
+
+#[test]
+fn metals_take_their_reflectance_from_the_base_color() {
+  let gold = Material::pbr(Color::new(1., 0.78, 0.34), 1.0, 0.3);
+  let plastic = Material::pbr(Color::new(1., 0., 0.), 0.0, 0.3);
+
+  assert_eq!(
+    Color::equals(
+      base_reflectance(&gold, gold.color),
+      Color::new(1., 0.78, 0.34)
+    ),
+    true
+  );
+  assert_eq!(
+    Color::equals(
+      base_reflectance(&plastic, plastic.color),
+      Color::new(0.04, 0.04, 0.04)
+    ),
+    true
+  );
+}
+
+#[test]
+fn a_metal_has_no_diffuse_lobe() {
+  let m = Material::pbr(Color::new(1., 1., 1.), 1.0, 0.5);
+  let fresnel = Color::new(0.9, 0.9, 0.9);
+
+  assert_eq!(
+    Color::equals(diffuse_albedo(&m, m.color, fresnel), Color::new(0., 0., 0.)),
+    true
+  );
+}
+
+#[test]
+fn a_rough_dielectric_never_reflects_more_than_it_receives() {
+  let m = Material::pbr(Color::new(1., 1., 1.), 0.0, 1.0);
+  let n = Normal3::new(0., 0., -1.);
+  let eyev = Vector3::new(0., 0., -1.);
+
+  // integrate the reflected light over all light directions
+  let steps = 400;
+  let mut sum = 0.;
+  for i in 0..steps {
+    let theta = (i as f64 + 0.5) / steps as f64 * f64::consts::PI / 2.;
+    let lightv = Vector3::new(theta.sin(), 0., -theta.cos());
+    let c = shade(&m, m.color, Color::new(1., 1., 1.), lightv, eyev, n);
+    // shade() is pi * brdf * cos, so each sample carries brdf * cos / pi
+    sum += c.r / f64::consts::PI * theta.sin();
+  }
+  sum *= 2. * f64::consts::PI * (f64::consts::PI / 2.) / steps as f64;
+
+  assert_eq!(sum <= 1., true);
+  assert_eq!(sum > 0.8, true);
+}
+
+#[test]
+fn sampled_normals_hug_the_surface_normal_when_smooth() {
+  let n = Normal3::new(0., 1., 0.);
+
+  let head_on = sample_normal(n, alpha(0.5), 0., 0.3);
+  assert_eq!(head_on.to_tuple().equals(n.to_tuple()), true);
+  for (u1, u2) in vec![(0.2, 0.1), (0.5, 0.5), (0.9, 0.8)] {
+    let smooth = sample_normal(n, alpha(0.01), u1, u2).to_vector();
+    let rough = sample_normal(n, alpha(0.9), u1, u2).to_vector();
+    assert_eq!(n.dot(smooth) > 0.999, true);
+    assert_eq!(n.dot(rough) < n.dot(smooth), true);
+    assert_eq!(n.dot(rough) > 0., true);
+    assert_eq!((rough.mag() - 1.).abs() < 1e-10, true);
+  }
+}
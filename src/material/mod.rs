@@ -2,7 +2,25 @@ use crate::colors::Color;
 use crate::light::{lighting, PointLight};
 use crate::pattern::{Pattern, PatternType};
 use crate::shape::{Shape, ShapeType};
-use crate::vectors::{point, vector, Tuple};
+use crate::spectrum::project;
+use crate::vectors::typed::{Normal3, Point3, Vector3};
+
+pub mod bump;
+pub mod dispersion;
+pub mod microfacet;
+
+use self::bump::BumpMap;
+use self::dispersion::{Dispersion, NOMINAL_WAVELENGTH};
+
+// Phong is the classic ambient/diffuse/specular model. Microfacet is a
+// metallic/roughness model built on GGX, for assets authored in PBR tools;
+// it ignores diffuse, specular, shininess and reflectiveness, taking the
+// split between diffuse and mirror-like reflection from Fresnel instead.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ShadingModel {
+  Phong,
+  Microfacet,
+}
 
 #[derive(Clone)]
 pub struct Material {
@@ -15,6 +33,17 @@ pub struct Material {
   pub transparency: f64,
   pub refractive_index: f64,
   pub pattern: Option<Pattern>,
+  pub emission: Color,
+  pub shading_model: ShadingModel,
+  pub metallic: f64,
+  pub roughness: f64,
+  pub glossy_samples: usize,
+  // light travelling through the material loses exp(-absorption * density * d)
+  // of each channel over a distance d
+  pub absorption: Color,
+  pub density: f64,
+  pub dispersion: Option<Dispersion>,
+  pub bump: Option<BumpMap>,
 }
 
 impl Material {
@@ -29,12 +58,84 @@ impl Material {
       transparency: 0.0,
       refractive_index: 1.0,
       pattern: None,
+      emission: Color::new(0., 0., 0.),
+      shading_model: ShadingModel::Phong,
+      metallic: 0.0,
+      roughness: 0.0,
+      glossy_samples: 8,
+      absorption: Color::new(0., 0., 0.),
+      density: 1.0,
+      dispersion: None,
+      bump: None,
     }
   }
 
+  pub fn pbr(color: Color, metallic: f64, roughness: f64) -> Material {
+    let mut m = Material::new();
+    m.shading_model = ShadingModel::Microfacet;
+    m.color = color;
+    m.metallic = metallic;
+    m.roughness = roughness;
+    return m;
+  }
+
   pub fn set_pattern(&mut self, pattern: Pattern) {
     self.pattern = Some(pattern);
   }
+
+  pub fn set_bump(&mut self, bump: BumpMap) {
+    self.bump = Some(bump);
+  }
+
+  // Makes the index of refraction depend on wavelength. refractive_index is
+  // kept at the nominal index, used by rays that carry no wavelength.
+  pub fn set_dispersion(&mut self, dispersion: Dispersion) {
+    self.refractive_index = dispersion.index_at(NOMINAL_WAVELENGTH);
+    self.dispersion = Some(dispersion);
+  }
+
+  pub fn is_dispersive(&self) -> bool {
+    return self.dispersion.is_some();
+  }
+
+  pub fn refractive_index_at(&self, wavelength: Option<f64>) -> f64 {
+    return match (self.dispersion, wavelength) {
+      (Some(dispersion), Some(w)) => dispersion.index_at(w),
+      _ => self.refractive_index,
+    };
+  }
+
+  // The material as seen by light of a single wavelength: every color is
+  // upsampled to a spectrum and reduced to its value there.
+  pub fn at_wavelength(&self, wavelength: f64) -> Material {
+    let w = Some(wavelength);
+    let mut m = self.clone();
+    m.color = project(self.color, w);
+    m.emission = project(self.emission, w);
+    m.absorption = project(self.absorption, w);
+    m.pattern = self.pattern.as_ref().map(|p| p.at_wavelength(wavelength));
+    return m;
+  }
+
+  pub fn is_absorbing(&self) -> bool {
+    return self.density > 0.
+      && (self.absorption.r > 0. || self.absorption.g > 0. || self.absorption.b > 0.);
+  }
+
+  // The fraction of light, per channel, that survives a path of the given
+  // length through the material.
+  pub fn transmittance(&self, distance: f64) -> Color {
+    let d = self.density * distance;
+    return Color::new(
+      (-self.absorption.r * d).exp(),
+      (-self.absorption.g * d).exp(),
+      (-self.absorption.b * d).exp(),
+    );
+  }
+
+  pub fn is_emissive(&self) -> bool {
+    return self.emission.r > 0. || self.emission.g > 0. || self.emission.b > 0.;
+  }
 }
 
 #[test]
@@ -45,6 +146,21 @@ fn create_a_new_material() {
   assert_eq!(m.reflectiveness, 0.0);
   assert_eq!(m.transparency, 0.0);
   assert_eq!(m.refractive_index, 1.0);
+  assert_eq!(Color::equals(m.emission, Color::new(0., 0., 0.)), true);
+  assert_eq!(m.is_emissive(), false);
+  assert_eq!(m.shading_model, ShadingModel::Phong);
+  assert_eq!(m.roughness, 0.0);
+  assert_eq!(m.is_absorbing(), false);
+}
+
+#[test]
+fn create_a_pbr_material() {
+  let m = Material::pbr(Color::new(1., 0.5, 0.), 1.0, 0.25);
+
+  assert_eq!(m.shading_model, ShadingModel::Microfacet);
+  assert_eq!(Color::equals(m.color, Color::new(1., 0.5, 0.)), true);
+  assert_eq!(m.metallic, 1.0);
+  assert_eq!(m.roughness, 0.25);
 }
 
 #[test]
@@ -59,27 +175,27 @@ fn lighting_an_applied_pattern() {
   m.diffuse = 0.0;
   m.specular = 0.0;
 
-  let eyev = vector(0., 0., -1.);
-  let normalv = vector(0., 0., -1.);
-  let light = PointLight::new(point(0., 0., -10.), Color::new(1., 1., 1.));
+  let eyev = Vector3::new(0., 0., -1.);
+  let normalv = Normal3::new(0., 0., -1.);
+  let light = PointLight::new(Point3::new(0., 0., -10.), Color::new(1., 1., 1.));
 
   let mut o = Shape::new(ShapeType::Sphere);
   o.material = m.clone();
 
   let c1 = lighting(
-    o.material.clone(),
-    o.clone(),
+    &o.material,
+    &o,
     light,
-    point(0.9, 0., 0.),
+    Point3::new(0.9, 0., 0.),
     eyev,
     normalv,
     false,
   );
   let c2 = lighting(
-    o.material.clone(),
-    o.clone(),
+    &o.material,
+    &o,
     light,
-    point(1.5, 0., 0.),
+    Point3::new(1.5, 0., 0.),
     eyev,
     normalv,
     false,
@@ -88,3 +204,52 @@ fn lighting_an_applied_pattern() {
   assert_eq!(Color::equals(c1, Color::new(1., 1., 1.)), true);
   assert_eq!(Color::equals(c2, Color::new(0., 0., 0.)), true);
 }
+
+#[test]
+fn thicker_material_absorbs_more_light() {
+  let mut m = Material::new();
+  m.absorption = Color::new(0., 0.5, 1.);
+  m.density = 2.;
+
+  let thin = m.transmittance(0.5);
+  let thick = m.transmittance(2.);
+
+  assert_eq!(m.is_absorbing(), true);
+  assert_eq!(Color::equals(m.transmittance(0.), Color::new(1., 1., 1.)), true);
+  assert_eq!(thin.r, 1.);
+  assert_eq!((thin.g - (-0.5f64).exp()).abs() < 1e-10, true);
+  assert_eq!((thick.b - (-4.0f64).exp()).abs() < 1e-10, true);
+  assert_eq!(thick.g < thin.g, true);
+}
+
+#[test]
+fn a_dispersive_material_has_an_index_per_wavelength() {
+  let mut m = Material::new();
+  m.set_dispersion(Dispersion::bk7());
+
+  assert_eq!(m.is_dispersive(), true);
+  assert_eq!(m.refractive_index_at(None), m.refractive_index);
+  assert_eq!(
+    m.refractive_index_at(Some(NOMINAL_WAVELENGTH)),
+    m.refractive_index
+  );
+  assert_eq!(
+    m.refractive_index_at(Some(450.)) > m.refractive_index_at(Some(650.)),
+    true
+  );
+}
+
+#[test]
+fn a_material_seen_at_one_wavelength_is_grey() {
+  let mut m = Material::new();
+  m.color = Color::new(0.8, 0.4, 0.2);
+  m.emission = Color::new(0., 0., 3.);
+
+  let red = m.at_wavelength(650.);
+  let blue = m.at_wavelength(450.);
+
+  assert_eq!(Color::equals(red.color, Color::new(0.8, 0.8, 0.8)), true);
+  assert_eq!(Color::equals(red.emission, Color::new(0., 0., 0.)), true);
+  assert_eq!(Color::equals(blue.color, Color::new(0.2, 0.2, 0.2)), true);
+  assert_eq!(Color::equals(blue.emission, Color::new(3., 3., 3.)), true);
+}
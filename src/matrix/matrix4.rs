@@ -0,0 +1,245 @@
+use crate::matrix::simd;
+use crate::vectors::Tuple;
+use std::ops::Mul;
+
+// A 4x4 matrix held inline, for transforms. It mirrors the operations of
+// Matrix, which stays for general sizes, but never allocates and inverts in
+// closed form rather than by recursive cofactors.
+#[derive(Copy, Clone, Debug)]
+pub struct Matrix4 {
+  data: [f64; 16],
+}
+
+impl Matrix4 {
+  // d holds the sixteen entries row by row.
+  pub fn new(d: &[f64]) -> Matrix4 {
+    let mut data = [0.; 16];
+    data.copy_from_slice(&d[..16]);
+    return Matrix4 { data: data };
+  }
+
+  pub fn identity() -> Matrix4 {
+    return Matrix4::new(&[
+      1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+    ]);
+  }
+
+  pub fn get(&self, r: usize, c: usize) -> f64 {
+    return self.data[r * 4 + c];
+  }
+
+  pub fn set(&mut self, r: usize, c: usize, d: f64) {
+    self.data[r * 4 + c] = d;
+  }
+
+  pub fn equals(a: &Matrix4, b: &Matrix4) -> bool {
+    return (0..16).all(|i| (a.data[i] - b.data[i]).abs() <= 1e-10);
+  }
+
+  pub fn approx_equals(a: &Matrix4, b: &Matrix4) -> bool {
+    return (0..16).all(|i| (a.data[i] - b.data[i]).abs() <= 1e-4);
+  }
+
+  pub fn mult(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    return Matrix4 {
+      data: simd::mult(&a.data, &b.data),
+    };
+  }
+
+  pub fn transpose(a: &Matrix4) -> Matrix4 {
+    let mut out = [0.; 16];
+    for row in 0..4 {
+      for col in 0..4 {
+        out[col * 4 + row] = a.get(row, col);
+      }
+    }
+    return Matrix4 { data: out };
+  }
+
+  pub fn mult_4x4_by_1d(a: &Matrix4, b: &Tuple) -> Tuple {
+    return simd::mult_4x4_by_1d(&a.data, b);
+  }
+
+  // The 2x2 determinants of the top two rows (s) and the bottom two rows
+  // (c), from which both the determinant and the inverse are built.
+  fn sub_determinants(a: &Matrix4) -> ([f64; 6], [f64; 6]) {
+    let m = &a.data;
+    let s = [
+      m[0] * m[5] - m[4] * m[1],
+      m[0] * m[6] - m[4] * m[2],
+      m[0] * m[7] - m[4] * m[3],
+      m[1] * m[6] - m[5] * m[2],
+      m[1] * m[7] - m[5] * m[3],
+      m[2] * m[7] - m[6] * m[3],
+    ];
+    let c = [
+      m[8] * m[13] - m[12] * m[9],
+      m[8] * m[14] - m[12] * m[10],
+      m[8] * m[15] - m[12] * m[11],
+      m[9] * m[14] - m[13] * m[10],
+      m[9] * m[15] - m[13] * m[11],
+      m[10] * m[15] - m[14] * m[11],
+    ];
+    return (s, c);
+  }
+
+  pub fn determinant(a: &Matrix4) -> f64 {
+    let (s, c) = Matrix4::sub_determinants(a);
+    return s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+  }
+
+  pub fn invertible(a: &Matrix4) -> bool {
+    return Matrix4::determinant(a) != 0.;
+  }
+
+  pub fn inverse(a: &Matrix4) -> Matrix4 {
+    let (s, c) = Matrix4::sub_determinants(a);
+    let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+    if det == 0. {
+      panic!("cannot invert matrix")
+    }
+
+    let m = &a.data;
+    let inv = 1. / det;
+    let out = [
+      (m[5] * c[5] - m[6] * c[4] + m[7] * c[3]) * inv,
+      (-m[1] * c[5] + m[2] * c[4] - m[3] * c[3]) * inv,
+      (m[13] * s[5] - m[14] * s[4] + m[15] * s[3]) * inv,
+      (-m[9] * s[5] + m[10] * s[4] - m[11] * s[3]) * inv,
+      (-m[4] * c[5] + m[6] * c[2] - m[7] * c[1]) * inv,
+      (m[0] * c[5] - m[2] * c[2] + m[3] * c[1]) * inv,
+      (-m[12] * s[5] + m[14] * s[2] - m[15] * s[1]) * inv,
+      (m[8] * s[5] - m[10] * s[2] + m[11] * s[1]) * inv,
+      (m[4] * c[4] - m[5] * c[2] + m[7] * c[0]) * inv,
+      (-m[0] * c[4] + m[1] * c[2] - m[3] * c[0]) * inv,
+      (m[12] * s[4] - m[13] * s[2] + m[15] * s[0]) * inv,
+      (-m[8] * s[4] + m[9] * s[2] - m[11] * s[0]) * inv,
+      (-m[4] * c[3] + m[5] * c[1] - m[6] * c[0]) * inv,
+      (m[0] * c[3] - m[1] * c[1] + m[2] * c[0]) * inv,
+      (-m[12] * s[3] + m[13] * s[1] - m[14] * s[0]) * inv,
+      (m[8] * s[3] - m[9] * s[1] + m[10] * s[0]) * inv,
+    ];
+    return Matrix4 { data: out };
+  }
+}
+
+impl Mul for Matrix4 {
+  type Output = Matrix4;
+  fn mul(self, b: Matrix4) -> Matrix4 {
+    return Matrix4::mult(&self, &b);
+  }
+}
+
+impl Mul<Tuple> for Matrix4 {
+  type Output = Tuple;
+  fn mul(self, b: Tuple) -> Tuple {
+    return Matrix4::mult_4x4_by_1d(&self, &b);
+  }
+}
+
+// A transform together with the inverse and inverse transpose that rays
+// and normals need, worked out once when the transform is set rather than
+// for every ray.
+#[derive(Copy, Clone, Debug)]
+pub struct CachedTransform {
+  pub transform: Matrix4,
+  pub inverse: Matrix4,
+  pub inverse_transpose: Matrix4,
+}
+
+impl CachedTransform {
+  pub fn new(transform: Matrix4) -> CachedTransform {
+    let inverse = Matrix4::inverse(&transform);
+    return CachedTransform {
+      transform: transform,
+      inverse: inverse,
+      inverse_transpose: Matrix4::transpose(&inverse),
+    };
+  }
+
+  pub fn identity() -> CachedTransform {
+    return CachedTransform::new(Matrix4::identity());
+  }
+}
+
+#[test]
+fn multiplying_two_4x4_matrices() {
+  let a = Matrix4::new(&[
+    1., 2., 3., 4., 5., 6., 7., 8., 9., 8., 7., 6., 5., 4., 3., 2.,
+  ]);
+  let b = Matrix4::new(&[
+    -2., 1., 2., 3., 3., 2., 1., -1., 4., 3., 6., 5., 1., 2., 7., 8.,
+  ]);
+  let expected = Matrix4::new(&[
+    20., 22., 50., 48., 44., 54., 114., 108., 40., 58., 110., 102., 16., 26., 46., 42.,
+  ]);
+
+  assert_eq!(Matrix4::equals(&Matrix4::mult(&a, &b), &expected), true);
+}
+
+#[test]
+fn the_determinant_of_a_4x4_matrix() {
+  let a = Matrix4::new(&[
+    -2., -8., 3., 5., -3., 1., 7., 3., 1., 2., -9., 6., -6., 7., 7., -9.,
+  ]);
+
+  assert_eq!(Matrix4::determinant(&a), -4071.);
+  assert_eq!(Matrix4::invertible(&a), true);
+}
+
+#[test]
+fn closed_form_inversion_matches_the_cofactor_inverse() {
+  let data = vec![
+    8., -5., 9., 2., 7., 5., 6., 1., -6., 0., 9., 6., -3., 0., -9., -4.,
+  ];
+  let a = Matrix4::new(&data);
+  let expected = crate::matrix::Matrix::inverse(&crate::matrix::Matrix::new(4, 4, &data));
+  let inverse = Matrix4::inverse(&a);
+
+  for row in 0..4 {
+    for col in 0..4 {
+      assert_eq!(
+        (inverse.get(row, col) - expected.get(row, col)).abs() < 1e-10,
+        true
+      );
+    }
+  }
+  assert_eq!(
+    Matrix4::equals(&Matrix4::mult(&a, &inverse), &Matrix4::identity()),
+    true
+  );
+}
+
+#[test]
+fn a_cached_transform_keeps_its_inverse_transpose() {
+  let t = Matrix4::new(&[
+    2., 0., 0., 1., 0., 3., 0., 2., 0., 0., 4., 3., 0., 0., 0., 1.,
+  ]);
+  let cached = CachedTransform::new(t);
+
+  assert_eq!(
+    Matrix4::equals(&Matrix4::mult(&t, &cached.inverse), &Matrix4::identity()),
+    true
+  );
+  assert_eq!(
+    Matrix4::equals(
+      &cached.inverse_transpose,
+      &Matrix4::transpose(&cached.inverse)
+    ),
+    true
+  );
+}
+
+#[test]
+fn multiplying_with_operators() {
+  let a = Matrix4::new(&[
+    1., 2., 3., 4., 2., 4., 4., 2., 8., 6., 4., 1., 0., 0., 0., 1.,
+  ]);
+  let b = Matrix4::new(&[
+    -2., 1., 2., 3., 3., 2., 1., -1., 4., 3., 6., 5., 1., 2., 7., 8.,
+  ]);
+  let t = Tuple::new(1., 2., 3., 1.);
+
+  assert_eq!(Matrix4::equals(&(a * b), &Matrix4::mult(&a, &b)), true);
+  assert_eq!((a * t).equals(Tuple::new(18., 24., 33., 1.)), true);
+}
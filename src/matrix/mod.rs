@@ -1,4 +1,8 @@
 use crate::vectors::Tuple;
+use std::ops::Mul;
+
+pub mod matrix4;
+pub mod simd;
 
 #[derive(Clone)]
 pub struct Matrix {
@@ -185,6 +189,20 @@ impl Matrix {
   }
 }
 
+impl<'a> Mul for &'a Matrix {
+  type Output = Matrix;
+  fn mul(self, b: &Matrix) -> Matrix {
+    return Matrix::mult(self, b);
+  }
+}
+
+impl<'a> Mul<Tuple> for &'a Matrix {
+  type Output = Tuple;
+  fn mul(self, b: Tuple) -> Tuple {
+    return Matrix::mult_4x4_by_1d(self, &b);
+  }
+}
+
 #[test]
 fn it_creates_a_2x2_matrix() {
   let flat_data = vec![-3., 5., 1., -2.];
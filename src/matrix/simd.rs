@@ -0,0 +1,227 @@
+use crate::vectors::Tuple;
+
+// Kernels for the 4x4 products every ray and normal goes through, and for
+// the dot product. On x86_64
+// they use SSE2, which every x86_64 cpu has, working on two f64 lanes at a
+// time; elsewhere they fall back to the plain scalar versions, which stay
+// public so the two can be compared.
+//
+// Matrices are sixteen entries row by row, and tuples are four contiguous
+// f64s (Tuple is repr(C)), so both load straight into registers.
+//
+// The element by element Tuple operations (add, sub, mult) stay scalar:
+// the compiler already does as well with them as the intrinsics, going by
+// the benchmark below.
+
+pub fn mult_4x4_by_1d_scalar(m: &[f64; 16], b: &Tuple) -> Tuple {
+  return Tuple::new(
+    m[0] * b.x + m[1] * b.y + m[2] * b.z + m[3] * b.w,
+    m[4] * b.x + m[5] * b.y + m[6] * b.z + m[7] * b.w,
+    m[8] * b.x + m[9] * b.y + m[10] * b.z + m[11] * b.w,
+    m[12] * b.x + m[13] * b.y + m[14] * b.z + m[15] * b.w,
+  );
+}
+
+pub fn dot_scalar(a: &Tuple, b: &Tuple) -> f64 {
+  return a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+}
+
+pub fn mult_scalar(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+  let mut out = [0.; 16];
+  for row in 0..4 {
+    for col in 0..4 {
+      out[row * 4 + col] = a[row * 4] * b[col]
+        + a[row * 4 + 1] * b[4 + col]
+        + a[row * 4 + 2] * b[8 + col]
+        + a[row * 4 + 3] * b[12 + col];
+    }
+  }
+  return out;
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn mult_4x4_by_1d(m: &[f64; 16], b: &Tuple) -> Tuple {
+  use std::arch::x86_64::*;
+
+  let mut out = [0.; 4];
+  // SAFETY: sse2 is part of x86_64. Tuple is repr(C), four f64s back to
+  // back, so a pointer to the whole struct covers both pairs; the other
+  // unaligned loads and stores stay within m and out.
+  let bp = (b as *const Tuple).cast::<f64>();
+  unsafe {
+    let b_xy = _mm_loadu_pd(bp);
+    let b_zw = _mm_loadu_pd(bp.add(2));
+    // each row as a pair of partial sums, still to be added across
+    let row = |r: usize| {
+      let p = m.as_ptr().add(r * 4);
+      _mm_add_pd(
+        _mm_mul_pd(_mm_loadu_pd(p), b_xy),
+        _mm_mul_pd(_mm_loadu_pd(p.add(2)), b_zw),
+      )
+    };
+    let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+    let xy = _mm_add_pd(_mm_unpacklo_pd(r0, r1), _mm_unpackhi_pd(r0, r1));
+    let zw = _mm_add_pd(_mm_unpacklo_pd(r2, r3), _mm_unpackhi_pd(r2, r3));
+    _mm_storeu_pd(out.as_mut_ptr(), xy);
+    _mm_storeu_pd(out.as_mut_ptr().add(2), zw);
+  }
+  return Tuple::new(out[0], out[1], out[2], out[3]);
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn mult_4x4_by_1d(m: &[f64; 16], b: &Tuple) -> Tuple {
+  return mult_4x4_by_1d_scalar(m, b);
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn dot(a: &Tuple, b: &Tuple) -> f64 {
+  use std::arch::x86_64::*;
+
+  // SAFETY: as above, Tuple is repr(C), so each pointer covers the four
+  // f64s of its struct and the loads stay within a and b
+  let ap = (a as *const Tuple).cast::<f64>();
+  let bp = (b as *const Tuple).cast::<f64>();
+  unsafe {
+    let products = _mm_add_pd(
+      _mm_mul_pd(_mm_loadu_pd(ap), _mm_loadu_pd(bp)),
+      _mm_mul_pd(_mm_loadu_pd(ap.add(2)), _mm_loadu_pd(bp.add(2))),
+    );
+    return _mm_cvtsd_f64(_mm_add_sd(products, _mm_unpackhi_pd(products, products)));
+  }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn dot(a: &Tuple, b: &Tuple) -> f64 {
+  return dot_scalar(a, b);
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn mult(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+  use std::arch::x86_64::*;
+
+  let mut out = [0.; 16];
+  // safe: as above, every access is within the three arrays
+  unsafe {
+    let bp = b.as_ptr();
+    let b_rows = [
+      (_mm_loadu_pd(bp), _mm_loadu_pd(bp.add(2))),
+      (_mm_loadu_pd(bp.add(4)), _mm_loadu_pd(bp.add(6))),
+      (_mm_loadu_pd(bp.add(8)), _mm_loadu_pd(bp.add(10))),
+      (_mm_loadu_pd(bp.add(12)), _mm_loadu_pd(bp.add(14))),
+    ];
+    // each row of the product is a's row weighting the rows of b
+    for row in 0..4 {
+      let mut left = _mm_setzero_pd();
+      let mut right = _mm_setzero_pd();
+      for k in 0..4 {
+        let weight = _mm_set1_pd(a[row * 4 + k]);
+        left = _mm_add_pd(left, _mm_mul_pd(weight, b_rows[k].0));
+        right = _mm_add_pd(right, _mm_mul_pd(weight, b_rows[k].1));
+      }
+      _mm_storeu_pd(out.as_mut_ptr().add(row * 4), left);
+      _mm_storeu_pd(out.as_mut_ptr().add(row * 4 + 2), right);
+    }
+  }
+  return out;
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn mult(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+  return mult_scalar(a, b);
+}
+
+#[cfg(test)]
+fn sample_matrix(seed: f64) -> [f64; 16] {
+  let mut m = [0.; 16];
+  for i in 0..16 {
+    m[i] = ((i as f64 + 1.) * seed).sin() * 10.;
+  }
+  return m;
+}
+
+#[test]
+fn the_simd_kernels_match_the_scalar_ones() {
+  let a = sample_matrix(0.37);
+  let b = sample_matrix(1.91);
+  let t = Tuple::new(1.5, -2.25, 3.125, 1.);
+
+  assert_eq!(mult_4x4_by_1d(&a, &t).equals(mult_4x4_by_1d_scalar(&a, &t)), true);
+  assert_eq!((dot(&t, &t) - dot_scalar(&t, &t)).abs() < 1e-12, true);
+  let fast = mult(&a, &b);
+  let plain = mult_scalar(&a, &b);
+  for i in 0..16 {
+    assert_eq!((fast[i] - plain[i]).abs() < 1e-12, true);
+  }
+}
+
+// Timings for the kernels against the scalar code, which is what Matrix4
+// ran before. Run with
+//   cargo test --release vector_math_benchmark -- --ignored --nocapture
+#[test]
+#[ignore]
+fn vector_math_benchmark() {
+  use std::hint::black_box;
+  use std::time::Instant;
+
+  const N: usize = 10_000_000;
+  let a = sample_matrix(0.37);
+  let b = sample_matrix(1.91);
+  let t = Tuple::new(1.5, -2.25, 3.125, 1.);
+
+  let time = |name: &str, f: &dyn Fn() -> f64| {
+    let start = Instant::now();
+    let checksum = f();
+    let ns = start.elapsed().as_nanos() as f64 / N as f64;
+    println!("{:<24} {:>6.2} ns/op (checksum {:.3})", name, ns, checksum);
+  };
+
+  time("matrix * tuple, scalar", &|| {
+    let mut sum = 0.;
+    for _i in 0..N {
+      sum += mult_4x4_by_1d_scalar(black_box(&a), black_box(&t)).x;
+    }
+    sum
+  });
+  time("matrix * tuple, simd", &|| {
+    let mut sum = 0.;
+    for _i in 0..N {
+      sum += mult_4x4_by_1d(black_box(&a), black_box(&t)).x;
+    }
+    sum
+  });
+  time("tuple . tuple, scalar", &|| {
+    let mut sum = 0.;
+    for _i in 0..N {
+      sum += dot_scalar(black_box(&t), black_box(&t));
+    }
+    sum
+  });
+  time("tuple . tuple, simd", &|| {
+    let mut sum = 0.;
+    for _i in 0..N {
+      sum += dot(black_box(&t), black_box(&t));
+    }
+    sum
+  });
+  time("tuple + tuple", &|| {
+    let mut sum = t;
+    for _i in 0..N {
+      sum = black_box(sum).add(black_box(t));
+    }
+    sum.x
+  });
+  time("matrix * matrix, scalar", &|| {
+    let mut sum = 0.;
+    for _i in 0..N {
+      sum += mult_scalar(black_box(&a), black_box(&b))[5];
+    }
+    sum
+  });
+  time("matrix * matrix, simd", &|| {
+    let mut sum = 0.;
+    for _i in 0..N {
+      sum += mult(black_box(&a), black_box(&b))[5];
+    }
+    sum
+  });
+}
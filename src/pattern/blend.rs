@@ -0,0 +1,67 @@
+use crate::colors::Color;
+use crate::pattern::procedural::Procedural;
+use crate::pattern::{Pattern, PatternType};
+use crate::vectors::typed::Point3;
+use crate::Transform;
+
+// The weighted average of two patterns, weight being the share of b.
+#[derive(Clone)]
+pub struct Blend {
+  a: Pattern,
+  b: Pattern,
+  weight: f64,
+}
+
+impl Blend {
+  pub fn new(a: Pattern, b: Pattern, weight: f64) -> Blend {
+    return Blend {
+      a: a,
+      b: b,
+      weight: weight,
+    };
+  }
+}
+
+impl Procedural for Blend {
+  fn pattern_at(&self, point: Point3) -> Color {
+    return Color::add(
+      Color::mult(self.a.pattern_at_nested(point), 1. - self.weight),
+      Color::mult(self.b.pattern_at_nested(point), self.weight),
+    );
+  }
+}
+
+#[test]
+fn blending_averages_two_patterns() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let red = Color::new(1., 0., 0.);
+
+  let stripes = Pattern::new(PatternType::Stripe, white, black);
+  let mut crossed = Pattern::new(PatternType::Stripe, red, black);
+  crossed.set_transform(
+    Transform::new()
+      .rotate_y(-std::f64::consts::PI / 2.)
+      .transform,
+  );
+  let pattern = Pattern::blend(stripes, crossed, 0.5);
+
+  assert_eq!(
+    Color::equals(
+      pattern.pattern_at(Point3::new(0.5, 0., 0.5)),
+      Color::new(1., 0.5, 0.5)
+    ),
+    true
+  );
+  assert_eq!(
+    Color::equals(
+      pattern.pattern_at(Point3::new(1.5, 0., 0.5)),
+      Color::new(0.5, 0., 0.)
+    ),
+    true
+  );
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(1.5, 0., 1.5)), black),
+    true
+  );
+}
@@ -1,32 +1,29 @@
 use crate::colors::Color;
-use crate::matrix::Matrix;
+use crate::pattern::procedural::Procedural;
 use crate::pattern::{Pattern, PatternType};
-use crate::shape::{Shape, ShapeType};
-use crate::vectors::{point, vector, Tuple};
-use crate::Transform;
+use crate::vectors::typed::Point3;
 
 #[derive(Clone)]
 pub struct Checker {
-  a: Color,
-  b: Color,
+  a: Pattern,
+  b: Pattern,
 }
 
 impl Checker {
-  pub fn new(color1: Color, color2: Color) -> Checker {
-    return Checker {
-      a: color1,
-      b: color2,
-    };
+  pub fn new(a: Pattern, b: Pattern) -> Checker {
+    return Checker { a: a, b: b };
   }
+}
 
-  pub fn pattern_at(pattern: &Pattern, point: Tuple) -> Color {
+impl Procedural for Checker {
+  fn pattern_at(&self, point: Point3) -> Color {
     if ((point.x.round().floor() + point.y.round().floor() + point.z.round().floor()) % 2.0f64)
       .abs()
       < 1e-9
     {
-      return pattern.a.clone();
+      return self.a.pattern_at_nested(point);
     } else {
-      return pattern.b.clone();
+      return self.b.pattern_at_nested(point);
     }
   }
 }
@@ -39,15 +36,15 @@ fn checker_should_repeat_in_x() {
   let pattern = Pattern::new(PatternType::Checker, white, black);
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 0.,)), white.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 0.,)), white.clone()),
     true
   );
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0.99, 0., 0.,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0.99, 0., 0.,)), black.clone()),
     true
   );
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(1.01, 0., 0.,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(1.01, 0., 0.,)), black.clone()),
     true
   );
 }
@@ -59,15 +56,15 @@ fn checker_should_repeat_in_y() {
   let pattern = Pattern::new(PatternType::Checker, white, black);
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 0.,)), white.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 0.,)), white.clone()),
     true
   );
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0.99, 0.,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0.99, 0.,)), black.clone()),
     true
   );
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0.0, 1.01, 0.,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0.0, 1.01, 0.,)), black.clone()),
     true
   );
 }
@@ -79,15 +76,15 @@ fn checker_should_repeat_in_z() {
   let pattern = Pattern::new(PatternType::Checker, white, black);
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 0.,)), white.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 0.,)), white.clone()),
     true
   );
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 0.99,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 0.99,)), black.clone()),
     true
   );
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0.0, 0., 1.01,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0.0, 0., 1.01,)), black.clone()),
     true
   );
 }
@@ -1,29 +1,27 @@
 use crate::colors::Color;
-use crate::matrix::Matrix;
+use crate::pattern::procedural::Procedural;
 use crate::pattern::{Pattern, PatternType};
-use crate::shape::{Shape, ShapeType};
-use crate::vectors::{point, vector, Tuple};
-use crate::Transform;
+use crate::vectors::typed::Point3;
 
 #[derive(Clone)]
 pub struct Gradient {
-  a: Color,
-  b: Color,
+  a: Pattern,
+  b: Pattern,
 }
 
 impl Gradient {
-  pub fn new(color1: Color, color2: Color) -> Gradient {
-    return Gradient {
-      a: color1,
-      b: color2,
-    };
+  pub fn new(a: Pattern, b: Pattern) -> Gradient {
+    return Gradient { a: a, b: b };
   }
+}
 
-  pub fn pattern_at(pattern: &Pattern, point: Tuple) -> Color {
-    let distance = Color::sub(pattern.b, pattern.a);
+impl Procedural for Gradient {
+  fn pattern_at(&self, point: Point3) -> Color {
+    let a = self.a.pattern_at_nested(point);
+    let distance = Color::sub(self.b.pattern_at_nested(point), a);
     let fraction = point.x - point.x.floor();
 
-    return Color::add(pattern.a, Color::mult(distance, fraction));
+    return Color::add(a, Color::mult(distance, fraction));
   }
 }
 
@@ -35,13 +33,13 @@ fn creating_a_gradient() {
   let pattern = Pattern::new(PatternType::Gradient, white, black);
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 0.,)), white.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 0.,)), white.clone()),
     true
   );
 
   assert_eq!(
     Color::equals(
-      pattern.pattern_at(point(0.25, 0., 0.,)),
+      pattern.pattern_at(Point3::new(0.25, 0., 0.,)),
       Color::new(0.75, 0.75, 0.75)
     ),
     true
@@ -49,7 +47,7 @@ fn creating_a_gradient() {
 
   assert_eq!(
     Color::equals(
-      pattern.pattern_at(point(0.5, 0., 0.,)),
+      pattern.pattern_at(Point3::new(0.5, 0., 0.,)),
       Color::new(0.5, 0.5, 0.5)
     ),
     true
@@ -57,7 +55,7 @@ fn creating_a_gradient() {
 
   assert_eq!(
     Color::equals(
-      pattern.pattern_at(point(0.75, 0., 0.,)),
+      pattern.pattern_at(Point3::new(0.75, 0., 0.,)),
       Color::new(0.25, 0.25, 0.25)
     ),
     true
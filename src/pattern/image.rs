@@ -0,0 +1,299 @@
+use crate::canvas::Canvas;
+use crate::colors::Color;
+use crate::spectrum::project;
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureFilter {
+  Nearest,
+  Bilinear,
+}
+
+// What happens to uv coordinates outside [0, 1): the image either tiles or
+// its edge pixels are stretched outwards.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureWrap {
+  Repeat,
+  Clamp,
+}
+
+// An image looked up by (u, v), with v = 0 at the bottom row. Alongside the
+// image it keeps a mip chain of box filtered copies, each half the size of
+// the one before, for filtering over large footprints. They are shared,
+// since patterns are copied along with every shape that uses them.
+#[derive(Clone, Debug)]
+pub struct ImageTexture {
+  levels: Arc<Vec<Canvas>>,
+  pub filter: TextureFilter,
+  pub wrap: TextureWrap,
+  wavelength: Option<f64>,
+}
+
+impl ImageTexture {
+  pub fn new(canvas: Canvas) -> ImageTexture {
+    return ImageTexture {
+      levels: Arc::new(mip_chain(canvas)),
+      filter: TextureFilter::Bilinear,
+      wrap: TextureWrap::Repeat,
+      wavelength: None,
+    };
+  }
+
+  pub fn with_options(canvas: Canvas, filter: TextureFilter, wrap: TextureWrap) -> ImageTexture {
+    let mut texture = ImageTexture::new(canvas);
+    texture.filter = filter;
+    texture.wrap = wrap;
+    return texture;
+  }
+
+  // Texels are converted lazily, so the image itself is never copied.
+  pub fn at_wavelength(&self, wavelength: f64) -> ImageTexture {
+    let mut texture = self.clone();
+    texture.wavelength = Some(wavelength);
+    return texture;
+  }
+
+  pub fn levels(&self) -> usize {
+    return self.levels.len();
+  }
+
+  fn texel(&self, canvas: &Canvas, x: i64, y: i64) -> Color {
+    let width = canvas.width() as i64;
+    let height = canvas.height() as i64;
+    let (x, y) = match self.wrap {
+      TextureWrap::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+      TextureWrap::Clamp => (x.max(0).min(width - 1), y.max(0).min(height - 1)),
+    };
+    return canvas.pixel_at(x as usize, y as usize);
+  }
+
+  fn sample(&self, level: usize, u: f64, v: f64) -> Color {
+    let canvas = &self.levels[level];
+    // continuous texel coordinates, with texel centers on half integers
+    let x = u * canvas.width() as f64;
+    let y = (1. - v) * canvas.height() as f64;
+
+    return match self.filter {
+      TextureFilter::Nearest => self.texel(canvas, x.floor() as i64, y.floor() as i64),
+      TextureFilter::Bilinear => {
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = Color::add(
+          Color::mult(self.texel(canvas, x0, y0), 1. - tx),
+          Color::mult(self.texel(canvas, x0 + 1, y0), tx),
+        );
+        let bottom = Color::add(
+          Color::mult(self.texel(canvas, x0, y0 + 1), 1. - tx),
+          Color::mult(self.texel(canvas, x0 + 1, y0 + 1), tx),
+        );
+        Color::add(Color::mult(top, 1. - ty), Color::mult(bottom, ty))
+      }
+    };
+  }
+
+  pub fn uv_color_at(&self, u: f64, v: f64) -> Color {
+    return project(self.sample(0, u, v), self.wavelength);
+  }
+
+  // The image averaged over a du by dv patch around (u, v), read from the
+  // mip level whose texels are about the size of the patch. Bilinear
+  // textures blend between the two nearest levels.
+  pub fn uv_color_filtered(&self, u: f64, v: f64, du: f64, dv: f64) -> Color {
+    let base = &self.levels[0];
+    let width = (du * base.width() as f64).max(dv * base.height() as f64);
+    if width <= 1. {
+      return self.uv_color_at(u, v);
+    }
+
+    let top = (self.levels.len() - 1) as f64;
+    let level = width.log2().min(top);
+    let color = match self.filter {
+      TextureFilter::Nearest => self.sample(level.round() as usize, u, v),
+      TextureFilter::Bilinear => {
+        let lower = level.floor();
+        let t = level - lower;
+        let upper = (lower + 1.).min(top);
+        Color::add(
+          Color::mult(self.sample(lower as usize, u, v), 1. - t),
+          Color::mult(self.sample(upper as usize, u, v), t),
+        )
+      }
+    };
+    return project(color, self.wavelength);
+  }
+}
+
+// The image followed by successively halved copies, down to a single
+// pixel. Odd sizes round down, with the last row or column folded into
+// its neighbour.
+fn mip_chain(canvas: Canvas) -> Vec<Canvas> {
+  let mut levels = vec![canvas];
+  loop {
+    let previous = &levels[levels.len() - 1];
+    let (w, h) = (previous.width(), previous.height());
+    if w == 1 && h == 1 {
+      break;
+    }
+
+    let (nw, nh) = ((w / 2).max(1), (h / 2).max(1));
+    let mut next = Canvas::new(nw, nh);
+    for y in 0..nh {
+      for x in 0..nw {
+        let mut sum = Color::new(0., 0., 0.);
+        let mut count = 0.;
+        let (x0, x1) = (x * w / nw, ((x + 1) * w / nw).max(x * w / nw + 1));
+        let (y0, y1) = (y * h / nh, ((y + 1) * h / nh).max(y * h / nh + 1));
+        for sy in y0..y1 {
+          for sx in x0..x1 {
+            sum = Color::add(sum, previous.pixel_at(sx, sy));
+            count += 1.;
+          }
+        }
+        next.set(x, y, Color::div(sum, count));
+      }
+    }
+    levels.push(next);
+  }
+  return levels;
+}
+
+#[cfg(test)]
+fn two_by_two() -> Canvas {
+  // black and white on the top row, red and green below
+  let mut canvas = Canvas::new(2, 2);
+  canvas.set(0, 0, Color::new(0., 0., 0.));
+  canvas.set(1, 0, Color::new(1., 1., 1.));
+  canvas.set(0, 1, Color::new(1., 0., 0.));
+  canvas.set(1, 1, Color::new(0., 1., 0.));
+  return canvas;
+}
+
+#[test]
+fn nearest_filtering_picks_the_texel_under_the_point() {
+  let texture =
+    ImageTexture::with_options(two_by_two(), TextureFilter::Nearest, TextureWrap::Repeat);
+
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.25, 0.75), Color::new(0., 0., 0.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.75, 0.75), Color::new(1., 1., 1.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.25, 0.25), Color::new(1., 0., 0.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.75, 0.25), Color::new(0., 1., 0.)),
+    true
+  );
+}
+
+#[test]
+fn bilinear_filtering_blends_neighbouring_texels() {
+  let texture =
+    ImageTexture::with_options(two_by_two(), TextureFilter::Bilinear, TextureWrap::Clamp);
+
+  // exactly on a texel center
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.75, 0.75), Color::new(1., 1., 1.)),
+    true
+  );
+  // halfway between the four texel centers
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.5, 0.5), Color::new(0.5, 0.5, 0.25)),
+    true
+  );
+}
+
+#[test]
+fn wrap_modes_repeat_or_clamp_the_image() {
+  let repeat =
+    ImageTexture::with_options(two_by_two(), TextureFilter::Nearest, TextureWrap::Repeat);
+  let clamp = ImageTexture::with_options(two_by_two(), TextureFilter::Nearest, TextureWrap::Clamp);
+
+  // one tile to the right of the black texel
+  assert_eq!(
+    Color::equals(repeat.uv_color_at(1.25, 0.75), Color::new(0., 0., 0.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(clamp.uv_color_at(1.25, 0.75), Color::new(1., 1., 1.)),
+    true
+  );
+  // below the image
+  assert_eq!(
+    Color::equals(repeat.uv_color_at(0.25, -0.25), Color::new(0., 0., 0.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(clamp.uv_color_at(0.25, -0.25), Color::new(1., 0., 0.)),
+    true
+  );
+}
+
+#[test]
+fn an_image_at_one_wavelength_is_grey() {
+  let texture =
+    ImageTexture::with_options(two_by_two(), TextureFilter::Nearest, TextureWrap::Repeat)
+      .at_wavelength(550.);
+
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.75, 0.25), Color::new(1., 1., 1.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(texture.uv_color_at(0.25, 0.25), Color::new(0., 0., 0.)),
+    true
+  );
+}
+
+#[test]
+fn the_mip_chain_halves_down_to_one_pixel() {
+  let mut canvas = Canvas::new(8, 3);
+  for y in 0..3 {
+    for x in 0..8 {
+      let c = if (x + y) % 2 == 0 { 1. } else { 0. };
+      canvas.set(x, y, Color::new(c, c, c));
+    }
+  }
+  let texture = ImageTexture::new(canvas);
+
+  // 8x3, 4x1, 2x1, 1x1
+  assert_eq!(texture.levels(), 4);
+  let average = texture.levels[3].pixel_at(0, 0);
+  assert_eq!((average.r - 0.5).abs() < 0.05, true);
+}
+
+#[test]
+fn a_large_footprint_reads_the_averaged_image() {
+  let mut canvas = Canvas::new(64, 64);
+  for y in 0..64 {
+    for x in 0..64 {
+      let c = if (x + y) % 2 == 0 { 1. } else { 0. };
+      canvas.set(x, y, Color::new(c, c, c));
+    }
+  }
+  let texture = ImageTexture::with_options(canvas, TextureFilter::Bilinear, TextureWrap::Repeat);
+
+  // a point sample on a texel center reads a single black or white texel
+  let (u, v) = (19.5 / 64., 1. - 19.5 / 64.);
+  let point = texture.uv_color_at(u, v);
+  assert_eq!(point.r == 0. || point.r == 1., true);
+
+  // a footprint of many texels sees the grey average
+  let filtered = texture.uv_color_filtered(0.3, 0.3, 0.25, 0.25);
+  assert_eq!((filtered.r - 0.5).abs() < 1e-9, true);
+
+  // a footprint under one texel is not blurred
+  let sharp = texture.uv_color_filtered(u, v, 0.001, 0.001);
+  assert_eq!(Color::equals(sharp, point), true);
+}
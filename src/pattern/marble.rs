@@ -0,0 +1,53 @@
+use crate::colors::Color;
+use crate::pattern::noise::turbulence;
+use crate::pattern::procedural::Procedural;
+use crate::pattern::{Pattern, PatternType};
+use crate::vectors::typed::Point3;
+use std::f64;
+
+// How far the veins wander from straight bands along x.
+const VEIN_TURBULENCE: f64 = 4.;
+const OCTAVES: usize = 6;
+
+#[derive(Clone)]
+pub struct Marble {
+  a: Pattern,
+  b: Pattern,
+}
+
+impl Marble {
+  pub fn new(a: Pattern, b: Pattern) -> Marble {
+    return Marble { a: a, b: b };
+  }
+}
+
+impl Procedural for Marble {
+  // Bands of a and b along x, bent into veins by turbulence.
+  fn pattern_at(&self, point: Point3) -> Color {
+    let t = point.x + VEIN_TURBULENCE * turbulence(point, OCTAVES);
+    let fraction = 0.5 + 0.5 * (t * f64::consts::PI).sin();
+
+    return Color::add(
+      Color::mult(self.a.pattern_at_nested(point), 1. - fraction),
+      Color::mult(self.b.pattern_at_nested(point), fraction),
+    );
+  }
+}
+
+#[test]
+fn marble_stays_between_its_colors() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let pattern = Pattern::new(PatternType::Marble, white, black);
+
+  let mut seen_dark = false;
+  let mut seen_light = false;
+  for i in 0..200 {
+    let t = i as f64 * 0.05;
+    let c = pattern.pattern_at(Point3::new(t, 0.3 * t, 0.7));
+    assert_eq!(c.r >= 0. && c.r <= 1., true);
+    seen_dark = seen_dark || c.r < 0.2;
+    seen_light = seen_light || c.r > 0.8;
+  }
+  assert_eq!(seen_dark && seen_light, true);
+}
@@ -0,0 +1,65 @@
+use crate::colors::Color;
+use crate::pattern::procedural::Procedural;
+use crate::pattern::{Pattern, PatternType};
+use crate::vectors::typed::Point3;
+
+// a where the mask is black and b where it is white, mixing the two in
+// between. The mask chooses rather than colors, so it is read in rgb even
+// when the pattern is seen at a single wavelength.
+#[derive(Clone)]
+pub struct Mask {
+  mask: Pattern,
+  a: Pattern,
+  b: Pattern,
+}
+
+impl Mask {
+  pub fn new(mask: Pattern, a: Pattern, b: Pattern) -> Mask {
+    return Mask {
+      mask: mask,
+      a: a,
+      b: b,
+    };
+  }
+}
+
+impl Procedural for Mask {
+  // The mask's brightness, averaged over its channels, picks between a and
+  // b.
+  fn pattern_at(&self, point: Point3) -> Color {
+    let m = self.mask.pattern_at_nested(point);
+    let weight = ((m.r + m.g + m.b) / 3.).max(0.).min(1.);
+
+    return Color::add(
+      Color::mult(self.a.pattern_at_nested(point), 1. - weight),
+      Color::mult(self.b.pattern_at_nested(point), weight),
+    );
+  }
+}
+
+#[test]
+fn a_mask_chooses_between_two_patterns() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let red = Color::new(1., 0., 0.);
+  let blue = Color::new(0., 0., 1.);
+
+  let mask = Pattern::new(PatternType::Stripe, black, white);
+  let a = Pattern::solid(red);
+  let b = Pattern::new(PatternType::Checker, blue, white);
+  let pattern = Pattern::mask(mask, a, b);
+
+  // black in the mask shows a, white shows b's checkers
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(0.5, 0., 0.)), red),
+    true
+  );
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(1.2, 0., 0.)), white),
+    true
+  );
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(1.2, 0., 1.)), blue),
+    true
+  );
+}
@@ -1,69 +1,189 @@
 use crate::colors::Color;
-use crate::matrix::Matrix;
+use crate::matrix::matrix4::{CachedTransform, Matrix4};
 use crate::shape::{Shape, ShapeType};
-use crate::vectors::{point, vector, Tuple};
+use crate::spectrum::project;
+use crate::vectors::typed::{Point3, Vector3};
 use crate::Transform;
+use std::sync::Arc;
 
+pub mod blend;
 pub mod checker;
 pub mod gradient;
+pub mod image;
+pub mod marble;
+pub mod mask;
+pub mod noise;
+pub mod perturbed;
+pub mod procedural;
 pub mod ring;
 pub mod stripe;
+pub mod texture_map;
+pub mod wood;
 
+use self::image::ImageTexture;
+use self::procedural::Procedural;
+use self::texture_map::{TextureMap, UvMapping, UvPattern};
+
+// The built in patterns that alternate between two others, as made by
+// Pattern::new and Pattern::nested.
 #[derive(Clone)]
 pub enum PatternType {
   Checker,
   Gradient,
+  Marble,
   Ring,
   Stripe,
   Test,
+  Wood,
 }
 
+// Any procedural pattern, built in or not, with its own transform. Two
+// colored patterns such as stripes alternate between a and b, which are
+// patterns themselves; plain colors are solid patterns.
 #[derive(Clone)]
 pub struct Pattern {
-  pattern_type: PatternType,
-  a: Color,
-  b: Color,
-  transform: Matrix,
+  procedural: Arc<dyn Procedural>,
+  transform: CachedTransform,
+  // set for patterns seen by light of a single wavelength; patterns only
+  // know rgb, so their colors are projected as they are looked up
+  wavelength: Option<f64>,
+}
+
+// A single color everywhere.
+struct Solid {
+  color: Color,
+}
+
+impl Procedural for Solid {
+  fn pattern_at(&self, _point: Point3) -> Color {
+    return self.color;
+  }
+}
+
+// The point itself as a color, for checking the transforms.
+struct TestPattern;
+
+impl Procedural for TestPattern {
+  fn pattern_at(&self, point: Point3) -> Color {
+    println!(
+      "************* test pattern {} {} {}",
+      point.x, point.y, point.z
+    );
+    return Color::new(point.x, point.y, point.z);
+  }
 }
 
 impl Pattern {
   pub fn new(pattern_type: PatternType, color1: Color, color2: Color) -> Pattern {
-    return Pattern {
-      pattern_type: pattern_type,
-      a: color1,
-      b: color2,
-      transform: Matrix::identity(4),
+    return Pattern::nested(pattern_type, Pattern::solid(color1), Pattern::solid(color2));
+  }
+
+  // Like new, alternating between two patterns rather than two colors.
+  pub fn nested(pattern_type: PatternType, a: Pattern, b: Pattern) -> Pattern {
+    return match pattern_type {
+      PatternType::Checker => Pattern::custom(Arc::new(checker::Checker::new(a, b))),
+      PatternType::Gradient => Pattern::custom(Arc::new(gradient::Gradient::new(a, b))),
+      PatternType::Marble => Pattern::custom(Arc::new(marble::Marble::new(a, b))),
+      PatternType::Ring => Pattern::custom(Arc::new(ring::Ring::new(a, b))),
+      PatternType::Stripe => Pattern::custom(Arc::new(stripe::Stripe::new(a, b))),
+      PatternType::Test => Pattern::custom(Arc::new(TestPattern)),
+      PatternType::Wood => Pattern::custom(Arc::new(wood::Wood::new(a, b))),
     };
   }
 
-  pub fn pattern_at(&self, point: Tuple) -> Color {
-    return match &self.pattern_type {
-      PatternType::Checker => checker::Checker::pattern_at(self, point),
-      PatternType::Gradient => gradient::Gradient::pattern_at(self, point),
-      PatternType::Stripe => stripe::Stripe::pattern_at(self, point),
-      PatternType::Ring => ring::Ring::pattern_at(self, point),
-      PatternType::Test => {
-        println!(
-          "************* test pattern {} {} {}",
-          point.x, point.y, point.z
-        );
-        return Color::new(point.x, point.y, point.z);
-      }
+  pub fn solid(color: Color) -> Pattern {
+    return Pattern::custom(Arc::new(Solid { color: color }));
+  }
+
+  // The weighted average of two patterns, weight being the share of b.
+  pub fn blend(a: Pattern, b: Pattern, weight: f64) -> Pattern {
+    return Pattern::custom(Arc::new(blend::Blend::new(a, b, weight)));
+  }
+
+  // a where the mask is black and b where it is white, mixing the two in
+  // between.
+  pub fn mask(mask: Pattern, a: Pattern, b: Pattern) -> Pattern {
+    return Pattern::custom(Arc::new(mask::Mask::new(mask, a, b)));
+  }
+
+  // A uv pattern wrapped around the object by the map.
+  pub fn texture_map(map: TextureMap) -> Pattern {
+    return Pattern::custom(Arc::new(map));
+  }
+
+  // A photograph, decal or label, placed on the object by the mapping.
+  pub fn image(texture: ImageTexture, mapping: UvMapping) -> Pattern {
+    return Pattern::texture_map(TextureMap::new(UvPattern::Image(texture), mapping));
+  }
+
+  // Any pattern at all; see Procedural.
+  pub fn custom(procedural: Arc<dyn Procedural>) -> Pattern {
+    return Pattern {
+      procedural: procedural,
+      transform: CachedTransform::identity(),
+      wavelength: None,
     };
   }
 
-  pub fn set_transform(&mut self, transform: Matrix) {
-    self.transform = transform;
+  pub fn from_fn<F>(f: F) -> Pattern
+  where
+    F: Fn(Point3) -> Color + Send + Sync + 'static,
+  {
+    return Pattern::custom(Arc::new(f));
+  }
+
+  // Another pattern with its edges roughened by noise, scale being the
+  // largest distance a point is moved.
+  pub fn perturbed(pattern: Pattern, scale: f64) -> Pattern {
+    return Pattern::custom(Arc::new(perturbed::Perturbed::new(pattern, scale)));
+  }
+
+  pub fn pattern_at(&self, point: Point3) -> Color {
+    return project(self.procedural.pattern_at(point), self.wavelength);
   }
 
-  pub fn pattern_at_object(&self, object: Shape, point: Tuple) -> Color {
-    let i_object_tx = Matrix::inverse(&object.transform);
-    let object_point = Matrix::mult_4x4_by_1d(&i_object_tx, &point);
-    let i_pattern_tx = Matrix::inverse(&self.transform);
-    let pattern_point = Matrix::mult_4x4_by_1d(&i_pattern_tx, &object_point);
+  // The pattern at a point given in the space of a pattern containing this
+  // one, so that inner patterns can be moved with their own transform.
+  pub fn pattern_at_nested(&self, point: Point3) -> Color {
+    return self.pattern_at(self.transform.invert_point(point));
+  }
+
+  pub fn set_transform(&mut self, transform: Matrix4) {
+    self.transform = CachedTransform::new(transform);
+  }
+
+  // The pattern as seen by light of a single wavelength. The projection
+  // is linear, so projecting the finished color is the same as projecting
+  // every color that went into it.
+  pub fn at_wavelength(&self, wavelength: f64) -> Pattern {
+    let mut pattern = self.clone();
+    pattern.wavelength = Some(wavelength);
+    return pattern;
+  }
+
+  pub fn pattern_at_object(&self, object: &Shape, point: Point3) -> Color {
+    let object_point = *object.inverse() * point;
+    let pattern_point = self.transform.invert_point(object_point);
 
     return self.pattern_at(pattern_point);
   }
+
+  // Like pattern_at_object, averaging over the patch of surface spanned by
+  // dpdx and dpdy around the point, where the pattern supports it.
+  pub fn pattern_at_object_filtered(
+    &self,
+    object: &Shape,
+    point: Point3,
+    dpdx: Vector3,
+    dpdy: Vector3,
+  ) -> Color {
+    let to_pattern = Matrix4::mult(&self.transform.inverse, object.inverse());
+    let p = to_pattern * point;
+    let dx = to_pattern * dpdx;
+    let dy = to_pattern * dpdy;
+
+    return project(self.procedural.pattern_at_filtered(p, dx, dy), self.wavelength);
+  }
 }
 
 #[test]
@@ -78,7 +198,7 @@ fn pattern_with_an_object_transformation() {
 
   object.set_transform(transform);
   object.material.set_pattern(pattern.clone());
-  let c = pattern.pattern_at_object(object, point(2., 3., 4.));
+  let c = pattern.pattern_at_object(&object, Point3::new(2., 3., 4.));
 
   assert_eq!(Color::equals(c, Color::new(1., 1.5, 2.)), true);
 }
@@ -95,7 +215,7 @@ fn pattern_with_a_pattern_transformation() {
 
   pattern.set_transform(transform);
   object.material.set_pattern(pattern.clone());
-  let c = pattern.pattern_at_object(object, point(2., 3., 4.));
+  let c = pattern.pattern_at_object(&object, Point3::new(2., 3., 4.));
 
   assert_eq!(Color::equals(c, Color::new(1., 1.5, 2.)), true);
 }
@@ -114,7 +234,7 @@ fn pattern_with_both_object_and_pattern_transformation() {
 
   object.set_transform(transform2);
   object.material.set_pattern(pattern.clone());
-  let c = pattern.pattern_at_object(object, point(2.5, 3., 3.5));
+  let c = pattern.pattern_at_object(&object, Point3::new(2.5, 3., 3.5));
 
   assert_eq!(Color::equals(c, Color::new(0.75, 0.5, 0.25)), true);
 }
@@ -131,7 +251,7 @@ fn stripes_with_an_object_transformation() {
 
   object.set_transform(transform);
   object.material.set_pattern(pattern.clone());
-  let c = pattern.pattern_at_object(object, point(1.5, 0., 0.));
+  let c = pattern.pattern_at_object(&object, Point3::new(1.5, 0., 0.));
 
   assert_eq!(Color::equals(c, Color::new(1., 1., 1.)), true);
 }
@@ -148,7 +268,7 @@ fn stripes_with_a_pattern_transformation() {
   pattern.set_transform(transform);
 
   object.material.set_pattern(pattern.clone());
-  let c = pattern.pattern_at_object(object, point(2.5, 0., 0.));
+  let c = pattern.pattern_at_object(&object, Point3::new(2.5, 0., 0.));
 
   assert_eq!(Color::equals(c, Color::new(0., 0., 0.)), true);
 }
@@ -167,7 +287,118 @@ fn stripes_with_both_object_and_pattern_transformation() {
 
   object.set_transform(transform2);
   object.material.set_pattern(pattern.clone());
-  let c = pattern.pattern_at_object(object, point(2.5, 0., 0.));
+  let c = pattern.pattern_at_object(&object, Point3::new(2.5, 0., 0.));
 
   assert_eq!(Color::equals(c, Color::new(1., 1., 1.)), true);
 }
+
+#[test]
+fn an_image_mapped_onto_a_plane() {
+  let mut canvas = crate::canvas::Canvas::new(2, 1);
+  canvas.set(0, 0, Color::new(1., 0., 0.));
+  canvas.set(1, 0, Color::new(0., 0., 1.));
+  let texture = ImageTexture::with_options(
+    canvas,
+    self::image::TextureFilter::Nearest,
+    self::image::TextureWrap::Repeat,
+  );
+  let pattern = Pattern::image(texture, UvMapping::Planar);
+
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(0.25, 0., 0.5)), Color::new(1., 0., 0.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(1.75, 0., 0.5)), Color::new(0., 0., 1.)),
+    true
+  );
+}
+
+#[test]
+fn stripes_of_checkers_with_their_own_transforms() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let red = Color::new(1., 0., 0.);
+
+  let mut fine = Pattern::new(PatternType::Checker, white, black);
+  fine.set_transform(Transform::new().scale(0.25, 0.25, 0.25).transform);
+  let pattern = Pattern::nested(PatternType::Stripe, fine, Pattern::solid(red));
+
+  // the a stripe shows checkers a quarter of the size
+  assert_eq!(Color::equals(pattern.pattern_at(Point3::new(0.1, 0., 0.)), white), true);
+  assert_eq!(Color::equals(pattern.pattern_at(Point3::new(0.3, 0., 0.)), black), true);
+  assert_eq!(Color::equals(pattern.pattern_at(Point3::new(1.3, 0., 0.)), red), true);
+}
+
+#[test]
+fn nested_patterns_follow_a_single_wavelength() {
+  let red = Color::new(1., 0., 0.);
+  let green = Color::new(0., 1., 0.);
+  let pattern = Pattern::nested(
+    PatternType::Stripe,
+    Pattern::new(PatternType::Checker, red, green),
+    Pattern::solid(green),
+  )
+  .at_wavelength(650.);
+
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(0.1, 0., 0.)), Color::new(1., 1., 1.)),
+    true
+  );
+  assert_eq!(
+    Color::equals(pattern.pattern_at(Point3::new(1.1, 0., 0.)), Color::new(0., 0., 0.)),
+    true
+  );
+}
+
+#[test]
+fn a_pattern_from_a_closure_is_transformed_like_the_others() {
+  let mut object = Shape::new(ShapeType::Sphere);
+  object.set_transform(Transform::new().scale(2., 2., 2.).transform);
+  let mut pattern = Pattern::from_fn(|p: Point3| {
+    if p.y > 0. {
+      return Color::new(1., 1., 1.);
+    }
+    return Color::new(0., 0., 0.);
+  });
+  pattern.set_transform(Transform::new().translate(0., 1., 0.).transform);
+
+  let above = pattern.pattern_at_object(&object, Point3::new(0., 2.5, 0.));
+  let below = pattern.pattern_at_object(&object, Point3::new(0., 1.5, 0.));
+  assert_eq!(Color::equals(above, Color::new(1., 1., 1.)), true);
+  assert_eq!(Color::equals(below, Color::new(0., 0., 0.)), true);
+}
+
+#[cfg(test)]
+struct Halves {
+  left: Pattern,
+  right: Pattern,
+}
+
+#[cfg(test)]
+impl Procedural for Halves {
+  fn pattern_at(&self, point: Point3) -> Color {
+    if point.x < 0. {
+      return self.left.pattern_at_nested(point);
+    }
+    return self.right.pattern_at_nested(point);
+  }
+}
+
+#[test]
+fn a_procedural_struct_can_hold_other_patterns() {
+  let white = Color::new(1., 1., 1.);
+  let black = Color::new(0., 0., 0.);
+  let pattern = Pattern::custom(Arc::new(Halves {
+    left: Pattern::solid(white),
+    right: Pattern::new(PatternType::Stripe, black, white),
+  }));
+
+  assert_eq!(Color::equals(pattern.pattern_at(Point3::new(-0.5, 0., 0.)), white), true);
+  assert_eq!(Color::equals(pattern.pattern_at(Point3::new(0.5, 0., 0.)), black), true);
+  assert_eq!(Color::equals(pattern.pattern_at(Point3::new(1.5, 0., 0.)), white), true);
+
+  let mono = pattern.at_wavelength(550.);
+  let c = mono.pattern_at(Point3::new(-0.5, 0., 0.));
+  assert_eq!(Color::equals(c, project(white, Some(550.))), true);
+}
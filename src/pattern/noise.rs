@@ -0,0 +1,150 @@
+use crate::vectors::typed::{Point3, Vector3};
+
+// The twelve edge directions of a cube, used as lattice gradients.
+const GRADIENTS: [(f64, f64, f64); 12] = [
+  (1., 1., 0.),
+  (-1., 1., 0.),
+  (1., -1., 0.),
+  (-1., -1., 0.),
+  (1., 0., 1.),
+  (-1., 0., 1.),
+  (1., 0., -1.),
+  (-1., 0., -1.),
+  (0., 1., 1.),
+  (0., -1., 1.),
+  (0., 1., -1.),
+  (0., -1., -1.),
+];
+
+// Scrambles a lattice point into a gradient index. Any well mixed hash will
+// do; this one needs no permutation table.
+fn hash(x: i64, y: i64, z: i64) -> usize {
+  let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+    ^ (y as u32).wrapping_mul(0xd816_3841)
+    ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+  h ^= h >> 13;
+  h = h.wrapping_mul(0x5bd1_e995);
+  h ^= h >> 15;
+  return h as usize % GRADIENTS.len();
+}
+
+fn fade(t: f64) -> f64 {
+  return t * t * t * (t * (t * 6. - 15.) + 10.);
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+  return a + t * (b - a);
+}
+
+fn grad(x: i64, y: i64, z: i64, dx: f64, dy: f64, dz: f64) -> f64 {
+  let (gx, gy, gz) = GRADIENTS[hash(x, y, z)];
+  return gx * dx + gy * dy + gz * dz;
+}
+
+// Perlin's gradient noise: smooth, repeatable, roughly in [-1, 1] and zero
+// on every integer lattice point.
+pub fn noise(p: Point3) -> f64 {
+  let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+  let (x, y, z) = (fx as i64, fy as i64, fz as i64);
+  let (dx, dy, dz) = (p.x - fx, p.y - fy, p.z - fz);
+  let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+
+  let x00 = lerp(
+    u,
+    grad(x, y, z, dx, dy, dz),
+    grad(x + 1, y, z, dx - 1., dy, dz),
+  );
+  let x10 = lerp(
+    u,
+    grad(x, y + 1, z, dx, dy - 1., dz),
+    grad(x + 1, y + 1, z, dx - 1., dy - 1., dz),
+  );
+  let x01 = lerp(
+    u,
+    grad(x, y, z + 1, dx, dy, dz - 1.),
+    grad(x + 1, y, z + 1, dx - 1., dy, dz - 1.),
+  );
+  let x11 = lerp(
+    u,
+    grad(x, y + 1, z + 1, dx, dy - 1., dz - 1.),
+    grad(x + 1, y + 1, z + 1, dx - 1., dy - 1., dz - 1.),
+  );
+  return lerp(w, lerp(v, x00, x10), lerp(v, x01, x11));
+}
+
+// Three uncorrelated noise values, for displacing a point in every axis.
+pub fn noise_vector(p: Point3) -> Vector3 {
+  return Vector3::new(
+    noise(p),
+    noise(p + Vector3::new(31.4, 15.9, 26.5)),
+    noise(p + Vector3::new(-35.8, 97.9, -32.3)),
+  );
+}
+
+// Fractal Brownian motion: octaves of noise, each at twice the frequency
+// and half the amplitude of the one before.
+pub fn fbm(p: Point3, octaves: usize) -> f64 {
+  let mut total = 0.;
+  let mut frequency = 1.;
+  let mut amplitude = 1.;
+  for _i in 0..octaves {
+    total += amplitude * noise(Point3::new(p.x * frequency, p.y * frequency, p.z * frequency));
+    frequency *= 2.;
+    amplitude *= 0.5;
+  }
+  return total;
+}
+
+// Like fbm, but summing the magnitude of each octave, which gives the sharp
+// creases of veins and flames.
+pub fn turbulence(p: Point3, octaves: usize) -> f64 {
+  let mut total = 0.;
+  let mut frequency = 1.;
+  let mut amplitude = 1.;
+  for _i in 0..octaves {
+    total +=
+      amplitude * noise(Point3::new(p.x * frequency, p.y * frequency, p.z * frequency)).abs();
+    frequency *= 2.;
+    amplitude *= 0.5;
+  }
+  return total;
+}
+
+#[test]
+fn noise_vanishes_on_the_lattice() {
+  for p in vec![Point3::new(0., 0., 0.), Point3::new(1., -2., 3.), Point3::new(-7., 4., 12.)] {
+    assert_eq!(noise(p), 0.);
+  }
+}
+
+#[test]
+fn noise_is_repeatable_and_bounded() {
+  let p = Point3::new(0.3, 1.7, -2.2);
+  assert_eq!(noise(p), noise(p));
+
+  let mut varied = false;
+  for i in 0..1000 {
+    let t = i as f64 * 0.137;
+    let n = noise(Point3::new(t, t * 0.71, t * 1.37));
+    assert_eq!(n.abs() <= 1., true);
+    varied = varied || n.abs() > 0.1;
+  }
+  assert_eq!(varied, true);
+}
+
+#[test]
+fn noise_is_smooth() {
+  let p = Point3::new(2.35, -0.6, 4.15);
+  let q = p + Vector3::new(1e-4, 1e-4, 1e-4);
+  assert_eq!((noise(p) - noise(q)).abs() < 1e-3, true);
+}
+
+#[test]
+fn turbulence_is_never_negative() {
+  for i in 0..100 {
+    let t = i as f64 * 0.29;
+    assert_eq!(turbulence(Point3::new(t, -t, 0.5 * t), 4) >= 0., true);
+  }
+  let p = Point3::new(0.4, 0.5, 0.6);
+  assert_eq!((fbm(p, 1) - noise(p)).abs() < 1e-12, true);
+}
@@ -0,0 +1,63 @@
+use crate::colors::Color;
+use crate::pattern::noise::noise_vector;
+use crate::pattern::procedural::Procedural;
+use crate::pattern::{Pattern, PatternType};
+use crate::vectors::typed::Point3;
+
+#[derive(Clone)]
+pub struct Perturbed {
+  inner: Pattern,
+  scale: f64,
+}
+
+impl Perturbed {
+  pub fn new(inner: Pattern, scale: f64) -> Perturbed {
+    return Perturbed {
+      inner: inner,
+      scale: scale,
+    };
+  }
+}
+
+impl Procedural for Perturbed {
+  // The inner pattern, read at a point displaced by up to scale in each
+  // axis, so its straight edges come out wavy.
+  fn pattern_at(&self, point: Point3) -> Color {
+    let jittered = point + noise_vector(point) * self.scale;
+    return self.inner.pattern_at_nested(jittered);
+  }
+}
+
+#[test]
+fn a_perturbation_of_zero_leaves_the_pattern_alone() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let stripes = Pattern::new(PatternType::Stripe, white, black);
+  let pattern = Pattern::perturbed(stripes.clone(), 0.);
+
+  for i in 0..50 {
+    let p = Point3::new(i as f64 * 0.13, 0.4, -0.2);
+    assert_eq!(
+      Color::equals(pattern.pattern_at(p), stripes.pattern_at(p)),
+      true
+    );
+  }
+}
+
+#[test]
+fn a_perturbation_moves_the_stripe_edges() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let stripes = Pattern::new(PatternType::Stripe, white, black);
+  let pattern = Pattern::perturbed(stripes.clone(), 0.3);
+
+  let mut moved = 0;
+  for i in 0..200 {
+    let p = Point3::new(i as f64 * 0.05, 0.37, 0.81);
+    if !Color::equals(pattern.pattern_at(p), stripes.pattern_at(p)) {
+      moved += 1;
+    }
+  }
+  // only points near an edge change color
+  assert_eq!(moved > 0 && moved < 200, true);
+}
@@ -0,0 +1,30 @@
+use crate::colors::Color;
+use crate::vectors::typed::{Point3, Vector3};
+
+// A pattern, built in or defined outside the crate. It is handed points in
+// pattern space; Pattern looks after the object and pattern transforms and
+// the projection to a single wavelength, so every implementation moves,
+// scales and nests the same way.
+// Patterns it wants to combine can be held as fields and read with
+// Pattern::pattern_at_nested.
+//
+// Closures from a point to a color are procedural patterns too, so most
+// callers can simply use Pattern::from_fn.
+pub trait Procedural: Send + Sync {
+  fn pattern_at(&self, point: Point3) -> Color;
+
+  // The pattern averaged over the footprint spanned by dpdx and dpdy, for
+  // patterns that can filter; the rest are point sampled.
+  fn pattern_at_filtered(&self, point: Point3, _dpdx: Vector3, _dpdy: Vector3) -> Color {
+    return self.pattern_at(point);
+  }
+}
+
+impl<F> Procedural for F
+where
+  F: Fn(Point3) -> Color + Send + Sync,
+{
+  fn pattern_at(&self, point: Point3) -> Color {
+    return self(point);
+  }
+}
@@ -1,29 +1,26 @@
 use crate::colors::Color;
-use crate::matrix::Matrix;
+use crate::pattern::procedural::Procedural;
 use crate::pattern::{Pattern, PatternType};
-use crate::shape::{Shape, ShapeType};
-use crate::vectors::{point, vector, Tuple};
-use crate::Transform;
+use crate::vectors::typed::Point3;
 
 #[derive(Clone)]
 pub struct Ring {
-  a: Color,
-  b: Color,
+  a: Pattern,
+  b: Pattern,
 }
 
 impl Ring {
-  pub fn new(color1: Color, color2: Color) -> Ring {
-    return Ring {
-      a: color1,
-      b: color2,
-    };
+  pub fn new(a: Pattern, b: Pattern) -> Ring {
+    return Ring { a: a, b: b };
   }
+}
 
-  pub fn pattern_at(pattern: &Pattern, point: Tuple) -> Color {
+impl Procedural for Ring {
+  fn pattern_at(&self, point: Point3) -> Color {
     if ((point.x * point.x + point.z * point.z).sqrt()).floor() % 2.0 == 0.0 {
-      return pattern.a.clone();
+      return self.a.pattern_at_nested(point);
     } else {
-      return pattern.b.clone();
+      return self.b.pattern_at_nested(point);
     }
   }
 }
@@ -36,22 +33,22 @@ fn creating_a_ring() {
   let pattern = Pattern::new(PatternType::Ring, white, black);
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 0.,)), white.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 0.,)), white.clone()),
     true
   );
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0., 0., 1.,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0., 0., 1.,)), black.clone()),
     true
   );
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(1., 0., 0.,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(1., 0., 0.,)), black.clone()),
     true
   );
 
   assert_eq!(
-    Color::equals(pattern.pattern_at(point(0.708, 0., 0.708,)), black.clone()),
+    Color::equals(pattern.pattern_at(Point3::new(0.708, 0., 0.708,)), black.clone()),
     true
   );
 }
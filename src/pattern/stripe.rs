@@ -1,35 +1,32 @@
 use crate::colors::Color;
-use crate::matrix::Matrix;
-use crate::pattern::{Pattern, PatternType};
-use crate::shape::{Shape, ShapeType};
-use crate::vectors::{point, vector, Tuple};
-use crate::Transform;
+use crate::pattern::procedural::Procedural;
+use crate::pattern::Pattern;
+use crate::vectors::typed::Point3;
 
 #[derive(Clone)]
 pub struct Stripe {
-  a: Color,
-  b: Color,
+  a: Pattern,
+  b: Pattern,
 }
 
 impl Stripe {
-  pub fn new(color1: Color, color2: Color) -> Stripe {
-    return Stripe {
-      a: color1,
-      b: color2,
-    };
+  pub fn new(a: Pattern, b: Pattern) -> Stripe {
+    return Stripe { a: a, b: b };
   }
+}
 
-  pub fn pattern_at(pattern: &Pattern, point: Tuple) -> Color {
+impl Procedural for Stripe {
+  fn pattern_at(&self, point: Point3) -> Color {
     if point.x >= 0.0 {
       if point.x % 2. >= 1.0 {
-        return pattern.b.clone();
+        return self.b.pattern_at_nested(point);
       }
-      return pattern.a.clone();
+      return self.a.pattern_at_nested(point);
     } else {
       if point.x.abs() % 2. > 1.0 {
-        return pattern.a.clone();
+        return self.a.pattern_at_nested(point);
       }
-      return pattern.b.clone();
+      return self.b.pattern_at_nested(point);
     }
   }
 }
@@ -39,9 +36,9 @@ fn creating_a_stripe_pattern() {
   let black = Color::new(0., 0., 0.);
   let white = Color::new(1., 1., 1.);
 
-  let pattern = Pattern::new(PatternType::Stripe, white, black);
-  assert_eq!(Color::equals(pattern.a, white.clone()), true);
-  assert_eq!(Color::equals(pattern.b, black.clone()), true);
+  let pattern = Stripe::new(Pattern::solid(white), Pattern::solid(black));
+  assert_eq!(Color::equals(pattern.a.pattern_at(Point3::new(0., 0., 0.)), white.clone()), true);
+  assert_eq!(Color::equals(pattern.b.pattern_at(Point3::new(0., 0., 0.)), black.clone()), true);
 }
 
 #[test]
@@ -49,25 +46,25 @@ fn a_stripe_pattern_is_constant_in_y() {
   let black = Color::new(0., 0., 0.);
   let white = Color::new(1., 1., 1.);
 
-  let pattern = Pattern::new(PatternType::Stripe, white, black);
+  let pattern = Stripe::new(Pattern::solid(white), Pattern::solid(black));
 
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 0., 0.)),
       white.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 1., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 1., 0.)),
       white.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 2., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 2., 0.)),
       white.clone()
     ),
     true
@@ -78,25 +75,25 @@ fn a_stripe_pattern_is_constant_in_z() {
   let black = Color::new(0., 0., 0.);
   let white = Color::new(1., 1., 1.);
 
-  let pattern = Pattern::new(PatternType::Stripe, white, black);
+  let pattern = Stripe::new(Pattern::solid(white), Pattern::solid(black));
 
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 0., 0.)),
       white.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 0., 1.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 0., 1.)),
       white.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 0., 2.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 0., 2.)),
       white.clone()
     ),
     true
@@ -108,20 +105,20 @@ fn a_stripe_pattern_alternates_in_x() {
   let black = Color::new(0., 0., 0.);
   let white = Color::new(1., 1., 1.);
 
-  let pattern = Pattern::new(PatternType::Stripe, white, black);
+  let pattern = Stripe::new(Pattern::solid(white), Pattern::solid(black));
 
-  let x = Stripe::pattern_at(&pattern, point(-0.1, 0., 0.));
+  let x = Stripe::pattern_at(&pattern, Point3::new(-0.1, 0., 0.));
 
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0., 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(0., 0., 0.)),
       white.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(0.9, 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(0.9, 0., 0.)),
       white.clone()
     ),
     true
@@ -129,28 +126,28 @@ fn a_stripe_pattern_alternates_in_x() {
 
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(1., 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(1., 0., 0.)),
       black.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(-0.1, 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(-0.1, 0., 0.)),
       black.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(-1.0, 0., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(-1.0, 0., 0.)),
       black.clone()
     ),
     true
   );
   assert_eq!(
     Color::equals(
-      Stripe::pattern_at(&pattern, point(-1.1, 1., 0.)),
+      Stripe::pattern_at(&pattern, Point3::new(-1.1, 1., 0.)),
       white.clone()
     ),
     true
@@ -0,0 +1,452 @@
+use crate::colors::Color;
+use crate::pattern::image::ImageTexture;
+use crate::pattern::Pattern;
+use crate::pattern::procedural::Procedural;
+use crate::vectors::typed::{Point3, Vector3};
+use std::f64;
+
+// How a point in pattern space is flattened to (u, v) in [0, 1).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UvMapping {
+  Spherical,
+  Planar,
+  Cylindrical,
+  Cubic,
+}
+
+// Two dimensional patterns, looked up by (u, v).
+#[derive(Clone, Debug)]
+pub enum UvPattern {
+  Checkers {
+    width: f64,
+    height: f64,
+    a: Color,
+    b: Color,
+  },
+  // one color with a different one in each corner, for checking that the
+  // faces of a cube line up
+  AlignCheck {
+    main: Color,
+    ul: Color,
+    ur: Color,
+    bl: Color,
+    br: Color,
+  },
+  Image(ImageTexture),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CubeFace {
+  Left,
+  Right,
+  Front,
+  Back,
+  Up,
+  Down,
+}
+
+// A uv pattern wrapped around an object. Cubic mappings take one pattern per
+// face, in the order left, front, right, back, up, down; the other mappings
+// use a single pattern.
+#[derive(Clone, Debug)]
+pub struct TextureMap {
+  mapping: UvMapping,
+  faces: Vec<UvPattern>,
+}
+
+impl UvPattern {
+  pub fn checkers(width: f64, height: f64, a: Color, b: Color) -> UvPattern {
+    return UvPattern::Checkers {
+      width: width,
+      height: height,
+      a: a,
+      b: b,
+    };
+  }
+
+  pub fn align_check(main: Color, ul: Color, ur: Color, bl: Color, br: Color) -> UvPattern {
+    return UvPattern::AlignCheck {
+      main: main,
+      ul: ul,
+      ur: ur,
+      bl: bl,
+      br: br,
+    };
+  }
+
+  pub fn uv_pattern_at(&self, u: f64, v: f64) -> Color {
+    return match self {
+      UvPattern::Checkers {
+        width,
+        height,
+        a,
+        b,
+      } => {
+        let u2 = (u * width).floor();
+        let v2 = (v * height).floor();
+        if (u2 + v2).rem_euclid(2.) == 0. {
+          *a
+        } else {
+          *b
+        }
+      }
+      UvPattern::AlignCheck {
+        main,
+        ul,
+        ur,
+        bl,
+        br,
+      } => {
+        if v > 0.8 && u < 0.2 {
+          *ul
+        } else if v > 0.8 && u > 0.8 {
+          *ur
+        } else if v < 0.2 && u < 0.2 {
+          *bl
+        } else if v < 0.2 && u > 0.8 {
+          *br
+        } else {
+          *main
+        }
+      }
+      UvPattern::Image(texture) => texture.uv_color_at(u, v),
+    };
+  }
+
+  // The pattern averaged over a du by dv patch; only images are filtered,
+  // the others are point sampled.
+  pub fn uv_pattern_at_filtered(&self, u: f64, v: f64, du: f64, dv: f64) -> Color {
+    return match self {
+      UvPattern::Image(texture) => texture.uv_color_filtered(u, v, du, dv),
+      _ => self.uv_pattern_at(u, v),
+    };
+  }
+}
+
+// Longitude becomes u and latitude v, with the seam at -z.
+pub fn spherical_map(p: Point3) -> (f64, f64) {
+  let theta = p.x.atan2(p.z);
+  let radius = (p.x * p.x + p.y * p.y + p.z * p.z).sqrt();
+  let phi = (p.y / radius).max(-1.).min(1.).acos();
+  let raw_u = theta / (2. * f64::consts::PI);
+  let u = 1. - (raw_u + 0.5);
+  let v = 1. - phi / f64::consts::PI;
+  return (u, v);
+}
+
+// The xz plane, repeating every unit.
+pub fn planar_map(p: Point3) -> (f64, f64) {
+  return (p.x.rem_euclid(1.), p.z.rem_euclid(1.));
+}
+
+// Around the y axis for u, repeating every unit along it for v.
+pub fn cylindrical_map(p: Point3) -> (f64, f64) {
+  let theta = p.x.atan2(p.z);
+  let raw_u = theta / (2. * f64::consts::PI);
+  let u = 1. - (raw_u + 0.5);
+  let v = p.y.rem_euclid(1.);
+  return (u, v);
+}
+
+// The face of the unit cube a point lies on, picked by its largest
+// coordinate.
+pub fn face_from_point(p: Point3) -> CubeFace {
+  let coord = p.x.abs().max(p.y.abs()).max(p.z.abs());
+  if coord == p.x {
+    return CubeFace::Right;
+  }
+  if coord == -p.x {
+    return CubeFace::Left;
+  }
+  if coord == p.y {
+    return CubeFace::Up;
+  }
+  if coord == -p.y {
+    return CubeFace::Down;
+  }
+  if coord == p.z {
+    return CubeFace::Front;
+  }
+  return CubeFace::Back;
+}
+
+// (u, v) on a face of the unit cube, each face seen from outside with up
+// pointing along +y (or -z/+z for the top and bottom).
+pub fn cube_map(p: Point3, face: CubeFace) -> (f64, f64) {
+  let wrap = |x: f64| x.rem_euclid(2.) / 2.;
+  return match face {
+    CubeFace::Front => (wrap(p.x + 1.), wrap(p.y + 1.)),
+    CubeFace::Back => (wrap(1. - p.x), wrap(p.y + 1.)),
+    CubeFace::Left => (wrap(p.z + 1.), wrap(p.y + 1.)),
+    CubeFace::Right => (wrap(1. - p.z), wrap(p.y + 1.)),
+    CubeFace::Up => (wrap(p.x + 1.), wrap(1. - p.z)),
+    CubeFace::Down => (wrap(p.x + 1.), wrap(p.z + 1.)),
+  };
+}
+
+impl TextureMap {
+  pub fn new(uv_pattern: UvPattern, mapping: UvMapping) -> TextureMap {
+    return TextureMap {
+      mapping: mapping,
+      faces: vec![uv_pattern],
+    };
+  }
+
+  pub fn cube(
+    left: UvPattern,
+    front: UvPattern,
+    right: UvPattern,
+    back: UvPattern,
+    up: UvPattern,
+    down: UvPattern,
+  ) -> TextureMap {
+    return TextureMap {
+      mapping: UvMapping::Cubic,
+      faces: vec![left, front, right, back, up, down],
+    };
+  }
+
+  pub fn uv_at(&self, p: Point3) -> (f64, f64) {
+    return match self.mapping {
+      UvMapping::Spherical => spherical_map(p),
+      UvMapping::Planar => planar_map(p),
+      UvMapping::Cylindrical => cylindrical_map(p),
+      UvMapping::Cubic => cube_map(p, face_from_point(p)),
+    };
+  }
+
+  pub fn pattern_at(&self, p: Point3) -> Color {
+    let (u, v) = self.uv_at(p);
+    return self.face_pattern(p).uv_pattern_at(u, v);
+  }
+
+  // The pattern filtered over the footprint spanned by dpdx and dpdy, which
+  // are measured in pattern space.
+  pub fn pattern_at_filtered(&self, p: Point3, dpdx: Vector3, dpdy: Vector3) -> Color {
+    let (u, v) = self.uv_at(p);
+    let (ux, vx) = self.uv_at(p + dpdx);
+    let (uy, vy) = self.uv_at(p + dpdy);
+    // differences across a seam are measured the short way round
+    let wrap = |d: f64| d - d.round();
+    let du = wrap(ux - u).abs().max(wrap(uy - u).abs());
+    let dv = wrap(vx - v).abs().max(wrap(vy - v).abs());
+    return self.face_pattern(p).uv_pattern_at_filtered(u, v, du, dv);
+  }
+
+  fn face_pattern(&self, p: Point3) -> &UvPattern {
+    let face = match self.mapping {
+      UvMapping::Cubic => match face_from_point(p) {
+        CubeFace::Left => 0,
+        CubeFace::Front => 1,
+        CubeFace::Right => 2,
+        CubeFace::Back => 3,
+        CubeFace::Up => 4,
+        CubeFace::Down => 5,
+      },
+      _ => 0,
+    };
+    return &self.faces[face.min(self.faces.len() - 1)];
+  }
+}
+
+impl Procedural for TextureMap {
+  fn pattern_at(&self, p: Point3) -> Color {
+    return TextureMap::pattern_at(self, p);
+  }
+
+  fn pattern_at_filtered(&self, p: Point3, dpdx: Vector3, dpdy: Vector3) -> Color {
+    return TextureMap::pattern_at_filtered(self, p, dpdx, dpdy);
+  }
+}
+
+#[test]
+fn checker_pattern_in_2d() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let checkers = UvPattern::checkers(2., 2., black, white);
+
+  for (u, v, expected) in vec![
+    (0.0, 0.0, black),
+    (0.5, 0.0, white),
+    (0.0, 0.5, white),
+    (0.5, 0.5, black),
+    (1.0, 1.0, black),
+  ] {
+    assert_eq!(Color::equals(checkers.uv_pattern_at(u, v), expected), true);
+  }
+}
+
+#[test]
+fn using_a_spherical_mapping_on_a_3d_point() {
+  for (p, u, v) in vec![
+    (Point3::new(0., 0., -1.), 0.0, 0.5),
+    (Point3::new(1., 0., 0.), 0.25, 0.5),
+    (Point3::new(0., 0., 1.), 0.5, 0.5),
+    (Point3::new(-1., 0., 0.), 0.75, 0.5),
+    (Point3::new(0., 1., 0.), 0.5, 1.0),
+    (Point3::new(0., -1., 0.), 0.5, 0.0),
+    (Point3::new(2.0f64.sqrt() / 2., 2.0f64.sqrt() / 2., 0.), 0.25, 0.75),
+  ] {
+    let (mu, mv) = spherical_map(p);
+    assert_eq!((mu - u).abs() < 1e-10, true);
+    assert_eq!((mv - v).abs() < 1e-10, true);
+  }
+}
+
+#[test]
+fn using_a_texture_map_pattern_with_a_spherical_map() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let checkers = UvPattern::checkers(16., 8., black, white);
+  let pattern = Pattern::texture_map(TextureMap::new(checkers, UvMapping::Spherical));
+
+  for (p, expected) in vec![
+    (Point3::new(0.4315, 0.4670, 0.7719), white),
+    (Point3::new(-0.9654, 0.2552, -0.0534), black),
+    (Point3::new(0.1039, 0.7090, 0.6975), white),
+    (Point3::new(-0.4986, -0.7856, -0.3663), black),
+    (Point3::new(-0.0317, -0.9395, 0.3411), black),
+    (Point3::new(0.4809, -0.7721, 0.4154), black),
+    (Point3::new(0.0285, -0.9612, -0.2745), black),
+    (Point3::new(-0.5734, -0.2162, -0.7903), white),
+    (Point3::new(0.7688, -0.1470, 0.6223), black),
+    (Point3::new(-0.7652, 0.2175, 0.6060), black),
+  ] {
+    assert_eq!(Color::equals(pattern.pattern_at(p), expected), true);
+  }
+}
+
+#[test]
+fn using_a_planar_mapping_on_a_3d_point() {
+  for (p, u, v) in vec![
+    (Point3::new(0.25, 0., 0.5), 0.25, 0.5),
+    (Point3::new(0.25, 0., -0.25), 0.25, 0.75),
+    (Point3::new(0.25, 0.5, -0.25), 0.25, 0.75),
+    (Point3::new(1.25, 0., 0.5), 0.25, 0.5),
+    (Point3::new(0.25, 0., -1.75), 0.25, 0.25),
+    (Point3::new(1., 0., -1.), 0.0, 0.0),
+    (Point3::new(0., 0., 0.), 0.0, 0.0),
+  ] {
+    let (mu, mv) = planar_map(p);
+    assert_eq!((mu - u).abs() < 1e-10, true);
+    assert_eq!((mv - v).abs() < 1e-10, true);
+  }
+}
+
+#[test]
+fn using_a_cylindrical_mapping_on_a_3d_point() {
+  let h = f64::consts::FRAC_1_SQRT_2;
+  for (p, u, v) in vec![
+    (Point3::new(0., 0., -1.), 0.0, 0.0),
+    (Point3::new(0., 0.5, -1.), 0.0, 0.5),
+    (Point3::new(0., 1., -1.), 0.0, 0.0),
+    (Point3::new(h, 0.5, -h), 0.125, 0.5),
+    (Point3::new(1., 0.5, 0.), 0.25, 0.5),
+    (Point3::new(h, 0.5, h), 0.375, 0.5),
+    (Point3::new(0., -0.25, 1.), 0.5, 0.75),
+    (Point3::new(-h, 0.5, h), 0.625, 0.5),
+    (Point3::new(-1., 1.25, 0.), 0.75, 0.25),
+    (Point3::new(-h, 0.5, -h), 0.875, 0.5),
+  ] {
+    let (mu, mv) = cylindrical_map(p);
+    assert_eq!((mu - u).abs() < 1e-5, true);
+    assert_eq!((mv - v).abs() < 1e-5, true);
+  }
+}
+
+#[test]
+fn layout_of_the_align_check_pattern() {
+  let main = Color::new(1., 1., 1.);
+  let ul = Color::new(1., 0., 0.);
+  let ur = Color::new(1., 1., 0.);
+  let bl = Color::new(0., 1., 0.);
+  let br = Color::new(0., 1., 1.);
+  let pattern = UvPattern::align_check(main, ul, ur, bl, br);
+
+  for (u, v, expected) in vec![
+    (0.5, 0.5, main),
+    (0.1, 0.9, ul),
+    (0.9, 0.9, ur),
+    (0.1, 0.1, bl),
+    (0.9, 0.1, br),
+  ] {
+    assert_eq!(Color::equals(pattern.uv_pattern_at(u, v), expected), true);
+  }
+}
+
+#[test]
+fn identifying_the_face_of_a_cube_from_a_point() {
+  for (p, face) in vec![
+    (Point3::new(-1., 0.5, -0.25), CubeFace::Left),
+    (Point3::new(1.1, -0.75, 0.8), CubeFace::Right),
+    (Point3::new(0.1, 0.6, 0.9), CubeFace::Front),
+    (Point3::new(-0.7, 0., -2.), CubeFace::Back),
+    (Point3::new(0.5, 1., 0.9), CubeFace::Up),
+    (Point3::new(-0.2, -1.3, 1.1), CubeFace::Down),
+  ] {
+    assert_eq!(face_from_point(p), face);
+  }
+}
+
+#[test]
+fn uv_mapping_the_faces_of_a_cube() {
+  for (p, face, u, v) in vec![
+    (Point3::new(-0.5, 0.5, 1.), CubeFace::Front, 0.25, 0.75),
+    (Point3::new(0.5, -0.5, 1.), CubeFace::Front, 0.75, 0.25),
+    (Point3::new(0.5, 0.5, -1.), CubeFace::Back, 0.25, 0.75),
+    (Point3::new(-0.5, -0.5, -1.), CubeFace::Back, 0.75, 0.25),
+    (Point3::new(-1., 0.5, -0.5), CubeFace::Left, 0.25, 0.75),
+    (Point3::new(-1., -0.5, 0.5), CubeFace::Left, 0.75, 0.25),
+    (Point3::new(1., 0.5, 0.5), CubeFace::Right, 0.25, 0.75),
+    (Point3::new(1., -0.5, -0.5), CubeFace::Right, 0.75, 0.25),
+    (Point3::new(-0.5, 1., -0.5), CubeFace::Up, 0.25, 0.75),
+    (Point3::new(0.5, 1., 0.5), CubeFace::Up, 0.75, 0.25),
+    (Point3::new(-0.5, -1., 0.5), CubeFace::Down, 0.25, 0.75),
+    (Point3::new(0.5, -1., -0.5), CubeFace::Down, 0.75, 0.25),
+  ] {
+    let (mu, mv) = cube_map(p, face);
+    assert_eq!((mu - u).abs() < 1e-10, true);
+    assert_eq!((mv - v).abs() < 1e-10, true);
+  }
+}
+
+#[test]
+fn finding_the_colors_on_a_mapped_cube() {
+  let red = Color::new(1., 0., 0.);
+  let yellow = Color::new(1., 1., 0.);
+  let brown = Color::new(1., 0.5, 0.);
+  let green = Color::new(0., 1., 0.);
+  let cyan = Color::new(0., 1., 1.);
+  let blue = Color::new(0., 0., 1.);
+  let purple = Color::new(1., 0., 1.);
+  let white = Color::new(1., 1., 1.);
+
+  let left = UvPattern::align_check(yellow, cyan, red, blue, brown);
+  let front = UvPattern::align_check(cyan, red, yellow, brown, green);
+  let right = UvPattern::align_check(red, yellow, purple, green, white);
+  let back = UvPattern::align_check(green, purple, cyan, white, blue);
+  let up = UvPattern::align_check(brown, cyan, purple, red, yellow);
+  let down = UvPattern::align_check(purple, brown, green, blue, white);
+  let pattern = Pattern::texture_map(TextureMap::cube(left, front, right, back, up, down));
+
+  for (p, expected) in vec![
+    (Point3::new(-1., 0., 0.), yellow),
+    (Point3::new(-1., 0.9, -0.9), cyan),
+    (Point3::new(-1., 0.9, 0.9), red),
+    (Point3::new(-1., -0.9, -0.9), blue),
+    (Point3::new(-1., -0.9, 0.9), brown),
+    (Point3::new(0., 0., 1.), cyan),
+    (Point3::new(-0.9, 0.9, 1.), red),
+    (Point3::new(0.9, -0.9, 1.), green),
+    (Point3::new(1., 0., 0.), red),
+    (Point3::new(1., 0.9, -0.9), purple),
+    (Point3::new(0., 0., -1.), green),
+    (Point3::new(0.9, 0.9, -1.), purple),
+    (Point3::new(0., 1., 0.), brown),
+    (Point3::new(-0.9, 1., -0.9), cyan),
+    (Point3::new(0., -1., 0.), purple),
+    (Point3::new(0.9, -1., -0.9), white),
+  ] {
+    assert_eq!(Color::equals(pattern.pattern_at(p), expected), true);
+  }
+}
@@ -0,0 +1,62 @@
+use crate::colors::Color;
+use crate::pattern::noise::fbm;
+use crate::pattern::procedural::Procedural;
+use crate::pattern::{Pattern, PatternType};
+use crate::vectors::typed::Point3;
+
+// How much the rings are distorted, and how fine the distortion is.
+const GRAIN: f64 = 0.15;
+const GRAIN_SCALE: f64 = 2.;
+const OCTAVES: usize = 3;
+
+#[derive(Clone)]
+pub struct Wood {
+  a: Pattern,
+  b: Pattern,
+}
+
+impl Wood {
+  pub fn new(a: Pattern, b: Pattern) -> Wood {
+    return Wood { a: a, b: b };
+  }
+}
+
+impl Procedural for Wood {
+  // Rings about the y axis, like the ring pattern, with a wobbly radius
+  // and a gradual fade from a to b across each ring.
+  fn pattern_at(&self, point: Point3) -> Color {
+    let grain_point = Point3::new(
+      point.x * GRAIN_SCALE,
+      point.y * GRAIN_SCALE,
+      point.z * GRAIN_SCALE,
+    );
+    let radius = (point.x * point.x + point.z * point.z).sqrt() + GRAIN * fbm(grain_point, OCTAVES);
+    let fraction = radius - radius.floor();
+
+    return Color::add(
+      Color::mult(self.a.pattern_at_nested(point), 1. - fraction),
+      Color::mult(self.b.pattern_at_nested(point), fraction),
+    );
+  }
+}
+
+#[test]
+fn wood_fades_across_wobbly_rings() {
+  let black = Color::new(0., 0., 0.);
+  let white = Color::new(1., 1., 1.);
+  let pattern = Pattern::new(PatternType::Wood, white, black);
+
+  let mut seen_light = false;
+  let mut seen_dark = false;
+  let mut distorted = false;
+  for i in 0..300 {
+    let x = i as f64 * 0.01;
+    let c = pattern.pattern_at(Point3::new(x, 0.3, 0.));
+    assert_eq!(c.r >= 0. && c.r <= 1., true);
+    seen_light = seen_light || c.r > 0.9;
+    seen_dark = seen_dark || c.r < 0.1;
+    // the perfect rings would read 1 - fract(x)
+    distorted = distorted || (c.r - (1. - (x - x.floor()))).abs() > 0.01;
+  }
+  assert_eq!(seen_light && seen_dark && distorted, true);
+}
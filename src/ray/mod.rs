@@ -1,79 +1,187 @@
-use crate::matrix::Matrix;
+use crate::matrix::matrix4::Matrix4;
 use crate::transform::Transform;
-use crate::vectors::Tuple;
+use crate::vectors::typed::{Point3, Vector3};
 use crate::vectors::{point, vector};
 
+// Two rays offset by one pixel in x and in y from a camera ray, carried
+// along through mirrors and refraction, so the size of a pixel's footprint
+// on a surface can be worked out at every hit.
+#[derive(Copy, Clone)]
+pub struct RayDifferential {
+  pub rx_origin: Point3,
+  pub rx_direction: Vector3,
+  pub ry_origin: Point3,
+  pub ry_direction: Vector3,
+}
+
+impl RayDifferential {
+  pub fn transform(&self, transform: &Matrix4) -> RayDifferential {
+    let m = *transform;
+    return RayDifferential {
+      rx_origin: m * self.rx_origin,
+      rx_direction: m * self.rx_direction,
+      ry_origin: m * self.ry_origin,
+      ry_direction: m * self.ry_direction,
+    };
+  }
+}
+
 #[derive(Copy, Clone)]
 pub struct Ray {
-  pub origin: Tuple,
-  pub direction: Tuple,
+  pub origin: Point3,
+  pub direction: Vector3,
+  // in nanometers, for rays that have been split up by a dispersive
+  // material; None for ordinary white rays
+  pub wavelength: Option<f64>,
+  pub differential: Option<RayDifferential>,
+  // only hits with t_min <= t < t_max count. Rays are unbounded unless
+  // narrowed with with_range, so that refraction still sees the surfaces
+  // behind a ray's origin.
+  pub t_min: f64,
+  pub t_max: f64,
+  // set on rays that have left a glossy or frosted surface; rough surfaces
+  // further along trace a single jittered ray instead of splitting again
+  pub scattered: bool,
 }
 
 impl Ray {
-  pub fn new(origin: Tuple, direction: Tuple) -> Ray {
+  pub fn new(origin: Point3, direction: Vector3) -> Ray {
+    Ray {
+      origin: origin,
+      direction: direction,
+      wavelength: None,
+      differential: None,
+      t_min: f64::NEG_INFINITY,
+      t_max: f64::INFINITY,
+      scattered: false,
+    }
+  }
+
+  pub fn with_wavelength(origin: Point3, direction: Vector3, wavelength: Option<f64>) -> Ray {
     Ray {
       origin: origin,
       direction: direction,
+      wavelength: wavelength,
+      differential: None,
+      t_min: f64::NEG_INFINITY,
+      t_max: f64::INFINITY,
+      scattered: false,
     }
   }
 
-  pub fn position(&self, time: f64) -> Tuple {
-    let delta = self.direction.mult(time);
-    let p = self.origin.add(delta);
-    return p;
+  // The same ray, only hitting things between t_min and t_max.
+  pub fn with_range(&self, t_min: f64, t_max: f64) -> Ray {
+    let mut r = *self;
+    r.t_min = t_min;
+    r.t_max = t_max;
+    return r;
+  }
+
+  pub fn in_range(&self, t: f64) -> bool {
+    return t >= self.t_min && t < self.t_max;
+  }
+
+  pub fn position(&self, time: f64) -> Point3 {
+    return self.origin + self.direction * time;
   }
 
-  pub fn transform(&self, transform: &Matrix) -> Ray {
-    let ot = Matrix::mult_4x4_by_1d(&transform, &self.origin);
-    let dt = Matrix::mult_4x4_by_1d(&transform, &self.direction);
+  pub fn transform(&self, transform: &Matrix4) -> Ray {
+    let ot = *transform * self.origin;
+    let dt = *transform * self.direction;
 
-    return Ray::new(ot, dt);
+    let mut r = Ray::with_wavelength(ot, dt, self.wavelength);
+    r.differential = self.differential.map(|d| d.transform(transform));
+    // the direction is not renormalized, so t means the same after
+    r.t_min = self.t_min;
+    r.t_max = self.t_max;
+    r.scattered = self.scattered;
+    return r;
   }
 }
 
 #[test]
 fn it_computes_position_over_t() {
-  let origin = point(2., 3., 4.);
-  let direction = vector(1., 0., 0.);
+  let origin = Point3::new(2., 3., 4.);
+  let direction = Vector3::new(1., 0., 0.);
   let r = Ray::new(origin, direction);
 
-  let p1 = r.position(0.);
+  let p1 = r.position(0.).to_tuple();
   let e1 = point(2., 3., 4.);
   assert_eq!(p1.equals(e1), true);
 
-  let p2 = r.position(1.);
+  let p2 = r.position(1.).to_tuple();
   let e2 = point(3., 3., 4.);
   assert_eq!(p2.equals(e2), true);
 
-  let p3 = r.position(-1.);
+  let p3 = r.position(-1.).to_tuple();
   let e3 = point(1., 3., 4.);
   assert_eq!(p3.equals(e3), true);
 
-  let p4 = r.position(2.5);
+  let p4 = r.position(2.5).to_tuple();
   let e4 = point(4.5, 3., 4.);
   assert_eq!(p4.equals(e4), true);
 }
 
 #[test]
 fn it_translates_a_ray() {
-  let r = Ray::new(point(1., 2., 3.), vector(0., 1., 0.));
+  let r = Ray::new(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.));
   let m = Transform::new().translate(3., 4., 5.).transform;
   let r2 = r.transform(&m);
 
   println!(
-    "******** {} {} {} {}",
-    r2.direction.x, r2.direction.y, r2.direction.z, r2.direction.w
+    "******** {} {} {}",
+    r2.direction.x, r2.direction.y, r2.direction.z
   );
-  assert_eq!(r2.origin.equals(point(4., 6., 8.)), true);
-  assert_eq!(r2.direction.equals(vector(0., 1., 0.)), true);
+  assert_eq!(r2.origin.to_tuple().equals(point(4., 6., 8.)), true);
+  assert_eq!(r2.direction.to_tuple().equals(vector(0., 1., 0.)), true);
 }
 
 #[test]
 fn it_scales_a_ray() {
-  let r = Ray::new(point(1., 2., 3.), vector(0., 1., 0.));
+  let r = Ray::new(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.));
+  let m = Transform::new().scale(2., 3., 4.).transform;
+  let r2 = r.transform(&m);
+
+  assert_eq!(r2.origin.to_tuple().equals(point(2., 6., 12.)), true);
+  assert_eq!(r2.direction.to_tuple().equals(vector(0., 3., 0.)), true);
+}
+
+#[test]
+fn transforming_a_ray_keeps_its_wavelength() {
+  let r = Ray::with_wavelength(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.), Some(450.));
+  let m = Transform::new().scale(2., 3., 4.).transform;
+  let r2 = r.transform(&m);
+
+  assert_eq!(r2.wavelength, Some(450.));
+  assert_eq!(Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 1., 0.)).wavelength, None);
+}
+
+#[test]
+fn transforming_a_ray_transforms_its_differentials() {
+  let mut r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
+  r.differential = Some(RayDifferential {
+    rx_origin: Point3::new(0., 0., 0.),
+    rx_direction: Vector3::new(0.1, 0., 1.),
+    ry_origin: Point3::new(0., 0., 0.),
+    ry_direction: Vector3::new(0., 0.1, 1.),
+  });
+  let m = Transform::new().translate(1., 0., 0.).scale(2., 2., 2.).transform;
+  let d = r.transform(&m).differential.unwrap();
+
+  assert_eq!(d.rx_origin.to_tuple().equals(point(1., 0., 0.)), true);
+  assert_eq!(d.rx_direction.to_tuple().equals(vector(0.2, 0., 2.)), true);
+  assert_eq!(d.ry_direction.to_tuple().equals(vector(0., 0.2, 2.)), true);
+}
+
+#[test]
+fn transforming_a_ray_keeps_its_range() {
+  let r = Ray::new(Point3::new(1., 2., 3.), Vector3::new(0., 1., 0.)).with_range(0.5, 4.);
   let m = Transform::new().scale(2., 3., 4.).transform;
   let r2 = r.transform(&m);
 
-  assert_eq!(r2.origin.equals(point(2., 6., 12.)), true);
-  assert_eq!(r2.direction.equals(vector(0., 3., 0.)), true);
+  assert_eq!(r2.t_min, 0.5);
+  assert_eq!(r2.t_max, 4.);
+  assert_eq!(r2.in_range(0.5), true);
+  assert_eq!(r2.in_range(4.), false);
+  assert_eq!(Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.)).in_range(-1e300), true);
 }
@@ -0,0 +1,113 @@
+use crate::vectors::typed::{Normal3, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::f64;
+
+thread_local! {
+  static RNG: RefCell<StdRng> =
+    RefCell::new(StdRng::seed_from_u64(rand::thread_rng().gen::<u64>()));
+}
+
+// A uniform random number in [0, 1), for the Monte Carlo estimators. Each
+// thread draws from its own generator, seeded at random unless seed() was
+// called on that thread.
+pub fn random() -> f64 {
+  return RNG.with(|rng| rng.borrow_mut().gen::<f64>());
+}
+
+// Restarts this thread's generator from a fixed seed, so that a test sees
+// the same samples on every run.
+pub fn seed(seed: u64) {
+  RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// Two unit vectors that, together with n, form an orthonormal basis.
+pub fn orthonormal_basis(n: Normal3) -> (Vector3, Vector3) {
+  let n = n.to_vector();
+  let helper = if n.x.abs() > 0.9 {
+    Vector3::new(0., 1., 0.)
+  } else {
+    Vector3::new(1., 0., 0.)
+  };
+  let tangent = helper.cross(n).norm();
+  let bitangent = n.cross(tangent);
+  return (tangent, bitangent);
+}
+
+// Expresses a direction given in (tangent, bitangent, normal) coordinates
+// in world space.
+pub fn to_world(local: Vector3, n: Normal3) -> Vector3 {
+  let (tangent, bitangent) = orthonormal_basis(n);
+  return tangent * local.x + bitangent * local.y + n.to_vector() * local.z;
+}
+
+// A direction on the hemisphere around n with pdf cos(theta) / pi.
+pub fn cosine_sample_hemisphere(n: Normal3, u1: f64, u2: f64) -> Vector3 {
+  let r = u1.sqrt();
+  let phi = 2. * f64::consts::PI * u2;
+  let local = Vector3::new(r * phi.cos(), r * phi.sin(), (1. - u1).max(0.).sqrt());
+  return to_world(local, n);
+}
+
+// A direction on the hemisphere around n with pdf 1 / (2 pi).
+pub fn uniform_sample_hemisphere(n: Normal3, u1: f64, u2: f64) -> Vector3 {
+  let z = u1;
+  let r = (1. - z * z).max(0.).sqrt();
+  let phi = 2. * f64::consts::PI * u2;
+  let local = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+  return to_world(local, n);
+}
+
+pub fn cosine_hemisphere_pdf(n: Normal3, direction: Vector3) -> f64 {
+  return n.dot(direction).max(0.) / f64::consts::PI;
+}
+
+#[test]
+fn the_basis_is_orthonormal() {
+  for n in vec![
+    Normal3::new(0., 1., 0.),
+    Normal3::new(1., 0., 0.),
+    Normal3::new(1., -2., 3.),
+  ] {
+    let (t, b) = orthonormal_basis(n);
+    assert_eq!(n.dot(t).abs() < 1e-10, true);
+    assert_eq!(n.dot(b).abs() < 1e-10, true);
+    assert_eq!(t.dot(b).abs() < 1e-10, true);
+    assert_eq!((t.mag() - 1.).abs() < 1e-10, true);
+    assert_eq!((b.mag() - 1.).abs() < 1e-10, true);
+  }
+}
+
+#[test]
+fn hemisphere_samples_stay_above_the_surface() {
+  let n = Normal3::new(0., 0., -1.);
+
+  for (u1, u2) in vec![(0., 0.), (0.5, 0.25), (0.999, 0.75), (1., 1.)] {
+    let c = cosine_sample_hemisphere(n, u1, u2);
+    let u = uniform_sample_hemisphere(n, u1, u2);
+    assert_eq!(n.dot(c) >= 0., true);
+    assert_eq!(n.dot(u) >= 0., true);
+    assert_eq!((c.mag() - 1.).abs() < 1e-10, true);
+    assert_eq!((u.mag() - 1.).abs() < 1e-10, true);
+  }
+}
+
+#[test]
+fn the_center_of_the_square_maps_to_the_normal() {
+  let n = Normal3::new(0., 1., 0.);
+
+  assert_eq!(cosine_sample_hemisphere(n, 0., 0.).to_tuple().equals(n.to_tuple()), true);
+  assert_eq!(uniform_sample_hemisphere(n, 1., 0.).to_tuple().equals(n.to_tuple()), true);
+}
+
+#[test]
+fn seeding_repeats_the_samples() {
+  seed(7);
+  let first: Vec<f64> = (0..4).map(|_| random()).collect();
+  seed(7);
+  let second: Vec<f64> = (0..4).map(|_| random()).collect();
+
+  assert_eq!(first, second);
+  assert_eq!(first.iter().all(|&u| u >= 0. && u < 1.), true);
+}
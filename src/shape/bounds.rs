@@ -0,0 +1,84 @@
+use crate::matrix::matrix4::Matrix4;
+use crate::vectors::{point, Tuple};
+use std::f64;
+
+// An axis aligned box enclosing a shape. Shapes that go on forever, like
+// planes, have infinite extents along the axes they span.
+#[derive(Copy, Clone)]
+pub struct Bounds {
+  pub min: Tuple,
+  pub max: Tuple,
+}
+
+impl Bounds {
+  pub fn new(min: Tuple, max: Tuple) -> Bounds {
+    return Bounds { min: min, max: max };
+  }
+
+  pub fn infinite() -> Bounds {
+    return Bounds::new(
+      point(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+      point(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+    );
+  }
+
+  pub fn is_finite(&self) -> bool {
+    return self.min.x.is_finite()
+      && self.min.y.is_finite()
+      && self.min.z.is_finite()
+      && self.max.x.is_finite()
+      && self.max.y.is_finite()
+      && self.max.z.is_finite();
+  }
+
+  // The box enclosing these bounds once transformed, found from its eight
+  // corners. Infinite bounds stay infinite, since a rotation can spread an
+  // infinite extent into every axis.
+  pub fn transform(&self, transform: &Matrix4) -> Bounds {
+    if !self.is_finite() {
+      return Bounds::infinite();
+    }
+
+    let mut min = point(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = point(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &x in &[self.min.x, self.max.x] {
+      for &y in &[self.min.y, self.max.y] {
+        for &z in &[self.min.z, self.max.z] {
+          let p = Matrix4::mult_4x4_by_1d(transform, &point(x, y, z));
+          min = point(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+          max = point(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+      }
+    }
+    return Bounds::new(min, max);
+  }
+}
+
+#[test]
+fn transforming_bounds_encloses_the_corners() {
+  use crate::transform::Transform;
+
+  let b = Bounds::new(point(-1., -1., -1.), point(1., 1., 1.));
+  let t = Transform::new()
+    .translate(1., 2., 3.)
+    .rotate_y(f64::consts::PI / 4.)
+    .transform;
+  let moved = b.transform(&t);
+  let r = (2.0f64).sqrt();
+
+  assert_eq!(moved.min.equals(point(1. - r, 1., 3. - r)), true);
+  assert_eq!(moved.max.equals(point(1. + r, 3., 3. + r)), true);
+}
+
+#[test]
+fn infinite_bounds_stay_infinite() {
+  let b = Bounds::new(
+    point(f64::NEG_INFINITY, 0., f64::NEG_INFINITY),
+    point(f64::INFINITY, 0., f64::INFINITY),
+  );
+  let t = crate::transform::Transform::new()
+    .translate(0., 1., 0.)
+    .transform;
+
+  assert_eq!(b.transform(&t).is_finite(), false);
+}
@@ -0,0 +1,20 @@
+use crate::ray::Ray;
+use crate::shape::bounds::Bounds;
+use crate::vectors::typed::{Normal3, Point3};
+
+// The part of a primitive that is particular to it. Everything here is in
+// object space: Shape transforms rays and points in, normals back out, and
+// looks after the material, so a primitive only has to describe its own
+// surface. Sphere and Plane are built on it too.
+//
+// Register one with Shape::custom.
+pub trait Geometry: Send + Sync {
+  // Every t at which the object space ray meets the surface, in any order.
+  fn local_intersect(&self, ray: Ray) -> Vec<f64>;
+
+  // The outward surface normal at an object space point on the surface.
+  fn local_normal_at(&self, point: Point3) -> Normal3;
+
+  // A box enclosing the surface in object space.
+  fn bounds(&self) -> Bounds;
+}
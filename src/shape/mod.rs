@@ -1,21 +1,38 @@
-use crate::colors::Color;
 use crate::intersections::{prepare_computations, Intersection, Intersections};
 use crate::material::Material;
-use crate::matrix::Matrix;
+use crate::matrix::matrix4::{CachedTransform, Matrix4};
 use crate::ray::Ray;
 use crate::transform::Transform;
-use crate::vectors::{dot, point, vector, Tuple};
+use crate::vectors::typed::{Normal3, Point3, Vector3};
+use crate::vectors::{point, vector, Tuple};
 use rand::Rng;
 use std::f64;
+use std::sync::Arc;
 
+pub mod bounds;
+pub mod geometry;
 pub mod plane;
 pub mod sphere;
 
+use bounds::Bounds;
+use geometry::Geometry;
+
 #[derive(Clone)]
 pub enum ShapeType {
   Sphere,
   Plane,
   Test,
+  // geometry supplied from outside the crate
+  Custom(Arc<dyn Geometry>),
+}
+
+// A point picked on the surface of a shape, with the pdf of picking it
+// measured per unit of world space area.
+#[derive(Copy, Clone)]
+pub struct SurfaceSample {
+  pub point: Point3,
+  pub normal: Normal3,
+  pub pdf: f64,
 }
 
 #[derive(Clone)]
@@ -23,7 +40,8 @@ pub struct Shape {
   pub shape_type: ShapeType,
   pub origin: Tuple,
   pub handle: u32,
-  pub transform: Matrix,
+  // set through set_transform, which keeps the inverses up to date
+  transform: CachedTransform,
   pub material: Material,
 }
 
@@ -34,7 +52,7 @@ impl Shape {
       shape_type: shape_type,
       origin: point(0., 0., 0.),
       handle: rng.gen::<u32>(),
-      transform: Matrix::identity(4),
+      transform: CachedTransform::identity(),
       material: Material::new(),
     };
   }
@@ -47,38 +65,132 @@ impl Shape {
     return sphere;
   }
 
+  pub fn custom(geometry: Arc<dyn Geometry>) -> Shape {
+    return Shape::new(ShapeType::Custom(geometry));
+  }
+
+  // Hits outside the ray's range are dropped here, so primitives need not
+  // check it themselves.
   pub fn intersects(&self, ray: Ray) -> Intersections {
-    let i = Matrix::inverse(&self.transform);
-    let local_ray = ray.transform(&i);
+    let mut xs = vec![];
+    self.intersect_into(ray, &mut xs);
+    return Intersections::new(xs);
+  }
+
+  // Same as intersects(), adding the hits to xs so that a whole world can
+  // be intersected into one list.
+  pub fn intersect_into<'a>(&'a self, ray: Ray, xs: &mut Vec<Intersection<'a>>) {
+    // the local ray keeps the range, so hits outside it are dropped before
+    // they are pushed
+    let local_ray = ray.transform(&self.transform.inverse);
+
+    match &self.shape_type {
+      // the built in shapes push their hits directly, without a Vec each
+      ShapeType::Sphere => sphere::Sphere::intersect_into(self, local_ray, xs),
+      ShapeType::Plane => plane::Plane::intersect_into(self, local_ray, xs),
+      _ => {
+        for t in self.geometry().local_intersect(local_ray) {
+          if local_ray.in_range(t) {
+            xs.push(Intersection::new(t, self));
+          }
+        }
+      }
+    }
+  }
+
+  // The shape with its material as seen by light of a single wavelength.
+  pub fn at_wavelength(&self, wavelength: f64) -> Shape {
+    let mut shape = self.clone();
+    shape.material = self.material.at_wavelength(wavelength);
+    return shape;
+  }
+
+  pub fn set_transform(&mut self, transform: Matrix4) {
+    self.transform = CachedTransform::new(transform);
+  }
+
+  pub fn transform(&self) -> &Matrix4 {
+    return &self.transform.transform;
+  }
 
+  pub fn inverse(&self) -> &Matrix4 {
+    return &self.transform.inverse;
+  }
+
+  pub fn inverse_transpose(&self) -> &Matrix4 {
+    return &self.transform.inverse_transpose;
+  }
+
+  pub fn normal_at(&self, p: Point3) -> Normal3 {
+    let object_point = self.transform.invert_point(p);
+    let object_normal = self.geometry().local_normal_at(object_point);
+    return self.transform.apply_normal(object_normal);
+  }
+
+  // The normal used for shading: the geometric normal, tilted by the
+  // material's bump map if it has one.
+  pub fn shading_normal_at(&self, p: Point3) -> Normal3 {
+    let bump = match &self.material.bump {
+      Some(bump) => bump,
+      None => return self.normal_at(p),
+    };
+    let object_point = self.transform.invert_point(p);
+    let object_normal = self.geometry().local_normal_at(object_point);
+    let bumped = bump.perturb(object_point, object_normal);
+    return self.transform.apply_normal(bumped);
+  }
+
+  // The object space surface, built in or registered.
+  pub fn geometry(&self) -> &dyn Geometry {
     return match &self.shape_type {
-      ShapeType::Sphere => sphere::Sphere::intersects(self, local_ray),
-      ShapeType::Plane => plane::Plane::intersects(self, local_ray),
-      ShapeType::Test => Intersections::new(vec![]),
+      ShapeType::Sphere => &sphere::Sphere,
+      ShapeType::Plane => &plane::Plane,
+      ShapeType::Test => &TestShape,
+      ShapeType::Custom(geometry) => geometry.as_ref(),
     };
   }
 
-  pub fn set_transform(&mut self, transform: Matrix) {
+  // A world space box enclosing the shape.
+  pub fn bounds(&self) -> Bounds {
+    return self.geometry().bounds().transform(self.transform());
+  }
+
+  pub fn can_sample_surface(&self) -> bool {
     return match &self.shape_type {
-      ShapeType::Sphere => sphere::Sphere::set_transform(self, transform),
-      ShapeType::Plane => plane::Plane::set_transform(self, transform),
-      ShapeType::Test => self.transform = transform,
+      ShapeType::Sphere => true,
+      ShapeType::Plane => false,
+      ShapeType::Test => false,
+      ShapeType::Custom(_) => false,
     };
   }
 
-  pub fn normal_at(&self, p: Tuple) -> Tuple {
-    let inverse_transform = Matrix::inverse(&self.transform);
-    let object_point = Matrix::mult_4x4_by_1d(&inverse_transform, &p);
-    let object_normal = match &self.shape_type {
-      ShapeType::Sphere => sphere::Sphere::normal_at(self, object_point),
-      ShapeType::Plane => plane::Plane::normal_at(self, object_point),
-      ShapeType::Test => vector(1., 1., 1.).norm(),
+  // Picks a point on the surface from two uniform random numbers, or None
+  // for shapes that cannot be sampled (such as infinite planes).
+  pub fn sample_surface(&self, u1: f64, u2: f64) -> Option<SurfaceSample> {
+    return match &self.shape_type {
+      ShapeType::Sphere => Some(sphere::Sphere::sample_surface(self, u1, u2)),
+      ShapeType::Plane => None,
+      ShapeType::Test => None,
+      ShapeType::Custom(_) => None,
     };
+  }
+}
+
+// The book's test shape: a unit box that nothing hits, with the same
+// normal everywhere.
+struct TestShape;
 
-    let transposed_inverse_transform = Matrix::transpose(&inverse_transform);
-    let mut world_normal = Matrix::mult_4x4_by_1d(&transposed_inverse_transform, &object_normal);
-    world_normal.w = 0.;
-    return world_normal.norm();
+impl Geometry for TestShape {
+  fn local_intersect(&self, _ray: Ray) -> Vec<f64> {
+    return vec![];
+  }
+
+  fn local_normal_at(&self, _point: Point3) -> Normal3 {
+    return Normal3::new(1., 1., 1.);
+  }
+
+  fn bounds(&self) -> Bounds {
+    return Bounds::new(point(-1., -1., -1.), point(1., 1., 1.));
   }
 }
 
@@ -92,7 +204,7 @@ impl PartialEq for Shape {
 fn the_default_transformation() {
   let s = Shape::new(ShapeType::Test);
 
-  assert_eq!(Matrix::equals(&s.transform, &Matrix::identity(4)), true);
+  assert_eq!(Matrix4::equals(s.transform(), &Matrix4::identity()), true);
 }
 
 #[test]
@@ -102,7 +214,7 @@ fn assigns_a_transformation() {
   let expected = transform.clone();
   s.set_transform(transform);
 
-  assert_eq!(Matrix::equals(&s.transform, &expected), true);
+  assert_eq!(Matrix4::equals(s.transform(), &expected), true);
 }
 
 #[test]
@@ -119,7 +231,7 @@ fn assigns_a_material() {
 fn creates_a_glass_sphere() {
   let s = Shape::glass_sphere();
 
-  assert_eq!(Matrix::equals(&s.transform, &Matrix::identity(4)), true);
+  assert_eq!(Matrix4::equals(s.transform(), &Matrix4::identity()), true);
   assert_eq!(s.material.transparency, 1.0);
   assert_eq!(s.material.refractive_index, 1.5);
 }
@@ -141,14 +253,14 @@ fn finding_n1_and_n2_and_various_intersections() {
   c.set_transform(c_transform);
   c.material.refractive_index = 2.5;
 
-  let r = Ray::new(point(0., 0., -4.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -4.), Vector3::new(0., 0., 1.));
   let xs = Intersections::new(vec![
-    Intersection::new(2., a.clone()),
-    Intersection::new(2.75, b.clone()),
-    Intersection::new(3.25, c.clone()),
-    Intersection::new(4.75, b.clone()),
-    Intersection::new(5.25, c.clone()),
-    Intersection::new(6., a.clone()),
+    Intersection::new(2., &a),
+    Intersection::new(2.75, &b),
+    Intersection::new(3.25, &c),
+    Intersection::new(4.75, &b),
+    Intersection::new(5.25, &c),
+    Intersection::new(6., &a),
   ]);
 
   let comp1 = prepare_computations(xs.intersections[0].clone(), r, xs.clone());
@@ -175,3 +287,98 @@ fn finding_n1_and_n2_and_various_intersections() {
   assert_eq!(comp6.n1, 1.5);
   assert_eq!(comp6.n2, 1.0);
 }
+
+// An axis aligned cube from -1 to 1, standing in for geometry a user of
+// the crate might register.
+#[cfg(test)]
+struct TestCube;
+
+#[cfg(test)]
+impl Geometry for TestCube {
+  fn local_intersect(&self, ray: Ray) -> Vec<f64> {
+    let axis = |origin: f64, direction: f64| {
+      let tmin = (-1. - origin) / direction;
+      let tmax = (1. - origin) / direction;
+      return (tmin.min(tmax), tmin.max(tmax));
+    };
+    let (xmin, xmax) = axis(ray.origin.x, ray.direction.x);
+    let (ymin, ymax) = axis(ray.origin.y, ray.direction.y);
+    let (zmin, zmax) = axis(ray.origin.z, ray.direction.z);
+    let tmin = xmin.max(ymin).max(zmin);
+    let tmax = xmax.min(ymax).min(zmax);
+    if tmin > tmax {
+      return vec![];
+    }
+    return vec![tmin, tmax];
+  }
+
+  fn local_normal_at(&self, point: Point3) -> Normal3 {
+    let (x, y, z) = (point.x.abs(), point.y.abs(), point.z.abs());
+    if x >= y && x >= z {
+      return Normal3::new(point.x, 0., 0.);
+    }
+    if y >= z {
+      return Normal3::new(0., point.y, 0.);
+    }
+    return Normal3::new(0., 0., point.z);
+  }
+
+  fn bounds(&self) -> Bounds {
+    return Bounds::new(point(-1., -1., -1.), point(1., 1., 1.));
+  }
+}
+
+#[test]
+fn a_custom_shape_is_intersected_in_object_space() {
+  let mut s = Shape::custom(Arc::new(TestCube));
+  s.set_transform(
+    Transform::new()
+      .translate(5., 0., 0.)
+      .scale(2., 2., 2.)
+      .transform,
+  );
+
+  let r = Ray::new(Point3::new(5., 0., -10.), Vector3::new(0., 0., 1.));
+  let xs = s.intersects(r);
+  assert_eq!(xs.intersections.len(), 2);
+  assert_eq!(xs.intersections[0].t, 8.);
+  assert_eq!(xs.intersections[1].t, 12.);
+
+  let missed = Ray::new(Point3::new(0., 0., -10.), Vector3::new(0., 0., 1.));
+  assert_eq!(s.intersects(missed).intersections.len(), 0);
+}
+
+#[test]
+fn a_custom_shape_has_its_normal_transformed() {
+  let mut s = Shape::custom(Arc::new(TestCube));
+  s.set_transform(Transform::new().rotate_y(f64::consts::PI / 2.).transform);
+
+  let n = s.normal_at(Point3::new(0., 0., -1.)).to_tuple();
+  assert_eq!(n.equals(vector(0., 0., -1.)), true);
+  let n = s.normal_at(Point3::new(1., 0.5, 0.2)).to_tuple();
+  assert_eq!(n.equals(vector(1., 0., 0.)), true);
+}
+
+#[test]
+fn a_shapes_bounds_are_in_world_space() {
+  let mut s = Shape::custom(Arc::new(TestCube));
+  s.set_transform(Transform::new().translate(0., 3., 0.).transform);
+  let b = s.bounds();
+  assert_eq!(b.min.equals(point(-1., 2., -1.)), true);
+  assert_eq!(b.max.equals(point(1., 4., 1.)), true);
+
+  let plane = Shape::new(ShapeType::Plane);
+  assert_eq!(plane.bounds().is_finite(), false);
+}
+
+#[test]
+fn intersections_outside_the_rays_range_are_dropped() {
+  let s = Shape::new(ShapeType::Sphere);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+
+  assert_eq!(s.intersects(r).intersections.len(), 2);
+  let xs = s.intersects(r.with_range(0., 5.));
+  assert_eq!(xs.intersections.len(), 1);
+  assert_eq!(xs.intersections[0].t, 4.);
+  assert_eq!(s.intersects(r.with_range(4.5, 5.5)).intersections.len(), 0);
+}
@@ -1,50 +1,43 @@
-use crate::colors::Color;
-use crate::intersections::{Intersection, Intersections};
-use crate::material::Material;
-use crate::matrix::Matrix;
+use crate::intersections::Intersection;
 use crate::ray::Ray;
+use crate::shape::bounds::Bounds;
+use crate::shape::geometry::Geometry;
 use crate::shape::Shape;
-use crate::transform::Transform;
-use crate::vectors::{dot, point, vector, Tuple};
-use rand::Rng;
+use crate::vectors::point;
+use crate::vectors::typed::{Normal3, Point3};
 use std::f64;
 
-#[derive(Clone)]
-pub struct Plane {
-  pub handle: u32,
-  pub transform: Matrix,
-  pub material: Material,
-}
+// The xz plane through the origin.
+pub struct Plane;
 
 impl Plane {
-  pub fn new() -> Plane {
-    let mut rng = rand::thread_rng();
-    return Plane {
-      handle: rng.gen::<u32>(),
-      transform: Matrix::identity(4),
-      material: Material::new(),
-    };
+  pub fn intersect_into<'a>(object: &'a Shape, ray: Ray, xs: &mut Vec<Intersection<'a>>) {
+    if ray.direction.y.abs() >= 1e-10 {
+      let t = -ray.origin.y / ray.direction.y;
+      if ray.in_range(t) {
+        xs.push(Intersection::new(t, object));
+      }
+    }
   }
+}
 
-  pub fn intersects(object: &Shape, ray: Ray) -> Intersections {
-    if (ray.direction.y.abs() < 1e-10) {
-      return Intersections {
-        intersections: vec![],
-      };
-    } else {
-      let t = -ray.origin.y / ray.direction.y;
-      return Intersections {
-        intersections: vec![Intersection::new(t, object.clone())],
-      };
+impl Geometry for Plane {
+  fn local_intersect(&self, ray: Ray) -> Vec<f64> {
+    if ray.direction.y.abs() < 1e-10 {
+      return vec![];
     }
+    return vec![-ray.origin.y / ray.direction.y];
   }
 
-  pub fn set_transform(object: &mut Shape, transform: Matrix) {
-    object.transform = transform;
+  fn local_normal_at(&self, _point: Point3) -> Normal3 {
+    return Normal3::new(0., 1., 0.);
   }
 
-  pub fn normal_at(object: &Shape, local_point: Tuple) -> Tuple {
-    return vector(0., 1., 0.);
+  fn bounds(&self) -> Bounds {
+    return Bounds::new(
+      point(f64::NEG_INFINITY, 0., f64::NEG_INFINITY),
+      point(f64::INFINITY, 0., f64::INFINITY),
+    );
   }
 }
 
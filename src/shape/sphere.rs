@@ -1,82 +1,89 @@
 use crate::colors::Color;
-use crate::intersections::{Intersection, Intersections};
-use crate::material::Material;
-use crate::matrix::Matrix;
+use crate::intersections::Intersection;
+use crate::matrix::matrix4::Matrix4;
 use crate::ray::Ray;
-use crate::shape::{Shape, ShapeType};
+use crate::shape::bounds::Bounds;
+use crate::shape::geometry::Geometry;
+use crate::shape::{Shape, ShapeType, SurfaceSample};
 use crate::transform::Transform;
-use crate::vectors::{dot, point, vector, Tuple};
-use rand::Rng;
+use crate::vectors::typed::{Normal3, Point3, Vector3};
+use crate::vectors::{point, vector};
 use std::f64;
 
-#[derive(Clone)]
-pub struct Sphere {
-  pub origin: Tuple,
-  pub radius: f64,
-  pub handle: u32,
-  pub transform: Matrix,
-  pub material: Material,
-}
+// The unit sphere at the origin.
+pub struct Sphere;
 
 impl Sphere {
-  pub fn new(origin: Tuple, radius: f64) -> Sphere {
-    let mut rng = rand::thread_rng();
-    return Sphere {
-      origin: origin,
-      radius: radius,
-      handle: rng.gen::<u32>(),
-      transform: Matrix::identity(4),
-      material: Material::new(),
-    };
-  }
-
-  pub fn intersects(object: &Shape, ray: Ray) -> Intersections {
-    let sphere_to_ray = ray.origin.sub(point(0., 0., 0.));
-    let a = dot(ray.direction, ray.direction);
-    let b = 2. * dot(ray.direction, sphere_to_ray);
-    let c = dot(sphere_to_ray, sphere_to_ray) - 1.;
+  fn roots(ray: Ray) -> Option<(f64, f64)> {
+    let sphere_to_ray = ray.origin - Point3::new(0., 0., 0.);
+    let a = ray.direction.dot(ray.direction);
+    let b = 2. * ray.direction.dot(sphere_to_ray);
+    let c = sphere_to_ray.dot(sphere_to_ray) - 1.;
     let discriminant = b * b - 4. * a * c;
 
     if discriminant < 0. {
-      return Intersections {
-        intersections: vec![],
-      };
+      return None;
     }
 
     let dsqrt = discriminant.sqrt();
     let t1 = (-b - dsqrt) / 2. / a;
     let t2 = (-b + dsqrt) / 2. / a;
-    if t2 < t1 {
-      return Intersections {
-        intersections: vec![
-          Intersection::new(t2, object.clone()),
-          Intersection::new(t1, object.clone()),
-        ],
-      };
+    return Some((t1.min(t2), t1.max(t2)));
+  }
+
+  // Pushes the hits, nearest first, onto xs.
+  pub fn intersect_into<'a>(object: &'a Shape, ray: Ray, xs: &mut Vec<Intersection<'a>>) {
+    if let Some((t1, t2)) = Sphere::roots(ray) {
+      for t in [t1, t2].iter() {
+        if ray.in_range(*t) {
+          xs.push(Intersection::new(*t, object));
+        }
+      }
     }
-    return Intersections {
-      intersections: vec![
-        Intersection::new(t1, object.clone()),
-        Intersection::new(t2, object.clone()),
-      ],
+  }
+
+  pub fn sample_surface(object: &Shape, u1: f64, u2: f64) -> SurfaceSample {
+    // uniform over the unit sphere in object space
+    let z = 1. - 2. * u1;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * f64::consts::PI * u2;
+    let object_point = Point3::new(r * phi.cos(), r * phi.sin(), z);
+    let world_point = *object.transform() * object_point;
+
+    // the transform stretches each patch of area by det(M) * |M^-T n|
+    let object_normal = object_point - Point3::new(0., 0., 0.);
+    let stretched = *object.inverse_transpose() * object_normal;
+    let jacobian = Matrix4::determinant(object.transform()).abs() * stretched.mag();
+
+    return SurfaceSample {
+      point: world_point,
+      normal: Normal3::from_vector(stretched),
+      pdf: 1. / (4. * f64::consts::PI * jacobian),
     };
   }
+}
 
-  pub fn set_transform(object: &mut Shape, transform: Matrix) {
-    object.transform = transform;
+impl Geometry for Sphere {
+  fn local_intersect(&self, ray: Ray) -> Vec<f64> {
+    return match Sphere::roots(ray) {
+      Some((t1, t2)) => vec![t1, t2],
+      None => vec![],
+    };
   }
 
-  pub fn normal_at(object: &Shape, object_point: Tuple) -> Tuple {
-    let origin = point(0., 0., 0.);
-    let object_normal = object_point.sub(origin).norm();
-    return object_normal;
+  fn local_normal_at(&self, point: Point3) -> Normal3 {
+    return Normal3::from_vector(point - Point3::new(0., 0., 0.));
+  }
+
+  fn bounds(&self) -> Bounds {
+    return Bounds::new(point(-1., -1., -1.), point(1., 1., 1.));
   }
 }
 
 #[test]
 fn it_computes_intersects_1() {
-  let origin = point(0., 0., -5.);
-  let direction = vector(0., 0., 1.);
+  let origin = Point3::new(0., 0., -5.);
+  let direction = Vector3::new(0., 0., 1.);
   let r = Ray::new(origin, direction);
   let s = Shape::new(ShapeType::Sphere);
   let intersects = s.intersects(r);
@@ -88,8 +95,8 @@ fn it_computes_intersects_1() {
 
 #[test]
 fn it_computes_intersects_2() {
-  let origin = point(0., 1., -5.);
-  let direction = vector(0., 0., 1.);
+  let origin = Point3::new(0., 1., -5.);
+  let direction = Vector3::new(0., 0., 1.);
   let r = Ray::new(origin, direction);
   let s = Shape::new(ShapeType::Sphere);
   let intersects = s.intersects(r);
@@ -101,8 +108,8 @@ fn it_computes_intersects_2() {
 
 #[test]
 fn it_computes_intersects_3() {
-  let origin = point(0., 2., -5.);
-  let direction = vector(0., 0., 1.);
+  let origin = Point3::new(0., 2., -5.);
+  let direction = Vector3::new(0., 0., 1.);
   let r = Ray::new(origin, direction);
   let s = Shape::new(ShapeType::Sphere);
   let intersects = s.intersects(r);
@@ -112,8 +119,8 @@ fn it_computes_intersects_3() {
 
 #[test]
 fn it_computes_intersects_4() {
-  let origin = point(0., 0., 0.);
-  let direction = vector(0., 0., 1.);
+  let origin = Point3::new(0., 0., 0.);
+  let direction = Vector3::new(0., 0., 1.);
   let r = Ray::new(origin, direction);
   let s = Shape::new(ShapeType::Sphere);
   let intersects = s.intersects(r);
@@ -125,8 +132,8 @@ fn it_computes_intersects_4() {
 
 #[test]
 fn it_computes_intersects_5() {
-  let origin = point(0., 0., 5.);
-  let direction = vector(0., 0., 1.);
+  let origin = Point3::new(0., 0., 5.);
+  let direction = Vector3::new(0., 0., 1.);
   let r = Ray::new(origin, direction);
 
   let s = Shape::new(ShapeType::Sphere);
@@ -140,10 +147,10 @@ fn it_computes_intersects_5() {
 #[test]
 fn default_transformation_is_identity() {
   let s = Shape::new(ShapeType::Sphere);
-  let transform = s.transform;
-  let i = Matrix::identity(4);
+  let transform = s.transform();
+  let i = Matrix4::identity();
 
-  assert_eq!(Matrix::equals(&transform, &i), true);
+  assert_eq!(Matrix4::equals(transform, &i), true);
 }
 
 #[test]
@@ -153,12 +160,12 @@ fn can_set_transformation() {
   s.set_transform(transform);
   let exp = Transform::new().translate(2., 3., 4.).transform;
 
-  assert_eq!(Matrix::equals(&s.transform, &exp), true);
+  assert_eq!(Matrix4::equals(s.transform(), &exp), true);
 }
 
 #[test]
 fn intersecting_scaled_sphere_with_a_ray() {
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let mut s = Shape::new(ShapeType::Sphere);
   let transform = Transform::new().scale(2., 2., 2.).transform;
   s.set_transform(transform);
@@ -171,7 +178,7 @@ fn intersecting_scaled_sphere_with_a_ray() {
 
 #[test]
 fn intersecting_translated_sphere_with_a_ray() {
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let mut s = Shape::new(ShapeType::Sphere);
   let transform = Transform::new().translate(5., 0., 0.).transform;
   s.set_transform(transform);
@@ -184,7 +191,7 @@ fn intersecting_translated_sphere_with_a_ray() {
 fn compute_normal_at_a_point_on_x_axis() {
   let s = Shape::new(ShapeType::Sphere);
 
-  let n = s.normal_at(point(1., 0., 0.));
+  let n = s.normal_at(Point3::new(1., 0., 0.)).to_tuple();
   assert_eq!(n.equals(vector(1., 0., 0.)), true);
 }
 
@@ -192,7 +199,7 @@ fn compute_normal_at_a_point_on_x_axis() {
 fn compute_normal_at_a_point_on_y_axis() {
   let s = Shape::new(ShapeType::Sphere);
 
-  let n = s.normal_at(point(0., 1., 0.));
+  let n = s.normal_at(Point3::new(0., 1., 0.)).to_tuple();
   assert_eq!(n.equals(vector(0., 1., 0.)), true);
 }
 
@@ -200,7 +207,7 @@ fn compute_normal_at_a_point_on_y_axis() {
 fn compute_normal_at_a_point_on_z_axis() {
   let s = Shape::new(ShapeType::Sphere);
 
-  let n = s.normal_at(point(0., 0., 1.));
+  let n = s.normal_at(Point3::new(0., 0., 1.)).to_tuple();
   assert_eq!(n.equals(vector(0., 0., 1.)), true);
 }
 
@@ -209,7 +216,7 @@ fn compute_normal_at_a_non_axial_point() {
   let s = Shape::new(ShapeType::Sphere);
   let root3 = 3.0f64.sqrt() / 3.;
 
-  let n = s.normal_at(point(root3, root3, root3));
+  let n = s.normal_at(Point3::new(root3, root3, root3)).to_tuple();
   assert_eq!(n.equals(vector(root3, root3, root3)), true);
 }
 
@@ -218,7 +225,7 @@ fn the_normal_is_a_normalized_vector() {
   let s = Shape::new(ShapeType::Sphere);
   let root3 = 3.0f64.sqrt() / 3.;
 
-  let n = s.normal_at(point(root3, root3, root3));
+  let n = s.normal_at(Point3::new(root3, root3, root3)).to_tuple();
 
   let exp = n.norm();
   assert_eq!(n.equals(exp), true);
@@ -230,7 +237,7 @@ fn compute_normal_on_a_translated_sphere() {
   let transform = Transform::new().translate(0., 1., 0.).transform;
   s.set_transform(transform);
 
-  let n = s.normal_at(point(0., 1.70711, -0.70711));
+  let n = s.normal_at(Point3::new(0., 1.70711, -0.70711)).to_tuple();
 
   assert_eq!(n.approx_equals(vector(0., 0.70711, -0.70711)), true);
 }
@@ -244,13 +251,37 @@ fn compute_normal_on_a_transformed_sphere() {
     .transform;
   s.set_transform(transform);
 
-  let n = s.normal_at(point(0., 2.0f64.sqrt() / 2., -2.0f64.sqrt() / 2.));
+  let n = s
+    .normal_at(Point3::new(0., 2.0f64.sqrt() / 2., -2.0f64.sqrt() / 2.))
+    .to_tuple();
   assert_eq!(n.approx_equals(vector(0., 0.97014, -0.24254)), true);
 }
 
+#[test]
+fn sampling_the_surface_of_a_transformed_sphere() {
+  let mut s = Shape::new(ShapeType::Sphere);
+  let transform = Transform::new()
+    .translate(1., 2., 3.)
+    .scale(2., 2., 2.)
+    .transform;
+  s.set_transform(transform);
+
+  for (u1, u2) in vec![(0., 0.), (0.3, 0.7), (0.5, 0.5), (1., 0.2)] {
+    let sample = s.sample_surface(u1, u2).unwrap();
+    let center = Point3::new(1., 2., 3.);
+    assert_eq!(((sample.point - center).mag() - 2.).abs() < 1e-9, true);
+    let n = s.normal_at(sample.point);
+    assert_eq!(sample.normal.to_tuple().approx_equals(n.to_tuple()), true);
+    assert_eq!(
+      (sample.pdf - 1. / (16. * f64::consts::PI)).abs() < 1e-9,
+      true
+    );
+  }
+}
+
 #[test]
 fn a_sphere_has_a_default_material() {
-  let mut s = Sphere::new(point(0., 0., 0.), 1.);
+  let mut s = Shape::new(ShapeType::Sphere);
 
   let m = s.material;
   assert_eq!(Color::equals(m.color, Color::new(1., 1., 1.)), true);
@@ -258,7 +289,7 @@ fn a_sphere_has_a_default_material() {
 
 #[test]
 fn a_sphere_can_change_its_material() {
-  let mut s = Sphere::new(point(0., 0., 0.), 1.);
+  let mut s = Shape::new(ShapeType::Sphere);
   s.material.ambient = 1.0;
   s.material.color = Color::new(1., 0.2, 1.0);
 
@@ -268,3 +299,17 @@ fn a_sphere_can_change_its_material() {
     true
   );
 }
+
+#[test]
+fn a_sphere_is_a_geometry_like_any_other() {
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let s = Shape::new(ShapeType::Sphere);
+  let ts = s.geometry().local_intersect(r);
+  let xs = s.intersects(r);
+
+  assert_eq!(ts, vec![4., 6.]);
+  assert_eq!(xs.intersections[0].t, ts[0]);
+  assert_eq!(xs.intersections[1].t, ts[1]);
+  let n = s.geometry().local_normal_at(Point3::new(0., 1., 0.));
+  assert_eq!(n.to_tuple().equals(vector(0., 1., 0.)), true);
+}
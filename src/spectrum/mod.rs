@@ -0,0 +1,223 @@
+use crate::colors::Color;
+use std::f64;
+
+// The visible range sampled by the spectral renderer, in nanometers.
+pub const MIN_WAVELENGTH: f64 = 380.;
+pub const MAX_WAVELENGTH: f64 = 730.;
+
+// Where the smooth rgb basis hands over from blue to green and from green
+// to red. Outside the transitions exactly one basis function is 1, so the
+// 650, 550 and 450 nm bands read back pure r, g and b.
+const BLUE_GREEN: (f64, f64) = (480., 510.);
+const GREEN_RED: (f64, f64) = (570., 600.);
+
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+  [3.2406, -1.5372, -0.4986],
+  [-0.9689, 1.8758, 0.0415],
+  [0.0557, -0.2040, 1.0570],
+];
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+  let t = ((x - edge0) / (edge1 - edge0)).max(0.).min(1.);
+  return t * t * (3. - 2. * t);
+}
+
+// Upsamples an rgb color to its value at one wavelength, with a smooth
+// basis that sums to one everywhere: white stays a flat spectrum and
+// existing colors keep their rgb value in the middle of each band.
+pub fn rgb_at(c: Color, wavelength: f64) -> f64 {
+  let to_green = smoothstep(BLUE_GREEN.0, BLUE_GREEN.1, wavelength);
+  let to_red = smoothstep(GREEN_RED.0, GREEN_RED.1, wavelength);
+  return c.b * (1. - to_green) + c.g * (to_green - to_red) + c.r * to_red;
+}
+
+// A color reduced to its value at one wavelength, in every channel, so the
+// usual rgb arithmetic carries a single spectral sample.
+pub fn project(c: Color, wavelength: Option<f64>) -> Color {
+  return match wavelength {
+    Some(w) => {
+      let v = rgb_at(c, w);
+      Color::new(v, v, v)
+    }
+    None => c,
+  };
+}
+
+fn lobe(wavelength: f64, mean: f64, below: f64, above: f64) -> f64 {
+  let sigma = if wavelength < mean { below } else { above };
+  let t = (wavelength - mean) / sigma;
+  return (-0.5 * t * t).exp();
+}
+
+// The CIE 1931 color matching functions, using the multi-lobe fit of
+// Wyman, Sloan and Shirley.
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+  let w = wavelength;
+  let x = 1.056 * lobe(w, 599.8, 37.9, 31.0) + 0.362 * lobe(w, 442.0, 16.0, 26.7)
+    - 0.065 * lobe(w, 501.1, 20.4, 26.2);
+  let y = 0.821 * lobe(w, 568.8, 46.9, 40.5) + 0.286 * lobe(w, 530.9, 16.3, 31.1);
+  let z = 1.217 * lobe(w, 437.0, 11.8, 36.0) + 0.681 * lobe(w, 459.0, 26.0, 13.8);
+  return (x, y, z);
+}
+
+// Linear sRGB, without the gamma curve; the canvas stores linear values.
+pub fn xyz_to_rgb(x: f64, y: f64, z: f64) -> Color {
+  let m = XYZ_TO_SRGB;
+  return Color::new(
+    m[0][0] * x + m[0][1] * y + m[0][2] * z,
+    m[1][0] * x + m[1][1] * y + m[1][2] * z,
+    m[2][0] * x + m[2][1] * y + m[2][2] * z,
+  );
+}
+
+// The rgb of a flat, equal energy spectrum, used to white balance so that a
+// white surface under a white light comes out white. It integrates the CIE
+// fit over the whole visible range, so work it out once and hand it to each
+// SpectralFilm.
+pub fn white_point() -> Color {
+  let steps = 350;
+  let width = (MAX_WAVELENGTH - MIN_WAVELENGTH) / steps as f64;
+  let (mut x, mut y, mut z) = (0., 0., 0.);
+  for i in 0..steps {
+    let (cx, cy, cz) = cie_xyz(MIN_WAVELENGTH + (i as f64 + 0.5) * width);
+    x += cx;
+    y += cy;
+    z += cz;
+  }
+  let n = steps as f64;
+  return xyz_to_rgb(x / n, y / n, z / n);
+}
+
+// The wavelength for the i-th of n stratified samples, given a jitter in
+// [0, 1). Samples are uniform over the visible range.
+pub fn sample_wavelength(i: usize, n: usize, jitter: f64) -> f64 {
+  let u = (i as f64 + jitter) / n as f64;
+  return MIN_WAVELENGTH + u * (MAX_WAVELENGTH - MIN_WAVELENGTH);
+}
+
+// What a single sample at this wavelength adds to the rgb of a film
+// balanced against white, for paths that carry one wavelength in place of
+// a whole spectrum. Averaged over wavelengths drawn uniformly from the
+// visible range, a flat spectrum comes out white.
+pub fn balanced_rgb(wavelength: f64, white: Color) -> Color {
+  let (x, y, z) = cie_xyz(wavelength);
+  let rgb = xyz_to_rgb(x, y, z);
+  return Color::new(rgb.r / white.r, rgb.g / white.g, rgb.b / white.b);
+}
+
+// Accumulates spectral radiance samples for one pixel and turns them into
+// an rgb color, balanced against white, the value of white_point().
+pub struct SpectralFilm {
+  x: f64,
+  y: f64,
+  z: f64,
+  count: usize,
+  white: Color,
+}
+
+impl SpectralFilm {
+  pub fn new(white: Color) -> SpectralFilm {
+    return SpectralFilm {
+      x: 0.,
+      y: 0.,
+      z: 0.,
+      count: 0,
+      white: white,
+    };
+  }
+
+  // radiance is the value traced for a ray carrying the wavelength; all of
+  // its channels hold the same number.
+  pub fn add(&mut self, wavelength: f64, radiance: Color) {
+    let v = (radiance.r + radiance.g + radiance.b) / 3.;
+    let (x, y, z) = cie_xyz(wavelength);
+    self.x += v * x;
+    self.y += v * y;
+    self.z += v * z;
+    self.count += 1;
+  }
+
+  pub fn to_rgb(&self) -> Color {
+    if self.count == 0 {
+      return Color::new(0., 0., 0.);
+    }
+    // both the samples and the white point average over the same range
+    let n = self.count as f64;
+    let rgb = xyz_to_rgb(self.x / n, self.y / n, self.z / n);
+    let white = self.white;
+    return Color::new(rgb.r / white.r, rgb.g / white.g, rgb.b / white.b);
+  }
+}
+
+#[test]
+fn upsampling_keeps_white_flat() {
+  for w in vec![400., 480., 495., 550., 585., 700.] {
+    assert_eq!((rgb_at(Color::new(1., 1., 1.), w) - 1.).abs() < 1e-10, true);
+  }
+}
+
+#[test]
+fn upsampling_reads_back_each_channel_in_its_band() {
+  let c = Color::new(0.9, 0.5, 0.1);
+
+  assert_eq!(rgb_at(c, 650.), 0.9);
+  assert_eq!(rgb_at(c, 550.), 0.5);
+  assert_eq!(rgb_at(c, 450.), 0.1);
+  assert_eq!(Color::equals(project(c, None), c), true);
+  assert_eq!(Color::equals(project(c, Some(650.)), Color::new(0.9, 0.9, 0.9)), true);
+}
+
+#[test]
+fn the_color_matching_functions_peak_where_expected() {
+  let (_, y555, _) = cie_xyz(555.);
+  let (_, y450, z450) = cie_xyz(450.);
+  let (x600, _, _) = cie_xyz(600.);
+
+  assert_eq!((y555 - 1.).abs() < 0.02, true);
+  assert_eq!(z450 > 1.5, true);
+  assert_eq!(y450 < 0.1, true);
+  assert_eq!(x600 > 1., true);
+}
+
+#[test]
+fn a_flat_spectrum_develops_to_white() {
+  let mut film = SpectralFilm::new(white_point());
+  let n = 350;
+  for i in 0..n {
+    film.add(sample_wavelength(i, n, 0.5), Color::new(0.5, 0.5, 0.5));
+  }
+
+  assert_eq!(Color::equals(film.to_rgb(), Color::new(0.5, 0.5, 0.5)), true);
+}
+
+#[test]
+fn a_red_spectrum_develops_to_a_red_color() {
+  let red = Color::new(1., 0., 0.);
+  let mut film = SpectralFilm::new(white_point());
+  let n = 350;
+  for i in 0..n {
+    let w = sample_wavelength(i, n, 0.5);
+    film.add(w, project(red, Some(w)));
+  }
+  let c = film.to_rgb();
+
+  assert_eq!(c.r > 0.8, true);
+  assert_eq!(c.g < 0.2, true);
+  assert_eq!(c.b < 0.1, true);
+}
+
+#[test]
+fn single_wavelengths_average_to_white_and_fill_in_the_hues() {
+  let white = white_point();
+  let n = 350;
+  let mut sum = Color::new(0., 0., 0.);
+  for i in 0..n {
+    sum = Color::add(sum, balanced_rgb(sample_wavelength(i, n, 0.5), white));
+  }
+
+  assert_eq!(Color::equals(Color::div(sum, n as f64), Color::new(1., 1., 1.)), true);
+  // yellow light, between the red and green channels
+  let yellow = balanced_rgb(580., white);
+  assert_eq!(yellow.r > yellow.b, true);
+  assert_eq!(yellow.g > yellow.b, true);
+}
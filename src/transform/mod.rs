@@ -1,32 +1,32 @@
-use crate::matrix::Matrix;
+use crate::matrix::matrix4::Matrix4;
 use crate::vectors::Tuple;
 use crate::vectors::{cross, point, vector};
 use std::f64;
 
 pub struct Transform {
-  pub transform: Matrix,
+  pub transform: Matrix4,
 }
 
 impl Transform {
   pub fn new() -> Transform {
     Transform {
-      transform: Matrix::identity(4),
+      transform: Matrix4::identity(),
     }
   }
 
   pub fn translate(&self, x: f64, y: f64, z: f64) -> Transform {
     let data = vec![1., 0., 0., x, 0., 1., 0., y, 0., 0., 1., z, 0., 0., 0., 1.];
-    let translate = Matrix::new(4, 4, &data);
+    let translate = Matrix4::new(&data);
     return Transform {
-      transform: Matrix::mult(&self.transform, &translate),
+      transform: Matrix4::mult(&self.transform, &translate),
     };
   }
 
   pub fn scale(&self, x: f64, y: f64, z: f64) -> Transform {
     let data = vec![x, 0., 0., 0., 0., y, 0., 0., 0., 0., z, 0., 0., 0., 0., 1.];
-    let scale = Matrix::new(4, 4, &data);
+    let scale = Matrix4::new(&data);
     return Transform {
-      transform: Matrix::mult(&self.transform, &scale),
+      transform: Matrix4::mult(&self.transform, &scale),
     };
   }
 
@@ -49,9 +49,9 @@ impl Transform {
       0.,
       1.,
     ];
-    let rotate_x = Matrix::new(4, 4, &data);
+    let rotate_x = Matrix4::new(&data);
     return Transform {
-      transform: Matrix::mult(&self.transform, &rotate_x),
+      transform: Matrix4::mult(&self.transform, &rotate_x),
     };
   }
 
@@ -74,9 +74,9 @@ impl Transform {
       0.,
       1.,
     ];
-    let rotate_y = Matrix::new(4, 4, &data);
+    let rotate_y = Matrix4::new(&data);
     return Transform {
-      transform: Matrix::mult(&self.transform, &rotate_y),
+      transform: Matrix4::mult(&self.transform, &rotate_y),
     };
   }
 
@@ -99,9 +99,9 @@ impl Transform {
       0.,
       1.,
     ];
-    let rotate_z = Matrix::new(4, 4, &data);
+    let rotate_z = Matrix4::new(&data);
     return Transform {
-      transform: Matrix::mult(&self.transform, &rotate_z),
+      transform: Matrix4::mult(&self.transform, &rotate_z),
     };
   }
 
@@ -109,13 +109,13 @@ impl Transform {
     let data = vec![
       1.0, xy, xz, 0., yx, 1., yz, 0., zx, zy, 1., 0., 0., 0., 0., 1.,
     ];
-    let rotate_z = Matrix::new(4, 4, &data);
+    let rotate_z = Matrix4::new(&data);
     return Transform {
-      transform: Matrix::mult(&self.transform, &rotate_z),
+      transform: Matrix4::mult(&self.transform, &rotate_z),
     };
   }
 
-  pub fn view_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix {
+  pub fn view_transform(from: Tuple, to: Tuple, up: Tuple) -> Matrix4 {
     let forward = to.sub(from).norm();
     let left = cross(forward, up.norm());
     let true_up = cross(left, forward);
@@ -123,8 +123,8 @@ impl Transform {
       left.x, left.y, left.z, 0., true_up.x, true_up.y, true_up.z, 0., -forward.x, -forward.y,
       -forward.z, 0., 0., 0., 0., 1.,
     ];
-    let orientation = Matrix::new(4, 4, &orientation_vec);
-    return Matrix::mult(
+    let orientation = Matrix4::new(&orientation_vec);
+    return Matrix4::mult(
       &orientation,
       &Transform::new()
         .translate(-from.x, -from.y, -from.z)
@@ -137,7 +137,7 @@ impl Transform {
 fn it_translates() {
   let p = point(-3., 4., 5.);
   let transform = Transform::new().translate(5., -3., 2.).transform;
-  let pt = Matrix::mult_4x4_by_1d(&transform, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&transform, &p);
   let expected = point(2., 1., 7.);
 
   assert_eq!(pt.equals(expected), true);
@@ -147,8 +147,8 @@ fn it_translates() {
 fn it_translates_inverse() {
   let p = point(-3., 4., 5.);
   let transform = Transform::new().translate(5., -3., 2.).transform;
-  let inv_transform = Matrix::inverse(&transform);
-  let pt = Matrix::mult_4x4_by_1d(&inv_transform, &p);
+  let inv_transform = Matrix4::inverse(&transform);
+  let pt = Matrix4::mult_4x4_by_1d(&inv_transform, &p);
   let expected = point(-8., 7., 3.);
 
   assert_eq!(pt.equals(expected), true);
@@ -158,7 +158,7 @@ fn it_translates_inverse() {
 fn it_translates_inverse_vector() {
   let v = vector(-3., 4., 5.);
   let transform = Transform::new().translate(5., -3., 2.).transform;
-  let vt = Matrix::mult_4x4_by_1d(&transform, &v);
+  let vt = Matrix4::mult_4x4_by_1d(&transform, &v);
   let expected = vector(-3., 4., 5.);
 
   assert_eq!(vt.equals(expected), true);
@@ -168,7 +168,7 @@ fn it_translates_inverse_vector() {
 fn it_scales_point() {
   let transform = Transform::new().scale(2., 3., 4.).transform;
   let p = point(-4., 6., 8.);
-  let pt = Matrix::mult_4x4_by_1d(&transform, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&transform, &p);
   let expected = point(-8., 18., 32.);
 
   assert_eq!(pt.equals(expected), true);
@@ -178,7 +178,7 @@ fn it_scales_point() {
 fn it_scales_vector() {
   let transform = Transform::new().scale(2., 3., 4.).transform;
   let v = vector(-4., 6., 8.);
-  let vt = Matrix::mult_4x4_by_1d(&transform, &v);
+  let vt = Matrix4::mult_4x4_by_1d(&transform, &v);
   let expected = vector(-8., 18., 32.);
 
   assert_eq!(vt.equals(expected), true);
@@ -187,9 +187,9 @@ fn it_scales_vector() {
 #[test]
 fn it_scales_inverse_vector() {
   let transform = Transform::new().scale(2., 3., 4.).transform;
-  let inv_transform = Matrix::inverse(&transform);
+  let inv_transform = Matrix4::inverse(&transform);
   let v = vector(-4., 6., 8.);
-  let vt = Matrix::mult_4x4_by_1d(&inv_transform, &v);
+  let vt = Matrix4::mult_4x4_by_1d(&inv_transform, &v);
   let expected = vector(-2., 2., 2.);
 
   assert_eq!(vt.equals(expected), true);
@@ -198,7 +198,7 @@ fn it_scales_inverse_vector() {
 fn it_scales_reflects() {
   let transform = Transform::new().scale(-1., 1., 1.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&transform, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&transform, &p);
   let expected = point(-2., 3., 4.);
 
   assert_eq!(pt.equals(expected), true);
@@ -209,11 +209,11 @@ fn it_rotates_a_point_around_x_axis() {
   let rotate_90 = Transform::new().rotate_x(f64::consts::PI / 4.).transform;
   let rotate_180 = Transform::new().rotate_x(f64::consts::PI / 2.).transform;
   let p = point(0., 1., 0.);
-  let pt1 = Matrix::mult_4x4_by_1d(&rotate_90, &p);
+  let pt1 = Matrix4::mult_4x4_by_1d(&rotate_90, &p);
   let ex1 = point(0., 2f64.sqrt() / 2., 2f64.sqrt() / 2.);
   assert_eq!(pt1.equals(ex1), true);
 
-  let pt2 = Matrix::mult_4x4_by_1d(&rotate_180, &p);
+  let pt2 = Matrix4::mult_4x4_by_1d(&rotate_180, &p);
   let ex2 = point(0., 0., 1.);
   assert_eq!(pt2.equals(ex2), true);
 }
@@ -221,7 +221,7 @@ fn it_rotates_a_point_around_x_axis() {
 fn it_rotates_a_point_around_x_axis_opposite_direction() {
   let rotate_90 = Transform::new().rotate_x(-f64::consts::PI / 4.).transform;
   let p = point(0., 1., 0.);
-  let pt1 = Matrix::mult_4x4_by_1d(&rotate_90, &p);
+  let pt1 = Matrix4::mult_4x4_by_1d(&rotate_90, &p);
   let ex1 = point(0., 2f64.sqrt() / 2., -2f64.sqrt() / 2.);
   assert_eq!(pt1.equals(ex1), true);
 }
@@ -231,11 +231,11 @@ fn it_rotates_a_point_around_y_axis() {
   let rotate_90 = Transform::new().rotate_y(f64::consts::PI / 4.).transform;
   let rotate_180 = Transform::new().rotate_y(f64::consts::PI / 2.).transform;
   let p = point(0., 0., 1.);
-  let pt = Matrix::mult_4x4_by_1d(&rotate_90, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&rotate_90, &p);
   let ex = point(2f64.sqrt() / 2., 0., 2f64.sqrt() / 2.);
   assert_eq!(pt.equals(ex), true);
 
-  let pt2 = Matrix::mult_4x4_by_1d(&rotate_180, &p);
+  let pt2 = Matrix4::mult_4x4_by_1d(&rotate_180, &p);
   let ex2 = point(1., 0., 0.);
   assert_eq!(pt2.equals(ex2), true);
 }
@@ -245,11 +245,11 @@ fn it_rotates_a_point_around_z_axis() {
   let rotate_90 = Transform::new().rotate_z(f64::consts::PI / 4.).transform;
   let rotate_180 = Transform::new().rotate_z(f64::consts::PI / 2.).transform;
   let p = point(0., 1., 0.);
-  let pt = Matrix::mult_4x4_by_1d(&rotate_90, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&rotate_90, &p);
   let ex = point(-2f64.sqrt() / 2., 2f64.sqrt() / 2., 0.);
   assert_eq!(pt.equals(ex), true);
 
-  let pt2 = Matrix::mult_4x4_by_1d(&rotate_180, &p);
+  let pt2 = Matrix4::mult_4x4_by_1d(&rotate_180, &p);
   let ex2 = point(-1., 0., 0.);
   assert_eq!(pt2.equals(ex2), true);
 }
@@ -258,7 +258,7 @@ fn it_rotates_a_point_around_z_axis() {
 fn it_shears_along_x_in_proportion_to_y() {
   let shear = Transform::new().shear(1., 0., 0., 0., 0., 0.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&shear, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&shear, &p);
   let ex = point(5., 3., 4.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -267,7 +267,7 @@ fn it_shears_along_x_in_proportion_to_y() {
 fn it_shears_along_x_in_proportion_to_z() {
   let shear = Transform::new().shear(0., 1., 0., 0., 0., 0.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&shear, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&shear, &p);
   let ex = point(6., 3., 4.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -276,7 +276,7 @@ fn it_shears_along_x_in_proportion_to_z() {
 fn it_shears_along_y_in_proportion_to_x() {
   let shear = Transform::new().shear(0., 0., 1., 0., 0., 0.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&shear, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&shear, &p);
   let ex = point(2., 5., 4.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -285,7 +285,7 @@ fn it_shears_along_y_in_proportion_to_x() {
 fn it_shears_along_y_in_proportion_to_z() {
   let shear = Transform::new().shear(0., 0., 0., 1., 0., 0.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&shear, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&shear, &p);
   let ex = point(2., 7., 4.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -294,7 +294,7 @@ fn it_shears_along_y_in_proportion_to_z() {
 fn it_shears_along_z_in_proportion_to_x() {
   let shear = Transform::new().shear(0., 0., 0., 0., 1., 0.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&shear, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&shear, &p);
   let ex = point(2., 3., 6.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -303,7 +303,7 @@ fn it_shears_along_z_in_proportion_to_x() {
 fn it_shears_along_z_in_proportion_to_y() {
   let shear = Transform::new().shear(0., 0., 0., 0., 0., 1.).transform;
   let p = point(2., 3., 4.);
-  let pt = Matrix::mult_4x4_by_1d(&shear, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&shear, &p);
   let ex = point(2., 3., 7.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -312,18 +312,18 @@ fn it_shears_along_z_in_proportion_to_y() {
 fn it_individual_transforms_work_sequentially() {
   let p = point(1., 0., 1.);
 
-  let p1 = Matrix::mult_4x4_by_1d(
+  let p1 = Matrix4::mult_4x4_by_1d(
     &Transform::new().rotate_x(f64::consts::PI / 2.).transform,
     &p,
   );
   let ex1 = point(1., -1., 0.);
   assert_eq!(p1.equals(ex1), true);
 
-  let p2 = Matrix::mult_4x4_by_1d(&Transform::new().scale(5., 5., 5.).transform, &p1);
+  let p2 = Matrix4::mult_4x4_by_1d(&Transform::new().scale(5., 5., 5.).transform, &p1);
   let ex2 = point(5., -5., 0.);
   assert_eq!(p2.equals(ex2), true);
 
-  let p3 = Matrix::mult_4x4_by_1d(&Transform::new().translate(10., 5., 7.).transform, &p2);
+  let p3 = Matrix4::mult_4x4_by_1d(&Transform::new().translate(10., 5., 7.).transform, &p2);
   let ex3 = point(15., 0., 7.);
   assert_eq!(p3.equals(ex3), true);
 }
@@ -336,7 +336,7 @@ fn it_chains_transformations_in_reverse_order() {
     .scale(5., 5., 5.)
     .rotate_x(f64::consts::PI / 2.)
     .transform;
-  let pt = Matrix::mult_4x4_by_1d(&transforms, &p);
+  let pt = Matrix4::mult_4x4_by_1d(&transforms, &p);
   let ex = point(15., 0., 7.);
   assert_eq!(pt.equals(ex), true);
 }
@@ -347,8 +347,8 @@ fn the_transformation_matrix_for_the_default_orientation() {
   let to = point(0., 0., -1.);
   let up = vector(0., 1., 0.);
   let t = Transform::view_transform(from, to, up);
-  let i = Matrix::identity(4);
-  assert_eq!(Matrix::equals(&t, &i), true);
+  let i = Matrix4::identity();
+  assert_eq!(Matrix4::equals(&t, &i), true);
 }
 
 #[test]
@@ -358,7 +358,7 @@ fn a_view_transformation_matrix_looking_in_positive_z_direction() {
   let up = vector(0., 1., 0.);
   let t = Transform::view_transform(from, to, up);
   let expected = Transform::new().scale(-1., 1., -1.).transform;
-  assert_eq!(Matrix::equals(&t, &expected), true);
+  assert_eq!(Matrix4::equals(&t, &expected), true);
 }
 
 #[test]
@@ -369,7 +369,7 @@ fn the_view_transformation_matrix_moves_the_world() {
   let t = Transform::view_transform(from, to, up);
 
   let expected = Transform::new().translate(0., 0., -8.).transform;
-  assert_eq!(Matrix::approx_equals(&t, &expected), true);
+  assert_eq!(Matrix4::approx_equals(&t, &expected), true);
 }
 
 #[test]
@@ -382,6 +382,6 @@ fn an_arbitrary_view_transformation() {
     -0.50709, 0.50709, 0.67612, -2.36643, 0.76772, 0.60609, 0.12122, -2.82843, -0.35857, 0.59761,
     -0.71714, 0.00000, 0.00000, 0.00000, 0.00000, 1.00000,
   ];
-  let expected = Matrix::new(4, 4, &expected_data);
-  assert_eq!(Matrix::approx_equals(&t, &expected), true);
+  let expected = Matrix4::new(&expected_data);
+  assert_eq!(Matrix4::approx_equals(&t, &expected), true);
 }
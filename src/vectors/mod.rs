@@ -1,4 +1,12 @@
+use crate::matrix::simd;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+pub mod typed;
+
+// repr(C) keeps x, y, z and w adjacent and in order, so the simd kernels
+// can load a tuple as two pairs.
 #[derive(Copy, Clone)]
+#[repr(C)]
 pub struct Tuple {
   pub x: f64,
   pub y: f64,
@@ -58,6 +66,48 @@ impl Tuple {
   }
 }
 
+impl Add for Tuple {
+  type Output = Tuple;
+  fn add(self, p: Tuple) -> Tuple {
+    return Tuple::new(self.x + p.x, self.y + p.y, self.z + p.z, self.w + p.w);
+  }
+}
+
+impl Sub for Tuple {
+  type Output = Tuple;
+  fn sub(self, p: Tuple) -> Tuple {
+    return Tuple::new(self.x - p.x, self.y - p.y, self.z - p.z, self.w - p.w);
+  }
+}
+
+impl Neg for Tuple {
+  type Output = Tuple;
+  fn neg(self) -> Tuple {
+    return self.negate();
+  }
+}
+
+impl Mul<f64> for Tuple {
+  type Output = Tuple;
+  fn mul(self, a: f64) -> Tuple {
+    return self.mult(a);
+  }
+}
+
+impl Mul<Tuple> for f64 {
+  type Output = Tuple;
+  fn mul(self, t: Tuple) -> Tuple {
+    return t.mult(self);
+  }
+}
+
+impl Div<f64> for Tuple {
+  type Output = Tuple;
+  fn div(self, a: f64) -> Tuple {
+    return Tuple::new(self.x / a, self.y / a, self.z / a, self.w / a);
+  }
+}
+
 pub fn point(x: f64, y: f64, z: f64) -> Tuple {
   return Tuple::new(x, y, z, 1.0);
 }
@@ -67,7 +117,7 @@ pub fn vector(x: f64, y: f64, z: f64) -> Tuple {
 }
 
 pub fn dot(a: Tuple, b: Tuple) -> f64 {
-  return a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w;
+  return simd::dot(&a, &b);
 }
 
 pub fn cross(a: Tuple, b: Tuple) -> Tuple {
@@ -82,6 +132,19 @@ pub fn reflect(incidence: Tuple, norm: Tuple) -> Tuple {
   return incidence.sub(norm.mult(2. * dot(incidence, norm)));
 }
 
+// Bends the ray arriving opposite eyev through a surface with normal norm,
+// where n_ratio is n1 / n2. None means total internal reflection.
+pub fn refract(eyev: Tuple, norm: Tuple, n_ratio: f64) -> Option<Tuple> {
+  let cos_i = dot(eyev, norm);
+  let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+  if sin2_t > 1.0 {
+    return None;
+  }
+
+  let cos_t = (1.0 - sin2_t).sqrt();
+  return Some(norm.mult(n_ratio * cos_i - cos_t).sub(eyev.mult(n_ratio)));
+}
+
 #[test]
 fn it_creates_a_tuple_where_w_0_is_a_vector() {
   let tuple = Tuple::new(4.3, -4.2, 3.1, 0.0);
@@ -229,3 +292,32 @@ fn reflect_a_45_deg_vector_rotated() {
   let n = vector(2.0f64.sqrt() / 2., 2.0f64.sqrt() / 2., 0.);
   assert_eq!(reflect(v, n).equals(vector(1., 0., 0.)), true);
 }
+
+#[test]
+fn refracting_head_on_keeps_the_direction() {
+  let eyev = vector(0., 0., -1.);
+  let n = vector(0., 0., -1.);
+
+  assert_eq!(refract(eyev, n, 1. / 1.5).unwrap().equals(vector(0., 0., 1.)), true);
+}
+
+#[test]
+fn refracting_past_the_critical_angle_fails() {
+  let half_root2 = 2.0f64.sqrt() / 2.;
+  let eyev = vector(0., half_root2, -half_root2);
+  let n = vector(0., 0., -1.);
+
+  assert_eq!(refract(eyev, n, 1.5).is_none(), true);
+}
+
+#[test]
+fn tuples_work_with_operators() {
+  let p = point(3.0, 2.0, 1.0);
+  let v = vector(5.0, 6.0, 7.0);
+
+  assert_eq!((p + v).equals(p.add(v)), true);
+  assert_eq!((p - v).equals(point(-2.0, -4.0, -6.0)), true);
+  assert_eq!((-v).equals(v.negate()), true);
+  assert_eq!((v * 2.).equals(2. * v), true);
+  assert_eq!((v / 2.).equals(vector(2.5, 3.0, 3.5)), true);
+}
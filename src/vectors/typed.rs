@@ -0,0 +1,322 @@
+use crate::matrix::matrix4::{CachedTransform, Matrix4};
+use crate::vectors::{point, vector, Tuple};
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Points, directions and surface normals as separate types, so that the
+// w component of a Tuple no longer has to be kept right by hand. Only the
+// operations that make sense are defined: a point minus a point is a
+// vector, a point plus a vector is a point, and adding two points does not
+// compile. A matrix moves points and vectors, but a normal can only be
+// moved by a CachedTransform, which uses the inverse transpose.
+//
+// Each converts to and from Tuple for the code that still works in tuples.
+// Converting from a tuple gives None when its w is for the other kind, so a
+// vector handed over as a point (or the other way round) is caught where it
+// crosses.
+
+#[derive(Copy, Clone, Debug)]
+pub struct Point3 {
+  pub x: f64,
+  pub y: f64,
+  pub z: f64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Vector3 {
+  pub x: f64,
+  pub y: f64,
+  pub z: f64,
+}
+
+// Always of unit length.
+#[derive(Copy, Clone, Debug)]
+pub struct Normal3 {
+  x: f64,
+  y: f64,
+  z: f64,
+}
+
+impl Point3 {
+  pub fn new(x: f64, y: f64, z: f64) -> Point3 {
+    return Point3 { x: x, y: y, z: z };
+  }
+
+  pub fn from_tuple(t: Tuple) -> Option<Point3> {
+    if (t.w - 1.).abs() > 1e-9 {
+      return None;
+    }
+    return Some(Point3::new(t.x, t.y, t.z));
+  }
+
+  pub fn to_tuple(&self) -> Tuple {
+    return point(self.x, self.y, self.z);
+  }
+}
+
+impl Vector3 {
+  pub fn new(x: f64, y: f64, z: f64) -> Vector3 {
+    return Vector3 { x: x, y: y, z: z };
+  }
+
+  pub fn from_tuple(t: Tuple) -> Option<Vector3> {
+    if t.w.abs() > 1e-9 {
+      return None;
+    }
+    return Some(Vector3::new(t.x, t.y, t.z));
+  }
+
+  pub fn to_tuple(&self) -> Tuple {
+    return vector(self.x, self.y, self.z);
+  }
+
+  pub fn dot(&self, v: Vector3) -> f64 {
+    return self.x * v.x + self.y * v.y + self.z * v.z;
+  }
+
+  pub fn cross(&self, v: Vector3) -> Vector3 {
+    return Vector3::new(
+      self.y * v.z - self.z * v.y,
+      self.z * v.x - self.x * v.z,
+      self.x * v.y - self.y * v.x,
+    );
+  }
+
+  pub fn mag(&self) -> f64 {
+    return self.dot(*self).sqrt();
+  }
+
+  pub fn norm(&self) -> Vector3 {
+    return *self * (1. / self.mag());
+  }
+
+  // This vector mirrored about the surface with normal n.
+  pub fn reflect(&self, n: Normal3) -> Vector3 {
+    return *self - n.to_vector() * (2. * n.dot(*self));
+  }
+
+  // Bends the ray arriving opposite this eye vector through a surface with
+  // normal n, where n_ratio is n1 / n2. None means total internal
+  // reflection.
+  pub fn refract(&self, n: Normal3, n_ratio: f64) -> Option<Vector3> {
+    let cos_i = n.dot(*self);
+    let sin2_t = n_ratio * n_ratio * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+      return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    return Some(n.to_vector() * (n_ratio * cos_i - cos_t) - *self * n_ratio);
+  }
+}
+
+impl Normal3 {
+  // Any direction will do; it is scaled to unit length.
+  pub fn new(x: f64, y: f64, z: f64) -> Normal3 {
+    let m = (x * x + y * y + z * z).sqrt();
+    return Normal3 {
+      x: x / m,
+      y: y / m,
+      z: z / m,
+    };
+  }
+
+  pub fn from_tuple(t: Tuple) -> Option<Normal3> {
+    return Vector3::from_tuple(t).map(Normal3::from_vector);
+  }
+
+  pub fn from_vector(v: Vector3) -> Normal3 {
+    return Normal3::new(v.x, v.y, v.z);
+  }
+
+  pub fn to_tuple(&self) -> Tuple {
+    return vector(self.x, self.y, self.z);
+  }
+
+  pub fn to_vector(&self) -> Vector3 {
+    return Vector3::new(self.x, self.y, self.z);
+  }
+
+  pub fn dot(&self, v: Vector3) -> f64 {
+    return self.x * v.x + self.y * v.y + self.z * v.z;
+  }
+}
+
+impl Sub for Point3 {
+  type Output = Vector3;
+  fn sub(self, p: Point3) -> Vector3 {
+    return Vector3::new(self.x - p.x, self.y - p.y, self.z - p.z);
+  }
+}
+
+impl Add<Vector3> for Point3 {
+  type Output = Point3;
+  fn add(self, v: Vector3) -> Point3 {
+    return Point3::new(self.x + v.x, self.y + v.y, self.z + v.z);
+  }
+}
+
+impl Sub<Vector3> for Point3 {
+  type Output = Point3;
+  fn sub(self, v: Vector3) -> Point3 {
+    return Point3::new(self.x - v.x, self.y - v.y, self.z - v.z);
+  }
+}
+
+impl Add for Vector3 {
+  type Output = Vector3;
+  fn add(self, v: Vector3) -> Vector3 {
+    return Vector3::new(self.x + v.x, self.y + v.y, self.z + v.z);
+  }
+}
+
+impl Sub for Vector3 {
+  type Output = Vector3;
+  fn sub(self, v: Vector3) -> Vector3 {
+    return Vector3::new(self.x - v.x, self.y - v.y, self.z - v.z);
+  }
+}
+
+impl Mul<f64> for Vector3 {
+  type Output = Vector3;
+  fn mul(self, a: f64) -> Vector3 {
+    return Vector3::new(self.x * a, self.y * a, self.z * a);
+  }
+}
+
+impl Neg for Vector3 {
+  type Output = Vector3;
+  fn neg(self) -> Vector3 {
+    return Vector3::new(-self.x, -self.y, -self.z);
+  }
+}
+
+impl Neg for Normal3 {
+  type Output = Normal3;
+  fn neg(self) -> Normal3 {
+    return Normal3 {
+      x: -self.x,
+      y: -self.y,
+      z: -self.z,
+    };
+  }
+}
+
+impl From<Point3> for Tuple {
+  fn from(p: Point3) -> Tuple {
+    return p.to_tuple();
+  }
+}
+
+impl From<Vector3> for Tuple {
+  fn from(v: Vector3) -> Tuple {
+    return v.to_tuple();
+  }
+}
+
+impl From<Normal3> for Tuple {
+  fn from(n: Normal3) -> Tuple {
+    return n.to_tuple();
+  }
+}
+
+// Transforms are affine, so points keep w = 1 and vectors w = 0.
+impl Mul<Point3> for Matrix4 {
+  type Output = Point3;
+  fn mul(self, p: Point3) -> Point3 {
+    let t = self * p.to_tuple();
+    return Point3::new(t.x, t.y, t.z);
+  }
+}
+
+// Only the upper 3x3 applies; translation leaves directions alone.
+impl Mul<Vector3> for Matrix4 {
+  type Output = Vector3;
+  fn mul(self, v: Vector3) -> Vector3 {
+    let t = self * v.to_tuple();
+    return Vector3::new(t.x, t.y, t.z);
+  }
+}
+
+impl CachedTransform {
+  // From object space out to world space.
+  pub fn apply_point(&self, p: Point3) -> Point3 {
+    return self.transform * p;
+  }
+
+  pub fn apply_vector(&self, v: Vector3) -> Vector3 {
+    return self.transform * v;
+  }
+
+  // Normals go through the inverse transpose, which keeps them
+  // perpendicular to the surface under non uniform scaling. Its bottom row
+  // carries the translation into w, which a normal has no use for.
+  pub fn apply_normal(&self, n: Normal3) -> Normal3 {
+    let t = self.inverse_transpose * n.to_tuple();
+    return Normal3::new(t.x, t.y, t.z);
+  }
+
+  // From world space into object space.
+  pub fn invert_point(&self, p: Point3) -> Point3 {
+    return self.inverse * p;
+  }
+
+  pub fn invert_vector(&self, v: Vector3) -> Vector3 {
+    return self.inverse * v;
+  }
+}
+
+#[test]
+fn points_and_vectors_combine_as_expected() {
+  let p = Point3::new(3., 2., 1.);
+  let q = Point3::new(5., 6., 7.);
+  let v = q - p;
+
+  assert_eq!(v.to_tuple().equals(vector(2., 4., 6.)), true);
+  assert_eq!((p + v).to_tuple().equals(q.to_tuple()), true);
+  assert_eq!((q - v).to_tuple().equals(p.to_tuple()), true);
+  assert_eq!(Tuple::from(v).is_a_vector(), true);
+  assert_eq!(Tuple::from(p).is_a_point(), true);
+}
+
+#[test]
+fn translation_moves_points_but_not_vectors() {
+  let t = CachedTransform::new(
+    crate::transform::Transform::new()
+      .translate(5., -3., 2.)
+      .transform,
+  );
+  let p = t.apply_point(Point3::new(-3., 4., 5.));
+  let v = t.apply_vector(Vector3::new(-3., 4., 5.));
+
+  assert_eq!(p.to_tuple().equals(point(2., 1., 7.)), true);
+  assert_eq!(v.to_tuple().equals(vector(-3., 4., 5.)), true);
+  assert_eq!(
+    t.invert_point(p).to_tuple().equals(point(-3., 4., 5.)),
+    true
+  );
+}
+
+#[test]
+fn normals_stay_perpendicular_under_scaling() {
+  let t = CachedTransform::new(
+    crate::transform::Transform::new()
+      .scale(1., 0.5, 1.)
+      .transform,
+  );
+  // the surface of a unit sphere, squashed in y
+  let s = (0.5f64).sqrt();
+  let tangent = t.apply_vector(Vector3::new(s, -s, 0.));
+  let n = t.apply_normal(Normal3::new(s, s, 0.));
+
+  assert_eq!(n.dot(tangent).abs() < 1e-10, true);
+  assert_eq!((n.to_vector().mag() - 1.).abs() < 1e-10, true);
+  assert_eq!(n.to_tuple().equals(vector(1., 2., 0.).norm()), true);
+}
+
+#[test]
+fn a_vector_cannot_be_taken_for_a_point() {
+  assert_eq!(Point3::from_tuple(vector(1., 2., 3.)).is_none(), true);
+  assert_eq!(Vector3::from_tuple(point(1., 2., 3.)).is_none(), true);
+  assert_eq!(Normal3::from_tuple(point(1., 2., 3.)).is_none(), true);
+  assert_eq!(Point3::from_tuple(point(1., 2., 3.)).is_some(), true);
+}
@@ -0,0 +1,142 @@
+use crate::colors::Color;
+use crate::intersections::{prepare_computations, Computations, Intersection, Intersections};
+use crate::ray::Ray;
+use crate::sampling::{cosine_sample_hemisphere, random};
+use crate::shape::{Shape, ShapeType};
+use crate::transform::Transform;
+use crate::vectors::typed::{Point3, Vector3};
+use crate::world::{Integrator, World};
+
+// How many hemisphere rays to shoot per shading point, and how far away a
+// blocker may be before it stops counting as occluding.
+#[derive(Copy, Clone)]
+pub struct AmbientOcclusion {
+  pub samples: usize,
+  pub distance: f64,
+}
+
+impl AmbientOcclusion {
+  pub fn new(samples: usize, distance: f64) -> AmbientOcclusion {
+    return AmbientOcclusion {
+      samples: samples,
+      distance: distance,
+    };
+  }
+}
+
+impl World {
+  // The cosine weighted fraction of the hemisphere above the hit that is
+  // blocked within the occlusion distance: 0 is fully open, 1 fully occluded.
+  pub fn ambient_occlusion(&self, comps: &Computations, settings: AmbientOcclusion) -> f64 {
+    if settings.samples == 0 {
+      return 0.;
+    }
+
+    let mut blocked = 0;
+
+    for _i in 0..settings.samples {
+      let direction = cosine_sample_hemisphere(comps.normalv, random(), random());
+      let r = Ray::new(comps.over_point, direction);
+      if self.is_occluded(r, settings.distance) {
+        blocked += 1;
+      }
+    }
+
+    return blocked as f64 / settings.samples as f64;
+  }
+
+  // A clay render: every surface is white, darkened only by its occlusion.
+  pub fn occlusion_color_at(&self, r: Ray) -> Color {
+    let xs = self.intersect_world(r);
+    let hit = xs.hit();
+    if hit.intersections.len() == 0 {
+      return Color::new(1., 1., 1.);
+    }
+
+    let comps = prepare_computations(hit.intersections[0].clone(), r, xs);
+    let settings = match self.ao_settings {
+      Some(settings) => settings,
+      None => AmbientOcclusion::new(16, 1.0),
+    };
+    let open = 1. - self.ambient_occlusion(&comps, settings);
+    return Color::new(open, open, open);
+  }
+}
+
+#[cfg(test)]
+fn world_under_a_ceiling() -> (World, Shape) {
+  let mut world = World::new();
+  let floor = Shape::new(ShapeType::Plane);
+  world.add_object(floor.clone());
+
+  let mut ceiling = Shape::new(ShapeType::Plane);
+  ceiling.set_transform(Transform::new().translate(0., 0.5, 0.).transform);
+  world.add_object(ceiling);
+
+  return (world, floor);
+}
+
+#[test]
+fn an_isolated_sphere_is_not_occluded() {
+  let mut world = World::new();
+  let s = Shape::new(ShapeType::Sphere);
+  world.add_object(s.clone());
+
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let i = Intersection::new(4., &s);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+
+  assert_eq!(world.ambient_occlusion(&comps, AmbientOcclusion::new(32, 10.)), 0.);
+}
+
+#[test]
+fn a_floor_under_a_low_ceiling_is_occluded() {
+  let (world, floor) = world_under_a_ceiling();
+
+  let r = Ray::new(Point3::new(0., 0.25, -0.25), Vector3::new(0., -1., 1.).norm());
+  let i = Intersection::new(0.25 * 2.0f64.sqrt(), &floor);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+
+  let everything = AmbientOcclusion::new(32, std::f64::INFINITY);
+  assert_eq!(world.ambient_occlusion(&comps, everything), 1.);
+  // the ceiling is always at least 0.5 away
+  assert_eq!(world.ambient_occlusion(&comps, AmbientOcclusion::new(32, 0.25)), 0.);
+}
+
+#[test]
+fn the_occlusion_integrator_renders_clay() {
+  let (mut world, _floor) = world_under_a_ceiling();
+  world.set_integrator(Integrator::AmbientOcclusion);
+  world.set_ambient_occlusion(AmbientOcclusion::new(16, std::f64::INFINITY));
+
+  let r = Ray::new(Point3::new(0., 0.25, -0.25), Vector3::new(0., -1., 1.).norm());
+  let c = world.color_at(r, 5);
+  let sky = world.color_at(Ray::new(Point3::new(0., 1., 0.), Vector3::new(0., 1., 0.)), 5);
+
+  assert_eq!(Color::equals(c, Color::new(0., 0., 0.)), true);
+  assert_eq!(Color::equals(sky, Color::new(1., 1., 1.)), true);
+}
+
+#[test]
+fn occlusion_darkens_the_ambient_light_in_shade_hit() {
+  let (mut world, floor) = world_under_a_ceiling();
+  world.set_light(crate::light::PointLight::new(
+    Point3::new(0., 10., 0.),
+    Color::new(1., 1., 1.),
+  ));
+
+  let r = Ray::new(Point3::new(0., 0.25, -0.25), Vector3::new(0., -1., 1.).norm());
+  let i = Intersection::new(0.25 * 2.0f64.sqrt(), &floor);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+
+  // the ceiling shadows the light, leaving only the ambient term
+  let without = world.shade_hit(comps.clone(), 1);
+  world.set_ambient_occlusion(AmbientOcclusion::new(16, std::f64::INFINITY));
+  let with = world.shade_hit(comps, 1);
+
+  assert_eq!(Color::equals(without, Color::new(0.1, 0.1, 0.1)), true);
+  assert_eq!(Color::equals(with, Color::new(0., 0., 0.)), true);
+}
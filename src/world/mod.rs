@@ -1,16 +1,40 @@
 use crate::colors::Color;
+use crate::environment::EnvironmentMap;
 use crate::intersections::{
   prepare_computations, schlick, Computations, Intersection, Intersections,
 };
-use crate::light::{lighting, PointLight};
-use crate::material::Material;
-use crate::matrix::Matrix;
+use crate::light::{lighting_with_options, LightingOptions, PointLight};
+use crate::material::dispersion::Dispersion;
+use crate::material::{microfacet, Material, ShadingModel};
 use crate::pattern::{Pattern, PatternType};
 use crate::ray::Ray;
-use crate::shape::sphere::Sphere;
+use crate::sampling::random;
 use crate::shape::{Shape, ShapeType};
+use crate::spectrum::{project, sample_wavelength, white_point, SpectralFilm};
 use crate::transform::Transform;
-use crate::vectors::{dot, point, vector, Tuple};
+use crate::vectors::typed::{Point3, Vector3};
+use crate::vectors::{point, Tuple};
+use std::f64;
+
+mod ambient_occlusion;
+mod path_tracer;
+
+pub use self::ambient_occlusion::AmbientOcclusion;
+
+// sample points on emissive surfaces are lifted off them by this much so
+// their shadow rays do not hit the emitter itself
+const EMITTER_OFFSET: f64 = 1.0e-6;
+
+// blockers closer than this along an occlusion ray are taken to be the
+// surface the ray left from
+const OCCLUSION_EPSILON: f64 = 1.0e-10;
+
+// how many rays whitted_color_at has traced on this thread, so tests can
+// check how the recursion fans out
+#[cfg(test)]
+thread_local! {
+  static TRACED_RAYS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
 
 #[derive(Copy, Clone)]
 pub struct Proj {
@@ -43,10 +67,27 @@ pub fn tick(env: Env, proj: Proj) -> Proj {
   return Proj::new(proj.pos.add(proj.vel), proj.vel.add(env.grav).add(env.wind));
 }
 
+// How color_at turns a ray into a color: recursive Whitted-style ray
+// tracing, Monte Carlo path tracing with indirect diffuse light, or a clay
+// render showing only ambient occlusion.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Integrator {
+  Whitted,
+  PathTracer,
+  AmbientOcclusion,
+}
+
 #[derive(Clone)]
 pub struct World {
   light: Option<PointLight>,
   objects: Vec<Shape>,
+  environment: Option<EnvironmentMap>,
+  integrator: Integrator,
+  emissive_samples: usize,
+  ao_settings: Option<AmbientOcclusion>,
+  // how many wavelengths a white ray is split into by a dispersive material
+  dispersion_samples: usize,
+  white_point: Color,
 }
 
 impl World {
@@ -54,6 +95,12 @@ impl World {
     World {
       light: None,
       objects: vec![],
+      environment: None,
+      integrator: Integrator::Whitted,
+      emissive_samples: 16,
+      ao_settings: None,
+      dispersion_samples: 8,
+      white_point: white_point(),
     }
   }
 
@@ -65,6 +112,26 @@ impl World {
     self.objects.push(object);
   }
 
+  pub fn set_environment(&mut self, environment: EnvironmentMap) {
+    self.environment = Some(environment);
+  }
+
+  pub fn set_integrator(&mut self, integrator: Integrator) {
+    self.integrator = integrator;
+  }
+
+  pub fn set_emissive_samples(&mut self, samples: usize) {
+    self.emissive_samples = samples;
+  }
+
+  pub fn set_ambient_occlusion(&mut self, settings: AmbientOcclusion) {
+    self.ao_settings = Some(settings);
+  }
+
+  pub fn set_dispersion_samples(&mut self, samples: usize) {
+    self.dispersion_samples = samples;
+  }
+
   pub fn default_world() -> World {
     let mut s1 = Shape::new(ShapeType::Sphere);
     s1.material.color = Color::new(0.8, 1.0, 0.6);
@@ -77,10 +144,16 @@ impl World {
 
     return World {
       light: Some(PointLight::new(
-        point(-10., 10., -10.),
+        Point3::new(-10., 10., -10.),
         Color::new(1., 1., 1.),
       )),
       objects: vec![s1, s2],
+      environment: None,
+      integrator: Integrator::Whitted,
+      emissive_samples: 16,
+      ao_settings: None,
+      dispersion_samples: 8,
+      white_point: white_point(),
     };
   }
 
@@ -98,63 +171,99 @@ impl World {
 
     return World {
       light: Some(PointLight::new(
-        point(-10., 10., -10.),
+        Point3::new(-10., 10., -10.),
         Color::new(1., 1., 1.),
       )),
       objects: vec![s1, s2],
+      environment: None,
+      integrator: Integrator::Whitted,
+      emissive_samples: 16,
+      ao_settings: None,
+      dispersion_samples: 8,
+      white_point: white_point(),
     };
   }
 
   pub fn intersect_world(&self, r: Ray) -> Intersections {
-    let objects = self.objects.clone();
-
-    let mut _intersections: Vec<Intersection> = objects
-      .clone()
-      .iter()
-      .map(|o| {
-        return o.intersects(r).intersections;
-      })
-      .flatten()
-      .collect::<Vec<Intersection>>();
+    let mut _intersections: Vec<Intersection> = vec![];
+    for object in &self.objects {
+      object.intersect_into(r, &mut _intersections);
+    }
     _intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
 
     return Intersections::new(_intersections);
   }
 
   pub fn shade_hit(&self, comps: Computations, remaining: u32) -> Color {
-    let is_in_shadow = self.is_shadowed(comps.over_point);
-
-    let surface = lighting(
-      comps.object.material.clone(),
-      comps.object.clone(),
-      self.light.unwrap(),
-      comps.point,
-      comps.eyev,
-      comps.normalv,
-      is_in_shadow,
-    );
+    let mut surface = match self.light {
+      Some(light) => {
+        let light = light.at_wavelength(comps.wavelength);
+        let mut options = LightingOptions::new(self.is_shadowed(comps.over_point));
+        options.color = comps.filtered_color;
+        if let Some(settings) = self.ao_settings {
+          options.ambient_scale = 1. - self.ambient_occlusion(&comps, settings);
+        }
+        lighting_with_options(
+          &comps.object.material,
+          &comps.object,
+          light,
+          comps.point,
+          comps.eyev,
+          comps.normalv,
+          options,
+        )
+      }
+      None => Color::new(0., 0., 0.),
+    };
+    surface = Color::add(surface, self.environment_lighting(&comps));
+    surface = Color::add(surface, self.emissive_lighting(&comps));
+    surface = Color::add(surface, comps.object.material.emission);
 
-    let reflected = self.reflected_color(comps.clone(), remaining);
-    let refracted = self.refracted_color(comps.clone(), remaining);
+    let (reflect_weight, refract_weight) = self.indirect_weights(&comps);
 
-    if comps.object.material.reflectiveness > 0.0 && comps.object.material.transparency > 0.0 {
-      let reflectance = schlick(comps);
+    let mut reflected = Color::new(0., 0., 0.);
+    if reflect_weight.r > 0. || reflect_weight.g > 0. || reflect_weight.b > 0. {
+      reflected = Color::hadamard(self.trace_reflection(&comps, remaining), reflect_weight);
+    }
 
-      return Color::add(
-        Color::add(surface, Color::mult(reflected, reflectance)),
-        Color::mult(refracted, 1. - reflectance),
-      );
+    let mut refracted = Color::new(0., 0., 0.);
+    if refract_weight > 0. {
+      refracted = Color::mult(self.trace_refraction(&comps, remaining), refract_weight);
     }
 
-    return Color::add(Color::add(surface, reflected), refracted);
+    let color = Color::add(Color::add(surface, reflected), refracted);
+    return Color::hadamard(color, self.medium_transmittance(&comps));
+  }
+
+  // How much of the light leaving a hit survives the way back along the ray,
+  // through whatever the ray was travelling in (Beer-Lambert). Rays inside
+  // an absorbing object, such as thick colored glass, lose more light the
+  // further they travel before reaching the hit.
+  pub fn medium_transmittance(&self, comps: &Computations) -> Color {
+    return match &comps.n1_material {
+      Some(medium) if medium.is_absorbing() => medium.transmittance(comps.t),
+      _ => Color::new(1., 1., 1.),
+    };
   }
 
   pub fn color_at(&self, r: Ray, remaining: u32) -> Color {
+    return match self.integrator {
+      Integrator::Whitted => self.whitted_color_at(r, remaining),
+      Integrator::PathTracer => self.path_trace(r, remaining),
+      Integrator::AmbientOcclusion => self.occlusion_color_at(r),
+    };
+  }
+
+  fn whitted_color_at(&self, r: Ray, remaining: u32) -> Color {
+    #[cfg(test)]
+    TRACED_RAYS.with(|n| n.set(n.get() + 1));
+
     let xs = self.intersect_world(r);
     if xs.intersections.len() > 0 {
       let hit = xs.hit();
       if hit.intersections.len() > 0 {
-        let xs = Intersections::new(hit.intersections.clone());
+        // every intersection along the ray is needed to work out which
+        // objects contain the hit (refractive indices and absorption)
         for h in hit.intersections {
           if h.t >= 0. {
             let intersect = h.clone();
@@ -164,38 +273,125 @@ impl World {
         }
       }
     }
-    return Color::new(0., 0., 0.);
+
+    return self.background(r);
   }
 
-  pub fn refracted_color(&self, comps: Computations, remaining: u32) -> Color {
-    if remaining == 0 || comps.object.material.transparency == 0.0 {
+  // What a ray that hits nothing sees.
+  pub fn background(&self, r: Ray) -> Color {
+    return match &self.environment {
+      Some(environment) => project(
+        environment.color_in_direction(r.direction),
+        r.wavelength,
+      ),
+      None => Color::new(0., 0., 0.),
+    };
+  }
+
+  // Diffuse light arriving from the environment map, estimated by importance
+  // sampling the map and dropping directions that are blocked by geometry.
+  pub fn environment_lighting(&self, comps: &Computations) -> Color {
+    let environment = match &self.environment {
+      Some(environment) => environment,
+      None => return Color::new(0., 0., 0.),
+    };
+    let material = &comps.object.material;
+    if material.diffuse == 0. || environment.samples == 0 {
       return Color::new(0., 0., 0.);
     }
 
-    let n_ratio = comps.n1 / comps.n2;
-    let cos_i = dot(comps.eyev, comps.normalv);
-    let sin2_t = n_ratio * n_ratio * (1.0f64 - cos_i * cos_i);
-    if sin2_t > 1.0 {
-      return Color::new(0., 0., 0.);
+    let mut irradiance = Color::new(0., 0., 0.);
+
+    for _i in 0..environment.samples {
+      let (direction, radiance, pdf) = environment.sample(random(), random());
+      let cos = comps.normalv.dot(direction);
+      if cos <= 0. || pdf <= 0. {
+        continue;
+      }
+
+      let r = Ray::new(
+        comps.over_point,
+        direction,
+      );
+      if self.is_occluded(r, f64::INFINITY) {
+        continue;
+      }
+
+      let radiance = project(radiance, comps.wavelength);
+      irradiance = Color::add(irradiance, Color::mult(radiance, cos / pdf));
     }
+    irradiance = Color::div(irradiance, environment.samples as f64);
 
-    let cos_t = (1.0 - sin2_t).sqrt();
+    // lambertian reflection of the gathered light: albedo / pi
+    let color = comps.surface_color();
+    return Color::mult(
+      Color::hadamard(color, irradiance),
+      material.diffuse / f64::consts::PI,
+    );
+  }
+
+  // Light from emissive shapes, gathered by sampling points on their
+  // surfaces and shading each one as a shadow tested point light.
+  pub fn emissive_lighting(&self, comps: &Computations) -> Color {
+    let mut total = Color::new(0., 0., 0.);
+    if self.emissive_samples == 0 {
+      return total;
+    }
+
+    for emitter in &self.objects {
+      if !emitter.material.is_emissive() || emitter.handle == comps.object.handle {
+        continue;
+      }
 
-    // compute the direction of the refracted ray
-    let direction = comps
-      .normalv
-      .mult(n_ratio * cos_i - cos_t)
-      .sub(comps.eyev.mult(n_ratio));
+      for _i in 0..self.emissive_samples {
+        let sample = match emitter.sample_surface(random(), random()) {
+          Some(sample) => sample,
+          None => break,
+        };
+
+        let to_light = sample.point - comps.over_point;
+        let distance_squared = to_light.dot(to_light);
+        let cos_light = -sample.normal.dot(to_light.norm());
+        if cos_light <= 0. || distance_squared <= 0. || sample.pdf <= 0. {
+          continue;
+        }
 
-    // create the refracted ray
-    let refracted_ray = Ray::new(comps.under_point, direction);
+        // converts the area sample into the point light intensity that
+        // lighting_with_options() turns into the same lambertian contribution
+        let weight = cos_light
+          / (f64::consts::PI * distance_squared * sample.pdf * self.emissive_samples as f64);
+        let position = sample.point + sample.normal.to_vector() * EMITTER_OFFSET;
+        let emission = project(emitter.material.emission, comps.wavelength);
+        let light = PointLight::new(position, Color::mult(emission, weight));
+
+        let mut options = LightingOptions::new(self.is_shadowed_from(comps.over_point, position));
+        options.ambient_scale = 0.;
+        options.color = comps.filtered_color;
+        let contribution = lighting_with_options(
+          &comps.object.material,
+          &comps.object,
+          light,
+          comps.point,
+          comps.eyev,
+          comps.normalv,
+          options,
+        );
+        total = Color::add(total, contribution);
+      }
+    }
+
+    return total;
+  }
+
+  pub fn refracted_color(&self, comps: Computations, remaining: u32) -> Color {
+    if remaining == 0 || comps.object.material.transparency == 0.0 {
+      return Color::new(0., 0., 0.);
+    }
 
     // find the color of the refracted ray, making sure to multiply
     // by the transparency value to account for any opacity
-    let color = self.color_at(refracted_ray, remaining - 1);
+    let color = self.trace_refraction(&comps, remaining);
     return Color::mult(color, comps.object.material.transparency);
-
-    return color;
   }
 
   pub fn reflected_color(&self, comps: Computations, remaining: u32) -> Color {
@@ -203,25 +399,199 @@ impl World {
       return Color::new(0., 0., 0.);
     }
 
-    let reflected_ray = Ray::new(comps.over_point, comps.reflectv);
-    let color = self.color_at(reflected_ray, remaining - 1);
-
+    let color = self.trace_reflection(&comps, remaining);
     return Color::mult(color, comps.object.material.reflectiveness);
   }
 
-  pub fn is_shadowed(&self, point: Tuple) -> bool {
-    let v = self.light.unwrap().position.sub(point);
+  // The light arriving along the mirror direction, before any weighting.
+  // Rough surfaces average several rays reflected about microfacet normals
+  // drawn around the surface normal, which blurs the mirror image.
+  fn trace_reflection(&self, comps: &Computations, remaining: u32) -> Color {
+    if remaining == 0 {
+      return Color::new(0., 0., 0.);
+    }
+
+    let m = &comps.object.material;
+    if m.roughness <= 0. || m.glossy_samples == 0 {
+      // a mirror keeps the footprint of the incoming ray, so textures seen
+      // in it stay filtered
+      let mut reflected_ray = Ray::with_wavelength(
+        comps.over_point,
+        comps.reflectv,
+        comps.wavelength,
+      );
+      reflected_ray.differential = comps.reflected_differential();
+      reflected_ray.scattered = comps.scattered;
+      return self.color_at(reflected_ray, remaining - 1);
+    }
+
+    let samples = self.glossy_samples(comps);
+    let mut total = Color::new(0., 0., 0.);
+    for _i in 0..samples {
+      let direction = self.glossy_direction(comps, random(), random());
+      let mut r = Ray::with_wavelength(
+        comps.over_point,
+        direction,
+        comps.wavelength,
+      );
+      r.scattered = true;
+      let color = self.color_at(r, remaining - 1);
+      total = Color::add(total, color);
+    }
+    return Color::div(total, samples as f64);
+  }
+
+  // The light arriving through the surface, before any weighting. A white
+  // ray meeting a dispersive material is split into rays of several
+  // wavelengths, stratified over the visible range and jittered per ray,
+  // each refracted with the index for its own wavelength and developed
+  // into rgb like a spectral pixel.
+  fn trace_refraction(&self, comps: &Computations, remaining: u32) -> Color {
+    if remaining == 0 {
+      return Color::new(0., 0., 0.);
+    }
+
+    if comps.wavelength.is_some() || !comps.is_dispersive() {
+      return self.trace_refraction_band(comps, remaining);
+    }
+
+    let bands = self.dispersion_samples.max(1);
+    let mut film = SpectralFilm::new(self.white_point);
+    for i in 0..bands {
+      let wavelength = sample_wavelength(i, bands, random());
+      let mut band = comps.clone();
+      let (n1, n2) = comps.indices_at(Some(wavelength));
+      band.n1 = n1;
+      band.n2 = n2;
+      band.wavelength = Some(wavelength);
+      film.add(wavelength, self.trace_refraction_band(&band, remaining));
+    }
+    return film.to_rgb();
+  }
+
+  // Refraction for a single wavelength. Rough surfaces refract about
+  // sampled microfacet normals, frosting the image.
+  fn trace_refraction_band(&self, comps: &Computations, remaining: u32) -> Color {
+    let m = &comps.object.material;
+    if m.roughness <= 0. || m.glossy_samples == 0 {
+      return match comps.eyev.refract(comps.normalv, comps.n1 / comps.n2) {
+        Some(direction) => {
+          let mut r = Ray::with_wavelength(
+            comps.under_point,
+            direction,
+            comps.wavelength,
+          );
+          r.differential = comps.refracted_differential();
+          r.scattered = comps.scattered;
+          self.color_at(r, remaining - 1)
+        }
+        None => Color::new(0., 0., 0.),
+      };
+    }
+
+    let samples = self.glossy_samples(comps);
+    let mut total = Color::new(0., 0., 0.);
+    for _i in 0..samples {
+      if let Some(direction) = self.frosted_direction(comps, random(), random()) {
+        let mut r = Ray::with_wavelength(
+          comps.under_point,
+          direction,
+          comps.wavelength,
+        );
+        r.scattered = true;
+        total = Color::add(total, self.color_at(r, remaining - 1));
+      }
+    }
+    return Color::div(total, samples as f64);
+  }
+
+  // How many rays a rough hit averages. Only the first rough surface along
+  // a path splits into the material's glossy_samples; later ones trace one
+  // jittered ray each, or the cost would grow as glossy_samples^depth.
+  fn glossy_samples(&self, comps: &Computations) -> usize {
+    if comps.scattered {
+      return 1;
+    }
+    return comps.object.material.glossy_samples;
+  }
+
+  // The eye ray reflected about a microfacet normal. Directions that would
+  // leave below the surface fall back to the mirror direction.
+  pub fn glossy_direction(&self, comps: &Computations, u1: f64, u2: f64) -> Vector3 {
+    let a = microfacet::alpha(comps.object.material.roughness);
+    let h = microfacet::sample_normal(comps.normalv, a, u1, u2);
+    let direction = (-comps.eyev).reflect(h);
+    if comps.normalv.dot(direction) <= 0. {
+      return comps.reflectv;
+    }
+    return direction;
+  }
+
+  // The eye ray refracted through a microfacet normal, falling back to the
+  // smooth refraction when the sampled facet reflects it or sends it back
+  // out. None means total internal reflection.
+  pub fn frosted_direction(&self, comps: &Computations, u1: f64, u2: f64) -> Option<Vector3> {
+    let n_ratio = comps.n1 / comps.n2;
+    let a = microfacet::alpha(comps.object.material.roughness);
+    let h = microfacet::sample_normal(comps.normalv, a, u1, u2);
+    if let Some(direction) = comps.eyev.refract(h, n_ratio) {
+      if comps.normalv.dot(direction) < 0. {
+        return Some(direction);
+      }
+    }
+    return comps.eyev.refract(comps.normalv, n_ratio);
+  }
+
+  // How much the mirror reflection (per channel) and the refraction add to a
+  // hit. Phong materials use reflectiveness and transparency, blended by
+  // Schlick when both are present; microfacet materials split by Fresnel.
+  pub fn indirect_weights(&self, comps: &Computations) -> (Color, f64) {
+    let m = &comps.object.material;
+
+    if m.shading_model == ShadingModel::Microfacet {
+      let color = comps.surface_color();
+      let fresnel = microfacet::fresnel_at_hit(m, color, comps);
+      let refract = (1. - microfacet::average(fresnel)) * m.transparency * (1. - m.metallic);
+      return (fresnel, refract);
+    }
+
+    if m.reflectiveness > 0.0 && m.transparency > 0.0 {
+      let reflectance = schlick(comps);
+      let reflect = m.reflectiveness * reflectance;
+      return (
+        Color::new(reflect, reflect, reflect),
+        m.transparency * (1. - reflectance),
+      );
+    }
+
+    let reflect = m.reflectiveness;
+    return (Color::new(reflect, reflect, reflect), m.transparency);
+  }
+
+  pub fn is_shadowed(&self, point: Point3) -> bool {
+    return self.is_shadowed_from(point, self.light.unwrap().position);
+  }
+
+  pub fn is_shadowed_from(&self, point: Point3, light_position: Point3) -> bool {
+    let v = light_position - point;
     let distance = v.mag();
     let direction = v.norm();
 
-    let r = Ray::new(point, direction);
-    let intersections = self.intersect_world(r);
+    return self.is_occluded(
+      Ray::new(point, direction),
+      distance,
+    );
+  }
 
-    let h = intersections.hit();
-    if h.intersections.len() > 0 && h.intersections[0].t < distance {
-      return true;
-    }
-    return false;
+  // Whether anything lies along r within [epsilon, distance). Unlike
+  // intersect_world it stops at the first blocker found, in whatever order,
+  // and never sorts, which is all shadow and occlusion rays need.
+  pub fn is_occluded(&self, r: Ray, distance: f64) -> bool {
+    let r = r.with_range(OCCLUSION_EPSILON, distance);
+    return self
+      .objects
+      .iter()
+      .any(|o| o.intersects(r).intersections.len() > 0);
   }
 }
 
@@ -241,7 +611,7 @@ fn default_world_contains_some_light_or_objects() {
   assert_eq!(world.objects.len() > 0, true);
 
   assert_eq!(
-    world.light.unwrap().position.equals(point(-10., 10., -10.)),
+    world.light.unwrap().position.to_tuple().equals(point(-10., 10., -10.)),
     true
   );
 }
@@ -250,7 +620,7 @@ fn default_world_contains_some_light_or_objects() {
 fn ray_along_z_axis_intersects_default_world() {
   let world = World::default_world();
 
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let xs = world.intersect_world(r).intersections;
 
   assert_eq!(xs.len(), 4);
@@ -263,10 +633,10 @@ fn ray_along_z_axis_intersects_default_world() {
 #[test]
 fn shading_an_intersection() {
   let world = World::default_world();
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let xs = world.intersect_world(r).intersections;
   let shape = xs[0].object.clone();
-  let i = Intersection::new(4., shape);
+  let i = Intersection::new(4., &shape);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
   let c = world.shade_hit(comps, 1);
@@ -280,10 +650,10 @@ fn shading_an_intersection() {
 #[test]
 fn shading_an_intersection_from_inside() {
   let mut world = World::default_world();
-  world.light = Some(PointLight::new(point(0., 0.25, 0.), Color::new(1., 1., 1.)));
-  let r = Ray::new(point(0., 0., 0.), vector(0., 0., 1.));
+  world.light = Some(PointLight::new(Point3::new(0., 0.25, 0.), Color::new(1., 1., 1.)));
+  let r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
   let shape = world.objects.clone()[1].clone();
-  let i = Intersection::new(0.5, shape);
+  let i = Intersection::new(0.5, &shape);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
   let c = world.shade_hit(comps, 1);
@@ -297,7 +667,7 @@ fn shading_an_intersection_from_inside() {
 #[test]
 fn the_color_when_a_ray_misses() {
   let world = World::default_world();
-  let r = Ray::new(point(0., 0., -5.), vector(0., 1., 0.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 1., 0.));
   let c = world.color_at(r, 1);
 
   assert_eq!(Color::approx_equals(c, Color::new(0., 0., 0.)), true);
@@ -306,7 +676,7 @@ fn the_color_when_a_ray_misses() {
 #[test]
 fn the_color_when_a_ray_hits() {
   let world = World::default_world();
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let c = world.color_at(r, 1);
 
   assert_eq!(
@@ -319,7 +689,7 @@ fn the_color_when_a_ray_hits() {
 fn the_color_with_an_intersection_from_behind() {
   let mut world = World::default_world2();
 
-  let r = Ray::new(point(0., 0., 0.75), vector(0., 0., -1.));
+  let r = Ray::new(Point3::new(0., 0., 0.75), Vector3::new(0., 0., -1.));
   let c = world.color_at(r, 1);
 
   assert_eq!(Color::approx_equals(c, Color::new(1.0, 1.0, 1.0)), true);
@@ -328,7 +698,7 @@ fn the_color_with_an_intersection_from_behind() {
 #[test]
 fn there_is_no_shadow_when_nothing_is_collinear_with_point_and_light() {
   let mut world = World::default_world();
-  let point = point(0., 110., 0.);
+  let point = Point3::new(0., 110., 0.);
   let is_in_shadow = world.is_shadowed(point);
 
   assert_eq!(is_in_shadow, false);
@@ -337,7 +707,7 @@ fn there_is_no_shadow_when_nothing_is_collinear_with_point_and_light() {
 #[test]
 fn there_is_a_shadow_when_an_object_is_between_the_point_and_the_light() {
   let mut world = World::default_world();
-  let point = point(10., -10., 10.);
+  let point = Point3::new(10., -10., 10.);
   let is_in_shadow = world.is_shadowed(point);
 
   assert_eq!(is_in_shadow, true);
@@ -346,7 +716,7 @@ fn there_is_a_shadow_when_an_object_is_between_the_point_and_the_light() {
 #[test]
 fn there_is_no_shadow_when_an_object_is_behind_the_light() {
   let mut world = World::default_world();
-  let point = point(-20., 20., -20.);
+  let point = Point3::new(-20., 20., -20.);
   let is_in_shadow = world.is_shadowed(point);
 
   assert_eq!(is_in_shadow, false);
@@ -355,7 +725,7 @@ fn there_is_no_shadow_when_an_object_is_behind_the_light() {
 #[test]
 fn there_is_no_shadow_when_an_object_is_behind_the_point() {
   let mut world = World::default_world();
-  let point = point(-2., 2., -2.);
+  let point = Point3::new(-2., 2., -2.);
   let is_in_shadow = world.is_shadowed(point);
 
   assert_eq!(is_in_shadow, false);
@@ -364,10 +734,10 @@ fn there_is_no_shadow_when_an_object_is_behind_the_point() {
 #[test]
 fn the_reflected_color_of_a_non_reflective_material() {
   let mut world = World::default_world();
-  let r = Ray::new(point(0., 0., 0.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
   let mut shape = world.objects[1].clone();
   shape.material.ambient = 1.0;
-  let i = Intersection::new(1., shape);
+  let i = Intersection::new(1., &shape);
   let xs = Intersections::new(vec![i.clone()]);
 
   let comps = prepare_computations(i, r, xs);
@@ -388,8 +758,11 @@ fn the_reflected_color_of_a_reflective_material() {
   world.add_object(plane.clone());
 
   // 45 degree angle ray
-  let r = Ray::new(point(0., 0., -3.), vector(0., -half_root2, half_root2));
-  let i = Intersection::new(2.0f64.sqrt(), plane);
+  let r = Ray::new(
+    Point3::new(0., 0., -3.),
+    Vector3::new(0., -half_root2, half_root2),
+  );
+  let i = Intersection::new(2.0f64.sqrt(), &plane);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
   let color = world.reflected_color(comps, 1);
@@ -411,8 +784,11 @@ fn shade_hit_of_a_reflective_material() {
   world.add_object(plane.clone());
 
   // 45 degree angle ray
-  let r = Ray::new(point(0., 0., -3.), vector(0., -half_root2, half_root2));
-  let i = Intersection::new(2.0f64.sqrt(), plane);
+  let r = Ray::new(
+    Point3::new(0., 0., -3.),
+    Vector3::new(0., -half_root2, half_root2),
+  );
+  let i = Intersection::new(2.0f64.sqrt(), &plane);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
   let color = world.shade_hit(comps, 1);
@@ -427,7 +803,7 @@ fn shade_hit_of_a_reflective_material() {
 #[test]
 fn color_at_with_mutually_reflective_surfaces() {
   let mut world = World::new();
-  world.set_light(PointLight::new(point(0., 0., 0.), Color::new(1., 1., 1.)));
+  world.set_light(PointLight::new(Point3::new(0., 0., 0.), Color::new(1., 1., 1.)));
 
   let mut lower_plane = Shape::new(ShapeType::Plane);
   lower_plane.material.reflectiveness = 1.0;
@@ -441,7 +817,7 @@ fn color_at_with_mutually_reflective_surfaces() {
   upper_plane.set_transform(upper_tx);
   world.add_object(upper_plane);
 
-  let r = Ray::new(point(0., 0., 0.), vector(0., 1., 0.));
+  let r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 1., 0.));
   let color = world.color_at(r, 4);
 
   println!("{:#?}", color);
@@ -457,8 +833,11 @@ fn the_reflected_color_at_maximum_recursion_depth() {
   world.add_object(plane.clone());
 
   // 45 degree angle ray
-  let r = Ray::new(point(0., 0., -3.), vector(0., -half_root2, half_root2));
-  let i = Intersection::new(2.0f64.sqrt(), plane);
+  let r = Ray::new(
+    Point3::new(0., 0., -3.),
+    Vector3::new(0., -half_root2, half_root2),
+  );
+  let i = Intersection::new(2.0f64.sqrt(), &plane);
   let xs = Intersections::new(vec![i.clone()]);
   let comps = prepare_computations(i, r, xs);
   let color = world.reflected_color(comps, 0);
@@ -470,11 +849,11 @@ fn the_reflected_color_at_maximum_recursion_depth() {
 #[test]
 fn refracted_color_of_an_opaque_surface() {
   let world = World::default_world();
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let xs = world.intersect_world(r).intersections;
   let shape = xs[0].object.clone();
-  let i1 = Intersection::new(4., shape.clone());
-  let i2 = Intersection::new(6., shape);
+  let i1 = Intersection::new(4., &shape);
+  let i2 = Intersection::new(6., &shape);
   let xs = Intersections::new(vec![i1.clone(), i2.clone()]);
   let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
   let c = world.refracted_color(comps, 1);
@@ -485,13 +864,13 @@ fn refracted_color_of_an_opaque_surface() {
 #[test]
 fn refracted_color_at_maximum_recursion_depth() {
   let world = World::default_world();
-  let r = Ray::new(point(0., 0., -5.), vector(0., 0., 1.));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
   let xs = world.intersect_world(r).intersections;
   let mut shape = xs[0].object.clone();
   shape.material.transparency = 1.0;
   shape.material.refractive_index = 1.5;
-  let i1 = Intersection::new(4., shape.clone());
-  let i2 = Intersection::new(6., shape);
+  let i1 = Intersection::new(4., &shape);
+  let i2 = Intersection::new(6., &shape);
   let xs = Intersections::new(vec![i1.clone(), i2.clone()]);
   let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
   let c = world.refracted_color(comps, 0);
@@ -503,13 +882,13 @@ fn refracted_color_at_maximum_recursion_depth() {
 fn refracted_color_at_total_internal_reflection() {
   let half_root2 = 2.0f64.sqrt() / 2.0;
   let world = World::default_world();
-  let r = Ray::new(point(0., 0., half_root2), vector(0., 1., 0.));
+  let r = Ray::new(Point3::new(0., 0., half_root2), Vector3::new(0., 1., 0.));
   let xs = world.intersect_world(r).intersections;
   let mut shape = xs[0].object.clone();
   shape.material.transparency = 1.0;
   shape.material.refractive_index = 1.5;
-  let i1 = Intersection::new(-half_root2, shape.clone());
-  let i2 = Intersection::new(half_root2, shape.clone());
+  let i1 = Intersection::new(-half_root2, &shape);
+  let i2 = Intersection::new(half_root2, &shape);
   let xs = Intersections::new(vec![i1.clone(), i2.clone()]);
   // we're inside sphere so need to look at the second intersection
   let comps = prepare_computations(xs.intersections[1].clone(), r, xs);
@@ -521,7 +900,7 @@ fn refracted_color_at_total_internal_reflection() {
 #[test]
 fn refracted_color_with_a_refracted_ray() {
   let mut world = World::default_world();
-  let r = Ray::new(point(0., 0., 0.1), vector(0., 1., 0.));
+  let r = Ray::new(Point3::new(0., 0., 0.1), Vector3::new(0., 1., 0.));
   let xs = world.intersect_world(r).intersections;
 
   world.objects[0].material.ambient = 1.0;
@@ -535,10 +914,10 @@ fn refracted_color_with_a_refracted_ray() {
   world.objects[1].material.transparency = 1.0;
   world.objects[1].material.refractive_index = 1.5;
 
-  let i1 = Intersection::new(-0.9899, world.objects[0].clone());
-  let i2 = Intersection::new(-0.4899, world.objects[1].clone());
-  let i3 = Intersection::new(0.4899, world.objects[1].clone());
-  let i4 = Intersection::new(0.9899, world.objects[0].clone());
+  let i1 = Intersection::new(-0.9899, &world.objects[0]);
+  let i2 = Intersection::new(-0.4899, &world.objects[1]);
+  let i3 = Intersection::new(0.4899, &world.objects[1]);
+  let i4 = Intersection::new(0.9899, &world.objects[0]);
 
   let xs = Intersections::new(vec![i1.clone(), i2.clone(), i3.clone(), i4.clone()]);
   let comps = prepare_computations(xs.intersections[2].clone(), r, xs);
@@ -567,9 +946,12 @@ fn shade_hit_with_a_transparent_material() {
   ball.set_transform(Transform::new().translate(0., -3.5, -0.5).transform);
   world.add_object(ball);
 
-  let r = Ray::new(point(0., 0., -3.0), vector(0., -half_root2, half_root2));
+  let r = Ray::new(
+    Point3::new(0., 0., -3.0),
+    Vector3::new(0., -half_root2, half_root2),
+  );
 
-  let i1 = Intersection::new(2.0f64.sqrt(), floor.clone());
+  let i1 = Intersection::new(2.0f64.sqrt(), &floor);
 
   let xs = Intersections::new(vec![i1.clone()]);
   let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
@@ -585,7 +967,10 @@ fn shade_hit_with_a_transparent_material() {
 fn shade_hit_with_a_reflective_and_transparent_material() {
   let half_root2 = 2.0f64.sqrt() / 2.0;
   let mut world = World::default_world();
-  let r = Ray::new(point(0., 0., -3.0), vector(0., -half_root2, half_root2));
+  let r = Ray::new(
+    Point3::new(0., 0., -3.0),
+    Vector3::new(0., -half_root2, half_root2),
+  );
 
   let mut floor = Shape::new(ShapeType::Plane);
   floor.set_transform(Transform::new().translate(0., -1., 0.).transform);
@@ -600,7 +985,7 @@ fn shade_hit_with_a_reflective_and_transparent_material() {
   ball.set_transform(Transform::new().translate(0., -3.5, -0.5).transform);
   world.add_object(ball);
 
-  let i1 = Intersection::new(2.0f64.sqrt(), floor.clone());
+  let i1 = Intersection::new(2.0f64.sqrt(), &floor);
 
   let xs = Intersections::new(vec![i1.clone()]);
   let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
@@ -611,3 +996,535 @@ fn shade_hit_with_a_reflective_and_transparent_material() {
     true
   );
 }
+
+#[test]
+fn a_ray_that_misses_sees_the_environment() {
+  let mut world = World::default_world();
+  world.set_environment(EnvironmentMap::uniform(Color::new(0.2, 0.4, 0.6)));
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 1., 0.));
+  let c = world.color_at(r, 1);
+
+  assert_eq!(Color::equals(c, Color::new(0.2, 0.4, 0.6)), true);
+}
+
+#[test]
+fn shade_hit_is_lit_by_a_uniform_environment() {
+  let mut world = World::new();
+  let mut environment = EnvironmentMap::uniform(Color::new(1., 1., 1.));
+  environment.samples = 4096;
+  world.set_environment(environment);
+  crate::sampling::seed(26);
+
+  let mut s = Shape::new(ShapeType::Sphere);
+  s.material.ambient = 0.0;
+  world.add_object(s.clone());
+
+  let r = Ray::new(Point3::new(0., 5., 0.), Vector3::new(0., -1., 0.));
+  let i = Intersection::new(4., &s);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  let c = world.shade_hit(comps, 1);
+
+  // a white hemisphere irradiates a lambertian surface with its albedo
+  assert_eq!((c.r - 0.9).abs() < 0.1, true);
+  assert_eq!((c.g - 0.9).abs() < 0.1, true);
+  assert_eq!((c.b - 0.9).abs() < 0.1, true);
+}
+
+#[test]
+fn environment_light_is_blocked_by_geometry() {
+  let mut world = World::new();
+  world.set_environment(EnvironmentMap::uniform(Color::new(1., 1., 1.)));
+
+  let mut s = Shape::new(ShapeType::Sphere);
+  s.material.ambient = 0.0;
+  world.add_object(s.clone());
+
+  // shading the inside of the sphere, which the environment cannot reach
+  let r = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
+  let i = Intersection::new(1., &s);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  let c = world.environment_lighting(&comps);
+
+  assert_eq!(Color::equals(c, Color::new(0., 0., 0.)), true);
+}
+
+#[test]
+fn shade_hit_includes_the_emission_of_the_surface() {
+  let mut world = World::default_world();
+  world.objects[0].material.emission = Color::new(0.5, 0.5, 0.5);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let c = world.color_at(r, 1);
+
+  assert_eq!(
+    Color::approx_equals(c, Color::new(0.88066, 0.97583, 0.7855)),
+    true
+  );
+}
+
+#[test]
+fn an_emissive_sphere_lights_the_floor_below_it() {
+  let mut world = World::new();
+  world.set_emissive_samples(4096);
+
+  let mut floor = Shape::new(ShapeType::Plane);
+  floor.material.ambient = 0.;
+  floor.material.specular = 0.;
+  world.add_object(floor.clone());
+
+  let mut lamp = Shape::new(ShapeType::Sphere);
+  lamp.set_transform(Transform::new().translate(0., 2., 0.).transform);
+  lamp.material.emission = Color::new(1., 1., 1.);
+  world.add_object(lamp);
+
+  let r = Ray::new(Point3::new(0., 1., -1.), Vector3::new(0., -1., 1.).norm());
+  let i = Intersection::new(2.0f64.sqrt(), &floor);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  crate::sampling::seed(28);
+  let c = world.shade_hit(comps, 1);
+
+  // a sphere of radius r at distance d irradiates pi * (r / d)^2 per unit
+  // of emitted radiance, of which the floor reflects albedo / pi
+  assert_eq!((c.r - 0.9 * 0.25).abs() < 0.03, true);
+  assert_eq!((c.g - 0.9 * 0.25).abs() < 0.03, true);
+}
+
+#[test]
+fn emissive_light_casts_shadows() {
+  let mut world = World::new();
+
+  let mut floor = Shape::new(ShapeType::Plane);
+  floor.material.ambient = 0.;
+  world.add_object(floor.clone());
+
+  let mut lamp = Shape::new(ShapeType::Sphere);
+  lamp.set_transform(Transform::new().translate(0., 4., 0.).transform);
+  lamp.material.emission = Color::new(1., 1., 1.);
+  world.add_object(lamp);
+
+  let mut blocker = Shape::new(ShapeType::Sphere);
+  blocker.set_transform(
+    Transform::new()
+      .translate(0., 2., 0.)
+      .scale(3., 0.1, 3.)
+      .transform,
+  );
+  world.add_object(blocker);
+
+  let r = Ray::new(Point3::new(0., 1., 0.), Vector3::new(0., -1., 0.));
+  let i = Intersection::new(1., &floor);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  let c = world.emissive_lighting(&comps);
+
+  assert_eq!(Color::equals(c, Color::new(0., 0., 0.)), true);
+}
+
+#[test]
+fn a_smooth_metal_reflects_tinted_by_its_color() {
+  let half_root2 = 2.0f64.sqrt() / 2.0;
+  let mut world = World::default_world();
+  let mut plane = Shape::new(ShapeType::Plane);
+  plane.material = Material::pbr(Color::new(1., 0.5, 0.25), 1.0, 0.0);
+  plane.material.ambient = 0.;
+  plane.set_transform(Transform::new().translate(0., -1.0, 0.).transform);
+  world.add_object(plane.clone());
+
+  let r = Ray::new(
+    Point3::new(0., 0., -3.),
+    Vector3::new(0., -half_root2, half_root2),
+  );
+  let i = Intersection::new(2.0f64.sqrt(), &plane);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  let (reflect, refract) = world.indirect_weights(&comps);
+
+  // fresnel pulls the tint towards white at 45 degrees, but only a little
+  assert_eq!(refract, 0.);
+  assert_eq!(reflect.r >= 1. - 1e-9, true);
+  assert_eq!(reflect.g > 0.5 && reflect.g < 0.52, true);
+  assert_eq!(reflect.b > 0.25 && reflect.b < 0.27, true);
+}
+
+#[test]
+fn a_rough_dielectric_reflects_only_its_fresnel_share() {
+  let mut world = World::default_world();
+  world.objects[0].material = Material::pbr(Color::new(1., 1., 1.), 0.0, 1.0);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let i = Intersection::new(4., &world.objects[0]);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  let (reflect, refract) = world.indirect_weights(&comps);
+
+  // roughness blurs the reflection rather than dimming it
+  assert_eq!((reflect.r - 0.04).abs() < 1e-9, true);
+  assert_eq!(refract, 0.);
+}
+
+#[test]
+fn pbr_glass_splits_light_between_reflection_and_refraction() {
+  let mut world = World::default_world();
+  let mut glass = Material::pbr(Color::new(1., 1., 1.), 0.0, 0.0);
+  glass.transparency = 1.0;
+  glass.refractive_index = 1.5;
+  world.objects[0].material = glass;
+
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let i = Intersection::new(4., &world.objects[0]);
+  let xs = Intersections::new(vec![i.clone()]);
+  let comps = prepare_computations(i, r, xs);
+  let (reflect, refract) = world.indirect_weights(&comps);
+
+  assert_eq!((reflect.r - 0.04).abs() < 1e-3, true);
+  assert_eq!((reflect.r + refract - 1.).abs() < 1e-9, true);
+}
+
+#[cfg(test)]
+fn small_ball_world(placement: Transform) -> World {
+  // a small bright ball, seen either in a mirror floor or through a glass
+  // floor, shaded only by its own ambient term
+  let mut world = World::new();
+  world.set_light(PointLight::new(Point3::new(0., 100., 0.), Color::new(1., 1., 1.)));
+  let mut ball = Shape::new(ShapeType::Sphere);
+  ball.set_transform(placement.transform);
+  ball.material.ambient = 1.;
+  ball.material.diffuse = 0.;
+  ball.material.specular = 0.;
+  world.add_object(ball);
+  return world;
+}
+
+#[test]
+fn glossy_reflection_blurs_the_mirror_image() {
+  let half_root2 = 2.0f64.sqrt() / 2.0;
+  let r = Ray::new(
+    Point3::new(0., 1., -1.),
+    Vector3::new(0., -half_root2, half_root2),
+  );
+
+  crate::sampling::seed(31);
+  let mut c = vec![];
+  for roughness in vec![0., 0.4] {
+    // the mirror direction from the floor at the origin hits the ball
+    let mut world = small_ball_world(Transform::new().translate(0., 3., 3.).scale(0.5, 0.5, 0.5));
+    let mut floor = Shape::new(ShapeType::Plane);
+    floor.material.color = Color::new(0., 0., 0.);
+    floor.material.ambient = 0.;
+    floor.material.reflectiveness = 1.;
+    floor.material.roughness = roughness;
+    floor.material.glossy_samples = 64;
+    world.add_object(floor.clone());
+
+    let i = Intersection::new(2.0f64.sqrt(), &floor);
+    let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+    c.push(world.reflected_color(comps, 5));
+  }
+
+  assert_eq!(Color::equals(c[0], Color::new(1., 1., 1.)), true);
+  assert_eq!(c[1].r < 0.9, true);
+  assert_eq!(c[1].r > 0., true);
+}
+
+#[test]
+fn only_the_first_glossy_bounce_splits_into_several_rays() {
+  // two rough mirrors facing each other, so every reflected ray hits the
+  // other one until the recursion runs out
+  let mut world = World::new();
+  let mut floor = Shape::new(ShapeType::Plane);
+  floor.material.reflectiveness = 1.;
+  floor.material.roughness = 0.5;
+  floor.material.glossy_samples = 8;
+  let mut ceiling = Shape::new(ShapeType::Plane);
+  ceiling.material = floor.material.clone();
+  ceiling.set_transform(Transform::new().translate(0., 1., 0.).transform);
+  world.add_object(floor);
+  world.add_object(ceiling);
+
+  crate::sampling::seed(31);
+  TRACED_RAYS.with(|n| n.set(0));
+  let r = Ray::new(Point3::new(0., 0.5, 0.), Vector3::new(0., -1., 0.));
+  world.color_at(r, 5);
+
+  // the camera ray, then 8 rays for each of the 5 levels below it, rather
+  // than 8 + 64 + 512 + 4096 + 32768
+  assert_eq!(TRACED_RAYS.with(|n| n.get()), 1 + 8 * 5);
+}
+
+#[test]
+fn glossy_directions_stay_above_the_surface() {
+  let world = World::new();
+  let mut floor = Shape::new(ShapeType::Plane);
+  floor.material.roughness = 1.;
+  // a grazing eye ray, where most sampled facets would reflect downwards
+  let r = Ray::new(
+    Point3::new(0., 0.01, -1.),
+    Vector3::new(0., -0.01, 1.).norm(),
+  );
+  let i = Intersection::new(1., &floor);
+  let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+
+  for (u1, u2) in vec![(0.1, 0.2), (0.5, 0.5), (0.9, 0.75), (0.99, 0.)] {
+    let direction = world.glossy_direction(&comps, u1, u2);
+    assert_eq!(comps.normalv.dot(direction) > 0., true);
+  }
+}
+
+#[test]
+fn frosted_refraction_blurs_what_is_behind_the_glass() {
+  let r = Ray::new(Point3::new(0., 1., 0.), Vector3::new(0., -1., 0.));
+
+  crate::sampling::seed(31);
+  let mut c = vec![];
+  for roughness in vec![0., 0.4] {
+    // the ball sits straight below the glass floor
+    let mut world = small_ball_world(Transform::new().translate(0., -3., 0.).scale(0.2, 0.2, 0.2));
+    let mut floor = Shape::new(ShapeType::Plane);
+    floor.material.color = Color::new(0., 0., 0.);
+    floor.material.ambient = 0.;
+    floor.material.transparency = 1.;
+    floor.material.refractive_index = 1.5;
+    floor.material.roughness = roughness;
+    floor.material.glossy_samples = 64;
+    world.add_object(floor.clone());
+
+    let i = Intersection::new(1., &floor);
+    let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+    c.push(world.refracted_color(comps, 5));
+  }
+
+  assert_eq!(Color::equals(c[0], Color::new(1., 1., 1.)), true);
+  assert_eq!(c[1].r < 0.9, true);
+  assert_eq!(c[1].r > 0., true);
+}
+
+#[cfg(test)]
+fn color_through_a_glass_slab(thickness: f64) -> Color {
+  // a white backdrop seen head on through a slab of green-tinted glass
+  let mut world = World::new();
+  world.set_light(PointLight::new(Point3::new(0., 0., -10.), Color::new(1., 1., 1.)));
+
+  let mut backdrop = Shape::new(ShapeType::Plane);
+  backdrop.set_transform(
+    Transform::new()
+      .translate(0., 0., 10.)
+      .rotate_x(f64::consts::PI / 2.)
+      .transform,
+  );
+  backdrop.material.ambient = 1.;
+  backdrop.material.diffuse = 0.;
+  backdrop.material.specular = 0.;
+  world.add_object(backdrop);
+
+  let mut slab = Shape::new(ShapeType::Sphere);
+  slab.set_transform(Transform::new().scale(5., 5., thickness / 2.).transform);
+  slab.material.color = Color::new(0., 0., 0.);
+  slab.material.ambient = 0.;
+  slab.material.diffuse = 0.;
+  slab.material.specular = 0.;
+  slab.material.transparency = 1.;
+  slab.material.refractive_index = 1.;
+  slab.material.absorption = Color::new(1., 0., 1.);
+  world.add_object(slab);
+
+  return world.color_at(
+    Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.)),
+    5,
+  );
+}
+
+#[test]
+fn thick_glass_absorbs_more_than_thin_glass() {
+  let thin = color_through_a_glass_slab(0.2);
+  let thick = color_through_a_glass_slab(2.);
+
+  assert_eq!(
+    Color::equals(thin, Color::new((-0.2f64).exp(), 1., (-0.2f64).exp())),
+    true
+  );
+  assert_eq!(
+    Color::equals(thick, Color::new((-2.0f64).exp(), 1., (-2.0f64).exp())),
+    true
+  );
+}
+
+#[test]
+fn light_outside_an_absorbing_object_is_unaffected() {
+  let mut world = World::default_world();
+  world.objects[0].material.absorption = Color::new(1., 1., 1.);
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let i = Intersection::new(4., &world.objects[0]);
+  let comps = prepare_computations(i.clone(), r, Intersections::new(vec![i]));
+
+  assert_eq!(
+    Color::equals(world.medium_transmittance(&comps), Color::new(1., 1., 1.)),
+    true
+  );
+}
+
+#[cfg(test)]
+fn prism_world(dispersion: Option<Dispersion>) -> World {
+  // a glass slab tilted so light passes through its faces at an angle, with
+  // a floor striped red, green and blue behind it
+  let mut world = World::new();
+  world.set_light(PointLight::new(Point3::new(0., 0., -10.), Color::new(1., 1., 1.)));
+
+  let mut backdrop = Shape::new(ShapeType::Plane);
+  backdrop.set_transform(
+    Transform::new()
+      .translate(0., 0., 4.)
+      .rotate_x(f64::consts::PI / 2.)
+      .transform,
+  );
+  let mut stripes = Pattern::new(
+    PatternType::Stripe,
+    Color::new(1., 1., 1.),
+    Color::new(0., 0., 0.),
+  );
+  stripes.set_transform(Transform::new().scale(0.02, 1., 1.).transform);
+  backdrop.material.set_pattern(stripes);
+  backdrop.material.ambient = 1.;
+  backdrop.material.diffuse = 0.;
+  backdrop.material.specular = 0.;
+  world.add_object(backdrop);
+
+  let mut prism = Shape::glass_sphere();
+  prism.set_transform(
+    Transform::new()
+      .rotate_y(0.8)
+      .scale(10., 10., 0.5)
+      .transform,
+  );
+  prism.material.color = Color::new(0., 0., 0.);
+  prism.material.ambient = 0.;
+  prism.material.diffuse = 0.;
+  prism.material.specular = 0.;
+  if let Some(d) = dispersion {
+    prism.material.set_dispersion(d);
+  }
+  world.add_object(prism);
+
+  return world;
+}
+
+#[test]
+fn a_dispersive_material_separates_white_light() {
+  // without dispersion every channel takes the same path, so the
+  // black and white stripes come through as greys
+  let r = Ray::new(Point3::new(0.013, 0.3, -5.), Vector3::new(0., 0., 1.));
+  let plain = prism_world(None).color_at(r, 5);
+  assert_eq!(plain.r, plain.g);
+  assert_eq!(plain.g, plain.b);
+
+  // strong dispersion sends the channels to different stripes somewhere
+  crate::sampling::seed(33);
+  let mut separated = false;
+  for i in 0..20 {
+    let x = i as f64 * 0.0037;
+    let r = Ray::new(Point3::new(x, 0.3, -5.), Vector3::new(0., 0., 1.));
+    let c = prism_world(Some(Dispersion::cauchy(1.5, 0.3))).color_at(r, 5);
+    if (c.r - c.b).abs() > 0.1 {
+      separated = true;
+    }
+  }
+  assert_eq!(separated, true);
+}
+
+#[test]
+fn dispersion_keeps_white_light_white() {
+  let mut world = World::new();
+  let mut glass = Shape::glass_sphere();
+  glass.material.set_dispersion(Dispersion::bk7());
+  world.add_object(glass);
+  world.set_environment(EnvironmentMap::uniform(Color::new(1., 1., 1.)));
+  world.set_dispersion_samples(64);
+
+  // head on, every wavelength passes straight through and sees white
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let xs = world.intersect_world(r);
+  let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
+
+  crate::sampling::seed(33);
+  let c = world.refracted_color(comps, 5);
+  assert_eq!((c.r - 1.).abs() < 0.05, true);
+  assert_eq!((c.g - 1.).abs() < 0.05, true);
+  assert_eq!((c.b - 1.).abs() < 0.05, true);
+}
+
+#[test]
+fn a_ray_with_a_wavelength_is_not_split_again() {
+  let mut world = World::new();
+  let mut glass = Shape::glass_sphere();
+  glass.material.set_dispersion(Dispersion::bk7());
+  world.add_object(glass);
+  world.set_environment(EnvironmentMap::uniform(Color::new(0.2, 0.4, 0.6)));
+
+  let r = Ray::with_wavelength(
+    Point3::new(0., 0., -5.),
+    Vector3::new(0., 0., 1.),
+    Some(450.),
+  );
+  let xs = world.intersect_world(r);
+  let comps = prepare_computations(xs.intersections[0].clone(), r, xs);
+
+  // head on, the ray passes straight through and sees the environment at
+  // its own wavelength only
+  let c = world.refracted_color(comps, 5);
+  assert_eq!(Color::equals(c, Color::new(0.6, 0.6, 0.6)), true);
+}
+
+#[test]
+fn occlusion_only_counts_blockers_within_the_distance() {
+  let world = World::default_world();
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+
+  // the outer sphere is first hit at t = 4
+  assert_eq!(world.is_occluded(r, 10.), true);
+  assert_eq!(world.is_occluded(r, 4.5), true);
+  assert_eq!(world.is_occluded(r, 3.5), false);
+
+  // a ray leaving from inside only sees the far sides, ahead of it
+  let inside = Ray::new(Point3::new(0., 0., 0.), Vector3::new(0., 0., 1.));
+  assert_eq!(world.is_occluded(inside, 0.4), false);
+  assert_eq!(world.is_occluded(inside, 0.6), true);
+
+  let away = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., -1.));
+  assert_eq!(world.is_occluded(away, f64::INFINITY), false);
+}
+
+#[test]
+fn surfaces_far_from_the_origin_do_not_shadow_themselves() {
+  // a fixed offset of 1e-10 leaves well over a third of these points
+  // inside the ball
+  let c = 1.234567e7;
+  let mut world = World::new();
+  world.set_light(PointLight::new(
+    Point3::new(c, c + 1.0e4, c),
+    Color::new(1., 1., 1.),
+  ));
+  let mut ball = Shape::new(ShapeType::Sphere);
+  ball.set_transform(
+    Transform::new()
+      .translate(c, c, c)
+      .scale(1.0e3, 1.0e3, 1.0e3)
+      .transform,
+  );
+  world.add_object(ball);
+
+  let origin = Point3::new(c + 123.4, c + 3.0e3, c - 77.7);
+  for i in 0..20 {
+    for j in 0..20 {
+      let target = Point3::new(
+        c + i as f64 * 37.1 - 350.,
+        c + 1.0e3,
+        c + j as f64 * 41.3 - 410.,
+      );
+      let r = Ray::new(origin, (target - origin).norm());
+      let xs = world.intersect_world(r);
+      let comps = prepare_computations(xs.hit().intersections[0], r, xs.clone());
+
+      assert_eq!(world.is_shadowed(comps.over_point), false);
+    }
+  }
+}
@@ -0,0 +1,297 @@
+use crate::colors::Color;
+use crate::intersections::prepare_computations;
+use crate::light::{lighting_with_options, LightingOptions, PointLight};
+use crate::material::{microfacet, ShadingModel};
+use crate::ray::Ray;
+use crate::sampling::{cosine_sample_hemisphere, random};
+use crate::spectrum::{balanced_rgb, sample_wavelength};
+use crate::shape::{Shape, ShapeType};
+use crate::transform::Transform;
+use crate::vectors::typed::{Point3, Vector3};
+use crate::world::{Integrator, World};
+
+// paths shorter than this are never terminated by russian roulette
+const MIN_BOUNCES: u32 = 3;
+
+fn max_component(c: Color) -> f64 {
+  return c.r.max(c.g).max(c.b);
+}
+
+impl World {
+  // Unidirectional path tracing. Every vertex gathers direct light from the
+  // point light and emissive shapes (next event estimation), then continues
+  // along one randomly chosen lobe: cosine weighted diffuse, mirror (or
+  // glossy) reflection or (frosted) refraction.
+  pub fn path_trace(&self, r: Ray, max_bounces: u32) -> Color {
+    let mut radiance = Color::new(0., 0., 0.);
+    let mut throughput = Color::new(1., 1., 1.);
+    let mut ray = r;
+    let mut specular_bounce = true;
+    // the object at the previous vertex, when next event estimation there
+    // sampled the emissive shapes
+    let mut sampled_from: Option<u32> = None;
+
+    for bounce in 0..max_bounces + 1 {
+      let xs = self.intersect_world(ray);
+      let hit = xs.hit();
+      if hit.intersections.len() == 0 {
+        radiance = Color::add(radiance, Color::hadamard(throughput, self.background(ray)));
+        break;
+      }
+
+      let comps = prepare_computations(hit.intersections[0].clone(), ray, xs);
+      let material = &comps.object.material;
+      throughput = Color::hadamard(throughput, self.medium_transmittance(&comps));
+      let color = comps.surface_color();
+
+      // emitters that next event estimation sampled at the previous vertex
+      // are only counted when found through a mirror or refraction,
+      // otherwise they would be counted twice. Every other emitter a bounce
+      // finds is counted here, or its light would be lost.
+      let sampled_emitter = match sampled_from {
+        Some(handle) => comps.object.can_sample_surface() && comps.object.handle != handle,
+        None => false,
+      };
+      if material.is_emissive() && (specular_bounce || !sampled_emitter) {
+        radiance = Color::add(radiance, Color::hadamard(throughput, material.emission));
+      }
+
+      // direct light; the ambient term is replaced by the indirect bounces
+      if let Some(light) = self.light {
+        let light = light.at_wavelength(comps.wavelength);
+        let mut options = LightingOptions::new(self.is_shadowed(comps.over_point));
+        options.ambient_scale = 0.;
+        options.color = comps.filtered_color;
+        let direct = lighting_with_options(
+          material,
+          &comps.object,
+          light,
+          comps.point,
+          comps.eyev,
+          comps.normalv,
+          options,
+        );
+        radiance = Color::add(radiance, Color::hadamard(throughput, direct));
+      }
+      let emitted = self.emissive_lighting(&comps);
+      radiance = Color::add(radiance, Color::hadamard(throughput, emitted));
+      sampled_from = if self.emissive_samples > 0 {
+        Some(comps.object.handle)
+      } else {
+        None
+      };
+
+      if bounce == max_bounces {
+        break;
+      }
+
+      // pick the next lobe proportionally to how much light it carries
+      let (reflect_tint, refract_weight) = self.indirect_weights(&comps);
+      let albedo = match material.shading_model {
+        ShadingModel::Phong => Color::mult(color, material.diffuse),
+        ShadingModel::Microfacet => {
+          let fresnel = microfacet::fresnel_at_hit(material, color, &comps);
+          microfacet::diffuse_albedo(material, color, fresnel)
+        }
+      };
+      let diffuse_weight = max_component(albedo);
+      let reflect_weight = max_component(reflect_tint);
+      let total_weight = diffuse_weight + reflect_weight + refract_weight;
+      if total_weight <= 0. {
+        break;
+      }
+
+      let choice = random() * total_weight;
+      if choice < diffuse_weight {
+        let direction = cosine_sample_hemisphere(comps.normalv, random(), random());
+        // brdf * cos / pdf reduces to the albedo for cosine weighted samples
+        throughput = Color::hadamard(
+          throughput,
+          Color::mult(albedo, total_weight / diffuse_weight),
+        );
+        ray = Ray::with_wavelength(
+          comps.over_point,
+          direction,
+          comps.wavelength,
+        );
+        specular_bounce = false;
+      } else if choice < diffuse_weight + reflect_weight {
+        throughput = Color::hadamard(
+          throughput,
+          Color::mult(reflect_tint, total_weight / reflect_weight),
+        );
+        let rough = material.roughness > 0.;
+        let direction = if rough {
+          self.glossy_direction(&comps, random(), random())
+        } else {
+          comps.reflectv
+        };
+        ray = Ray::with_wavelength(
+          comps.over_point,
+          direction,
+          comps.wavelength,
+        );
+        if !rough {
+          ray.differential = comps.reflected_differential();
+        }
+        specular_bounce = !rough;
+      } else {
+        throughput = Color::mult(throughput, total_weight);
+
+        // a white path entering a dispersive material carries on at a single
+        // wavelength, drawn uniformly over the visible range and weighted by
+        // what it adds to each channel
+        let mut band = comps.clone();
+        if band.wavelength.is_none() && band.is_dispersive() {
+          let wavelength = sample_wavelength(0, 1, random());
+          let (n1, n2) = band.indices_at(Some(wavelength));
+          band.n1 = n1;
+          band.n2 = n2;
+          band.wavelength = Some(wavelength);
+          throughput = Color::hadamard(throughput, balanced_rgb(wavelength, self.white_point));
+        }
+
+        let rough = material.roughness > 0.;
+        let refracted = if rough {
+          self.frosted_direction(&band, random(), random())
+        } else {
+          band.eyev.refract(band.normalv, band.n1 / band.n2)
+        };
+        specular_bounce = !rough;
+        ray = match refracted {
+          Some(direction) => Ray::with_wavelength(
+            comps.under_point,
+            direction,
+            band.wavelength,
+          ),
+          // total internal reflection
+          None => Ray::with_wavelength(
+            comps.over_point,
+            comps.reflectv,
+            band.wavelength,
+          ),
+        };
+        if !rough && refracted.is_some() {
+          ray.differential = band.refracted_differential();
+        }
+      }
+
+      // russian roulette keeps long paths unbiased without tracing them all
+      if bounce + 1 >= MIN_BOUNCES {
+        let survival = max_component(throughput).min(0.95);
+        if survival <= 0. || random() >= survival {
+          break;
+        }
+        throughput = Color::div(throughput, survival);
+      }
+    }
+
+    return radiance;
+  }
+}
+
+#[test]
+fn a_path_that_escapes_sees_the_environment() {
+  let mut world = World::new();
+  world.set_integrator(Integrator::PathTracer);
+  world.set_environment(crate::environment::EnvironmentMap::uniform(Color::new(
+    0.3, 0.2, 0.1,
+  )));
+
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let c = world.color_at(r, 5);
+
+  assert_eq!(Color::equals(c, Color::new(0.3, 0.2, 0.1)), true);
+}
+
+#[test]
+fn direct_light_matches_the_whitted_integrator_without_ambient() {
+  let mut world = World::default_world();
+  world.set_integrator(Integrator::PathTracer);
+
+  // indirect bounces off the convex outer sphere all escape into darkness
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let c = world.color_at(r, 5);
+
+  assert_eq!(
+    Color::approx_equals(c, Color::new(0.30066, 0.37583, 0.2255)),
+    true
+  );
+}
+
+#[test]
+fn diffuse_surfaces_bleed_color_onto_each_other() {
+  let mut world = World::new();
+  world.set_integrator(Integrator::PathTracer);
+  world.set_light(PointLight::new(Point3::new(-10., 10., 0.), Color::new(1., 1., 1.)));
+
+  let mut floor = Shape::new(ShapeType::Plane);
+  floor.set_transform(Transform::new().translate(0., -2., 0.).transform);
+  floor.material.color = Color::new(1., 0., 0.);
+  floor.material.specular = 0.;
+  world.add_object(floor);
+
+  world.add_object(Shape::new(ShapeType::Sphere));
+
+  // the underside of the white ball faces away from the light, so all of
+  // its light has bounced off the red floor first
+  let r = Ray::new(Point3::new(0., -1.5, 0.), Vector3::new(0., 1., 0.));
+
+  crate::sampling::seed(27);
+  let mut c = Color::new(0., 0., 0.);
+  for _i in 0..64 {
+    c = Color::add(c, world.path_trace(r, 5));
+  }
+
+  assert_eq!(c.r > 0., true);
+  assert_eq!(c.g, 0.);
+  assert_eq!(c.b, 0.);
+}
+
+#[test]
+fn an_emissive_surface_seen_directly_shows_its_emission() {
+  let mut world = World::new();
+  world.set_integrator(Integrator::PathTracer);
+
+  let mut lamp = Shape::new(ShapeType::Sphere);
+  lamp.material.color = Color::new(0., 0., 0.);
+  lamp.material.emission = Color::new(2., 1., 0.5);
+  world.add_object(lamp);
+
+  let r = Ray::new(Point3::new(0., 0., -5.), Vector3::new(0., 0., 1.));
+  let c = world.color_at(r, 5);
+
+  assert_eq!(Color::equals(c, Color::new(2., 1., 0.5)), true);
+}
+
+#[test]
+fn emitters_found_by_a_bounce_count_without_emitter_sampling() {
+  let mut world = World::new();
+  world.set_integrator(Integrator::PathTracer);
+  world.set_emissive_samples(0);
+
+  // a grey floor inside a large glowing sphere, which is all a bounce off
+  // the floor can see
+  let mut floor = Shape::new(ShapeType::Plane);
+  floor.material.color = Color::new(0.5, 0.5, 0.5);
+  floor.material.diffuse = 1.;
+  floor.material.specular = 0.;
+  world.add_object(floor);
+  let mut dome = Shape::new(ShapeType::Sphere);
+  dome.set_transform(Transform::new().scale(10., 10., 10.).transform);
+  dome.material.color = Color::new(0., 0., 0.);
+  dome.material.emission = Color::new(1., 1., 1.);
+  world.add_object(dome);
+
+  let r = Ray::new(Point3::new(0., 1., 0.), Vector3::new(0., -1., 0.));
+
+  crate::sampling::seed(27);
+  let mut c = Color::new(0., 0., 0.);
+  for _i in 0..16 {
+    c = Color::add(c, world.path_trace(r, 5));
+  }
+  c = Color::div(c, 16.);
+
+  // albedo times the dome's radiance
+  assert_eq!(Color::approx_equals(c, Color::new(0.5, 0.5, 0.5)), true);
+}